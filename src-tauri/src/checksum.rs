@@ -0,0 +1,208 @@
+// Digest verification for downloaded release assets.
+use sha2::{Digest, Sha256, Sha512};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum HashAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            other => Err(format!("不支持的校验算法: {}", other)),
+        }
+    }
+}
+
+/// An expected digest, normalised to lowercase hex.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExpectedDigest {
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+}
+
+impl ExpectedDigest {
+    /// Builds an expected digest from the command arguments. The algorithm defaults to
+    /// SHA-256 and may also be given as a prefix, e.g. GitHub's `sha256:abc...`.
+    pub fn parse(digest: &str, algorithm: Option<&str>) -> Result<Self, String> {
+        let digest = digest.trim();
+        let (prefix, hex) = match digest.split_once(':') {
+            Some((prefix, hex)) => (Some(prefix), hex),
+            None => (None, digest),
+        };
+        let algorithm = match algorithm.or(prefix) {
+            Some(name) => HashAlgorithm::parse(name)?,
+            None => HashAlgorithm::Sha256,
+        };
+
+        let hex = hex.trim().to_ascii_lowercase();
+        let expected_len = match algorithm {
+            HashAlgorithm::Sha256 => 64,
+            HashAlgorithm::Sha512 => 128,
+        };
+        if hex.len() != expected_len || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("无效的校验值: {}", digest));
+        }
+
+        Ok(ExpectedDigest { algorithm, hex })
+    }
+}
+
+impl std::fmt::Display for ExpectedDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.algorithm {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+        };
+        write!(f, "{}:{}", name, self.hex)
+    }
+}
+
+/// Incremental hasher fed while the download streams.
+pub(crate) enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            Hasher::Sha256(_) => HashAlgorithm::Sha256,
+            Hasher::Sha512(_) => HashAlgorithm::Sha512,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Feeds the whole content of `path` into the hasher.
+    pub fn update_from_file(&mut self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buf[..read]);
+        }
+    }
+
+    pub fn finalize_hex(self) -> String {
+        let bytes = match self {
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+        };
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Hashes the file at `path` and compares it against `expected`.
+pub(crate) fn verify_file(path: &Path, expected: &ExpectedDigest) -> Result<(), String> {
+    let mut hasher = Hasher::new(expected.algorithm);
+    hasher.update_from_file(path).map_err(|e| e.to_string())?;
+    check(hasher.finalize_hex(), expected)
+}
+
+/// Compares a computed hex digest against the expected one.
+pub(crate) fn check(actual: String, expected: &ExpectedDigest) -> Result<(), String> {
+    if actual == expected.hex {
+        Ok(())
+    } else {
+        Err(format!("文件校验失败：期望 {}，实际 {}", expected.hex, actual))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const ABC_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                              2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+
+    fn digest_of(algorithm: HashAlgorithm, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(data);
+        hasher.finalize_hex()
+    }
+
+    #[test]
+    fn hashers_match_known_vectors() {
+        assert_eq!(digest_of(HashAlgorithm::Sha256, b"abc"), ABC_SHA256);
+        assert_eq!(digest_of(HashAlgorithm::Sha512, b"abc"), ABC_SHA512);
+    }
+
+    #[test]
+    fn bare_hex_defaults_to_sha256() {
+        let digest =
+            ExpectedDigest::parse(&format!(" {} ", ABC_SHA256.to_uppercase()), None).unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(digest.hex, ABC_SHA256);
+        assert_eq!(digest.to_string(), format!("sha256:{}", ABC_SHA256));
+    }
+
+    #[test]
+    fn prefixes_and_arguments_pick_the_algorithm() {
+        let digest = ExpectedDigest::parse(&format!("sha256:{}", ABC_SHA256), None).unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha256);
+
+        let digest = ExpectedDigest::parse(&format!("SHA-512:{}", ABC_SHA512), None).unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha512);
+        assert_eq!(digest.hex, ABC_SHA512);
+
+        let digest = ExpectedDigest::parse(ABC_SHA512, Some("sha512")).unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha512);
+
+        assert!(ExpectedDigest::parse(&format!("md5:{}", ABC_SHA256), None).is_err());
+    }
+
+    #[test]
+    fn wrong_lengths_and_non_hex_are_rejected() {
+        assert!(ExpectedDigest::parse(&ABC_SHA256[..63], None).is_err());
+        assert!(ExpectedDigest::parse(&format!("{}0", ABC_SHA256), None).is_err());
+        assert!(ExpectedDigest::parse(ABC_SHA256, Some("sha512")).is_err());
+        assert!(ExpectedDigest::parse(&format!("sha512:{}", ABC_SHA256), None).is_err());
+        let not_hex = format!("{}g", &ABC_SHA256[..63]);
+        assert!(ExpectedDigest::parse(&not_hex, None).is_err());
+        assert!(ExpectedDigest::parse("", None).is_err());
+    }
+
+    #[test]
+    fn files_are_verified_against_the_digest() {
+        let path = std::env::temp_dir().join(format!("openstore-checksum-{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        let expected = ExpectedDigest::parse(ABC_SHA256, None).unwrap();
+        assert_eq!(verify_file(&path, &expected), Ok(()));
+        let expected = ExpectedDigest::parse(ABC_SHA512, Some("sha512")).unwrap();
+        assert_eq!(verify_file(&path, &expected), Ok(()));
+
+        std::fs::write(&path, b"abd").unwrap();
+        let expected = ExpectedDigest::parse(ABC_SHA256, None).unwrap();
+        let error = verify_file(&path, &expected).unwrap_err();
+        assert!(error.contains(ABC_SHA256), "{}", error);
+        let actual = digest_of(HashAlgorithm::Sha256, b"abd");
+        assert!(error.contains(&actual), "{}", error);
+
+        let missing = path.with_extension("missing");
+        assert!(verify_file(&missing, &expected).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
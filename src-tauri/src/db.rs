@@ -0,0 +1,216 @@
+// The app's SQLite database, openstore.db in the app config directory. The frontend
+// still reads `data_sources` through tauri-plugin-sql on the same file, but the schema
+// belongs to the backend: it is created and upgraded here, in order, before any window
+// opens. `PRAGMA user_version` holds the number of migrations applied.
+use rusqlite::{Connection, Transaction};
+use std::path::Path;
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Applied in order; append new steps, never edit or reorder released ones.
+const MIGRATIONS: &[Migration] = &[
+    // 1: the tables as the frontend used to create them, kept if they already exist
+    |tx| {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS installed_repos (
+                url TEXT PRIMARY KEY,
+                owner TEXT,
+                repo TEXT,
+                name TEXT,
+                path TEXT,
+                status TEXT,
+                message TEXT,
+                dateInstalled TEXT,
+                description TEXT,
+                primaryLanguage TEXT,
+                stars INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS data_sources (
+                id TEXT PRIMARY KEY,
+                name TEXT,
+                apiEndpointMode TEXT,
+                customEndpoint TEXT,
+                apiVersion TEXT,
+                token TEXT,
+                addedAt TEXT
+            );",
+        )
+    },
+    // 2: `platform` column, which newer frontends may have added already
+    |tx| {
+        if !has_column(tx, "data_sources", "platform")? {
+            tx.execute_batch("ALTER TABLE data_sources ADD COLUMN platform TEXT")?;
+        }
+        Ok(())
+    },
+    // 3: download directories uninstall may delete from
+    |tx| tx.execute_batch("CREATE TABLE IF NOT EXISTS install_roots (path TEXT PRIMARY KEY)"),
+    // 4: the clone options an install was made with, as JSON, for re-cloning it
+    |tx| tx.execute_batch("ALTER TABLE installed_repos ADD COLUMN cloneOptions TEXT"),
+    // 5: roots are only ever GitAppStore folders; drop any other directory registered
+    |tx| {
+        let roots: Vec<String> = {
+            let mut statement = tx.prepare("SELECT path FROM install_roots")?;
+            let rows = statement.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for root in roots {
+            if Path::new(&root).file_name() != Some(crate::installs::INSTALL_DIR_NAME.as_ref()) {
+                tx.execute("DELETE FROM install_roots WHERE path = ?1", [&root])?;
+            }
+        }
+        Ok(())
+    },
+];
+
+fn has_column(tx: &Transaction, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut statement = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = statement.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Brings the schema up to date, each migration in its own transaction so a failed
+/// step leaves the database at the previous version.
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Opens (creating if needed) and migrates the database at `path`.
+pub(crate) fn open(path: &Path) -> Result<Connection, String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut conn = Connection::open(path).map_err(|e| format!("无法打开数据库: {}", e))?;
+    // The frontend's connection may hold the lock for a moment
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+    migrate(&mut conn).map_err(|e| format!("数据库升级失败: {}", e))?;
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn scratch_db(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-db-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("openstore.db")
+    }
+
+    fn version(conn: &Connection) -> usize {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn columns(conn: &mut Connection, table: &str) -> Vec<String> {
+        let tx = conn.transaction().unwrap();
+        let mut statement = tx
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .unwrap();
+        let names = statement
+            .query_map([], |row| row.get::<_, String>(1))
+            .unwrap();
+        names.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn migrates_an_empty_database() {
+        let path = scratch_db("empty");
+        let mut conn = open(&path).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        assert!(columns(&mut conn, "installed_repos").contains(&"cloneOptions".to_string()));
+        assert!(columns(&mut conn, "data_sources").contains(&"platform".to_string()));
+        assert_eq!(columns(&mut conn, "install_roots"), ["path"]);
+        drop(conn);
+
+        // Opening again applies nothing twice
+        let conn = open(&path).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn migrates_a_version_1_database() {
+        let path = scratch_db("v1");
+        {
+            let mut conn = Connection::open(&path).unwrap();
+            let tx = conn.transaction().unwrap();
+            MIGRATIONS[0](&tx).unwrap();
+            // A newer frontend may have added the column itself
+            tx.execute_batch(
+                "ALTER TABLE data_sources ADD COLUMN platform TEXT;
+                 INSERT INTO installed_repos (url, path, status)
+                     VALUES ('https://github.com/o/r', '/x/GitAppStore/r', 'completed');
+                 INSERT INTO data_sources (id, name, platform) VALUES ('s', 'Source', 'gitee');",
+            )
+            .unwrap();
+            tx.pragma_update(None, "user_version", 1).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let conn = open(&path).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+        let (status, options): (String, Option<String>) = conn
+            .query_row(
+                "SELECT status, cloneOptions FROM installed_repos WHERE url = 'https://github.com/o/r'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(status, "completed");
+        assert_eq!(options, None);
+        let platform: String = conn
+            .query_row(
+                "SELECT platform FROM data_sources WHERE id = 's'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(platform, "gitee");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn only_install_folders_stay_roots() {
+        let path = scratch_db("roots");
+        {
+            let mut conn = Connection::open(&path).unwrap();
+            let tx = conn.transaction().unwrap();
+            for migration in &MIGRATIONS[..4] {
+                migration(&tx).unwrap();
+            }
+            tx.execute_batch(
+                "INSERT INTO install_roots (path) VALUES ('/home/user');
+                 INSERT INTO install_roots (path) VALUES ('/home/user/GitAppStore');",
+            )
+            .unwrap();
+            tx.pragma_update(None, "user_version", 4).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let conn = open(&path).unwrap();
+        let roots: Vec<String> = {
+            let mut statement = conn.prepare("SELECT path FROM install_roots").unwrap();
+            let rows = statement.query_map([], |row| row.get(0)).unwrap();
+            rows.map(Result::unwrap).collect()
+        };
+        assert_eq!(roots, ["/home/user/GitAppStore"]);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,851 @@
+// Release asset downloads. Data is streamed into `<file>.part` with a small
+// `<file>.part.json` sidecar so an interrupted download can be resumed with an
+// HTTP Range request, even after the app has been restarted.
+use crate::checksum::{ExpectedDigest, HashAlgorithm, Hasher};
+use crate::download_queue::DownloadQueue;
+use crate::history::{DownloadHistory, HistoryEntry};
+use crate::retry::RetrySettings;
+use crate::throttle::BandwidthLimiter;
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+/// Requested state of an active download, broadcast through its watch channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DownloadControl {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Managed state: maps download URL -> tokio watch Sender carrying its `DownloadControl`
+pub(crate) struct DownloadRegistry(
+    pub Mutex<HashMap<String, tokio::sync::watch::Sender<DownloadControl>>>,
+);
+
+#[derive(serde::Serialize, Clone)]
+pub(crate) struct AssetProgressPayload {
+    url: String,
+    /// Name of the file on disk, which may carry a ` (n)` suffix after a collision.
+    filename: String,
+    downloaded: u64,
+    total: u64,
+    percent: u32,
+    status: String, // "queued", "downloading", "paused", "retrying", "verifying", "completed", "failed", "cancelled"
+    /// Number of the current retry, 0 until a transient failure has been retried.
+    attempt: u32,
+    /// Wait before the next attempt, only set with the "retrying" status.
+    retry_in_ms: Option<u64>,
+}
+
+/// Persisted description of an unfinished download, stored next to the `.part` file.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub(crate) struct PartialDownload {
+    pub url: String,
+    pub filename: String,
+    pub total: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Expected digest as `algorithm:hex`, re-checked when the download finishes.
+    #[serde(default)]
+    pub expected_hash: Option<String>,
+    /// Bytes already on disk; filled in from the `.part` file when listing.
+    #[serde(default)]
+    pub downloaded: u64,
+}
+
+/// Returns `<download dir>/GitAppStore`, creating it if necessary.
+pub(crate) fn store_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let mut dir = app.path().download_dir().map_err(|e| e.to_string())?;
+    dir.push("GitAppStore");
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+    Ok(dir)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub(crate) fn part_path(target: &Path) -> PathBuf {
+    with_suffix(target, ".part")
+}
+
+fn state_path(target: &Path) -> PathBuf {
+    with_suffix(target, ".part.json")
+}
+
+fn load_partial(target: &Path) -> Option<PartialDownload> {
+    let data = std::fs::read(state_path(target)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn save_partial(target: &Path, state: &PartialDownload) -> Result<(), String> {
+    let data = serde_json::to_vec_pretty(state).map_err(|e| e.to_string())?;
+    std::fs::write(state_path(target), data).map_err(|e| e.to_string())
+}
+
+/// Removes the `.part` file and its sidecar for `target`.
+pub(crate) fn discard_partial(target: &Path) {
+    let _ = std::fs::remove_file(part_path(target));
+    let _ = std::fs::remove_file(state_path(target));
+}
+
+/// Lists every unfinished download recorded in `dir`.
+pub(crate) fn list_partials(dir: &Path) -> Vec<PartialDownload> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let filename = name.strip_suffix(".part.json")?;
+            let target = dir.join(filename);
+            let mut state = load_partial(&target)?;
+            state.downloaded = std::fs::metadata(part_path(&target))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            Some(state)
+        })
+        .collect()
+}
+
+/// Finds the unfinished download for `url` in `dir`, if any.
+pub(crate) fn find_partial(dir: &Path, url: &str) -> Option<PartialDownload> {
+    list_partials(dir).into_iter().find(|state| state.url == url)
+}
+
+/// Picks the file `url` is downloaded to: `filename` in `dir`, or a numbered variant when
+/// that name is taken by a finished file, another URL's partial file or a queued download.
+pub(crate) fn unique_target(app: &tauri::AppHandle, dir: &Path, filename: &str, url: &str) -> PathBuf {
+    let queued = app.state::<DownloadQueue>().list();
+    crate::paths::numbered_path(dir, filename, |candidate| {
+        let name = candidate.file_name().map(|n| n.to_string_lossy().to_string());
+        let claimed = queued
+            .iter()
+            .any(|task| task.url != url && Some(&task.filename) == name.as_ref());
+        let partial_free = match load_partial(candidate) {
+            Some(state) => state.url == url,
+            None => !part_path(candidate).exists(),
+        };
+        !claimed && partial_free && !candidate.exists()
+    })
+}
+
+/// Parses `Content-Range: bytes START-END/TOTAL` into `(start, total)`.
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let (start, _) = span.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+fn header_string(response: &reqwest::Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// Tracks and reports the progress of one download as `asset-download-progress` events.
+struct Reporter<'a> {
+    app: &'a tauri::AppHandle,
+    url: &'a str,
+    filename: &'a str,
+    downloaded: u64,
+    total: u64,
+    last_percent: u32,
+    status: String,
+    attempt: u32,
+}
+
+impl Reporter<'_> {
+    fn percent(&self) -> u32 {
+        if self.total > 0 {
+            (self.downloaded as f64 / self.total as f64 * 100.0) as u32
+        } else {
+            0
+        }
+    }
+
+    fn emit(&mut self, status: &str) {
+        self.emit_with_delay(status, None);
+    }
+
+    fn emit_with_delay(&mut self, status: &str, retry_in: Option<Duration>) {
+        self.last_percent = self.percent();
+        self.status = status.to_string();
+        self.app
+            .state::<DownloadQueue>()
+            .update(self.url, status, self.downloaded, self.total);
+        let _ = self.app.emit(
+            "asset-download-progress",
+            AssetProgressPayload {
+                url: self.url.to_string(),
+                filename: self.filename.to_string(),
+                downloaded: self.downloaded,
+                total: self.total,
+                percent: if status == "completed" { 100 } else { self.last_percent },
+                status: status.to_string(),
+                attempt: self.attempt,
+                retry_in_ms: retry_in.map(|delay| delay.as_millis() as u64),
+            },
+        );
+    }
+
+    /// Emits "downloading" only when the integer percentage moved, so the IPC bridge
+    /// is not flooded with one event per chunk.
+    fn tick(&mut self) {
+        if self.percent() > self.last_percent {
+            self.emit("downloading");
+        }
+    }
+}
+
+enum FetchOutcome {
+    Completed(Box<Hasher>),
+    Paused,
+    Cancelled,
+}
+
+/// Why a single request failed. Transient failures are retried with backoff.
+enum FetchError {
+    Transient {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    Fatal(String),
+}
+
+impl From<String> for FetchError {
+    fn from(message: String) -> Self {
+        FetchError::Fatal(message)
+    }
+}
+
+impl FetchError {
+    fn network(e: reqwest::Error) -> Self {
+        FetchError::Transient {
+            message: e.to_string(),
+            retry_after: None,
+        }
+    }
+}
+
+/// Downloads `url` into `target_path`, resuming from an existing `.part` file when
+/// the server honours Range requests. Emits `asset-download-progress` events and
+/// keeps the partial file on failure so `resume_download` can pick it up later.
+/// The file is hashed while streaming (SHA-256 unless `expected` says otherwise) and,
+/// when `expected` is given, only moved into place if the digest matches. Every
+/// finished, failed or cancelled download is appended to the `DownloadHistory`.
+/// The download waits in the `DownloadQueue` (ordered by `priority`, higher first)
+/// until a concurrency slot is free, and `rate_limit` (bytes/sec) caps this task on
+/// top of the global `BandwidthLimiter` limit.
+pub(crate) async fn run_download(
+    app: &tauri::AppHandle,
+    url: &str,
+    filename: &str,
+    target_path: &Path,
+    expected: Option<&ExpectedDigest>,
+    priority: i32,
+    rate_limit: Option<u64>,
+) -> Result<String, String> {
+    // Register a control channel for this URL so it can be paused, resumed or cancelled.
+    // Downloads are keyed by URL, so a second one of the same URL would take over the
+    // first one's channel, queue slot and .part file
+    let (control_tx, control_rx) = tokio::sync::watch::channel(DownloadControl::Running);
+    {
+        let registry = app.state::<DownloadRegistry>();
+        let mut map = registry.0.lock().unwrap();
+        if map.contains_key(url) {
+            return Err("该文件已在下载中".to_string());
+        }
+        map.insert(url.to_string(), control_tx);
+    }
+    app.state::<DownloadQueue>().enqueue(url, filename, priority);
+    if let Some(rate_limit) = rate_limit {
+        app.state::<BandwidthLimiter>().set_task_limit(url, rate_limit);
+    }
+
+    let started_at = crate::dates::unix_now();
+    let mut reporter = Reporter {
+        app,
+        url,
+        filename,
+        downloaded: 0,
+        total: 0,
+        last_percent: 0,
+        status: String::new(),
+        attempt: 0,
+    };
+    let result = drive_download(&mut reporter, filename, target_path, expected, control_rx).await;
+
+    let (status, hash, error) = match &result {
+        Ok((_, digest)) => ("completed".to_string(), Some(digest.clone()), None),
+        Err(e) if reporter.status == "cancelled" => (reporter.status.clone(), None, Some(e.clone())),
+        Err(e) => ("failed".to_string(), None, Some(e.clone())),
+    };
+    let _ = app.state::<DownloadHistory>().append(&HistoryEntry {
+        url: url.to_string(),
+        filename: filename.to_string(),
+        path: target_path.to_string_lossy().to_string(),
+        size: reporter.downloaded,
+        hash,
+        started_at,
+        finished_at: crate::dates::unix_now(),
+        status,
+        error,
+    });
+
+    {
+        let registry = app.state::<DownloadRegistry>();
+        let mut map = registry.0.lock().unwrap();
+        map.remove(url);
+    }
+    app.state::<DownloadQueue>().remove(url);
+    // A limit set with `set_download_rate_limit` outlives the download
+    if let Some(rate_limit) = rate_limit {
+        app.state::<BandwidthLimiter>().clear_task_limit(url, rate_limit);
+    }
+
+    result.map(|(path, _)| path)
+}
+
+/// Waits until the queue grants this download a slot. Returns `Ok(false)` if it was
+/// cancelled while waiting; a paused download keeps waiting without blocking others.
+async fn wait_for_slot(
+    reporter: &mut Reporter<'_>,
+    control_rx: &mut tokio::sync::watch::Receiver<DownloadControl>,
+) -> Result<bool, String> {
+    let app = reporter.app;
+    let queue = app.state::<DownloadQueue>();
+    let mut last_status = "";
+
+    loop {
+        let queue_changed = queue.changed();
+        let control = *control_rx.borrow_and_update();
+        let status = match control {
+            DownloadControl::Cancelled => return Ok(false),
+            DownloadControl::Paused => "paused",
+            DownloadControl::Running => {
+                if queue.try_start(reporter.url) {
+                    return Ok(true);
+                }
+                "queued"
+            }
+        };
+        if status != last_status {
+            reporter.emit(status);
+            last_status = status;
+        }
+
+        tokio::select! {
+            _ = queue_changed => {}
+            changed = control_rx.changed() => {
+                if changed.is_err() {
+                    return Err("下载任务已失效".to_string());
+                }
+            }
+        }
+    }
+}
+
+async fn drive_download(
+    reporter: &mut Reporter<'_>,
+    filename: &str,
+    target_path: &Path,
+    expected: Option<&ExpectedDigest>,
+    mut control_rx: tokio::sync::watch::Receiver<DownloadControl>,
+) -> Result<(String, String), String> {
+    let client = crate::http_client()?;
+    let policy = *reporter.app.state::<RetrySettings>().0.lock().unwrap();
+    // Bytes on disk at the previous failure; progress since then resets the attempts
+    let mut failed_at = 0;
+
+    let hasher = loop {
+        if !wait_for_slot(reporter, &mut control_rx).await? {
+            discard_partial(target_path);
+            reporter.emit("cancelled");
+            return Err("已取消下载".to_string());
+        }
+
+        let outcome =
+            fetch_once(&client, reporter, filename, target_path, expected, &control_rx).await;
+        match outcome {
+            Ok(FetchOutcome::Completed(hasher)) => break hasher,
+            Ok(FetchOutcome::Cancelled) => {
+                discard_partial(target_path);
+                reporter.emit("cancelled");
+                return Err("已取消下载".to_string());
+            }
+            Ok(FetchOutcome::Paused) => {
+                // The connection has been dropped; free the slot until resumed
+                reporter.app.state::<DownloadQueue>().release(reporter.url);
+            }
+            Err(FetchError::Transient {
+                message,
+                retry_after,
+            }) => {
+                if reporter.downloaded > failed_at {
+                    reporter.attempt = 0;
+                }
+                failed_at = reporter.downloaded;
+                if reporter.attempt >= policy.max_retries {
+                    reporter.emit("failed");
+                    return Err(if policy.max_retries > 0 {
+                        format!("{}（已重试 {} 次）", message, policy.max_retries)
+                    } else {
+                        message
+                    });
+                }
+
+                reporter.attempt += 1;
+                let Some(delay) = policy.delay(reporter.attempt, retry_after) else {
+                    reporter.emit("failed");
+                    return Err(format!(
+                        "{}（服务器要求 {} 秒后重试，超过最长重试间隔）",
+                        message,
+                        retry_after.unwrap_or_default().as_secs()
+                    ));
+                };
+                reporter.emit_with_delay("retrying", Some(delay));
+
+                // Let other downloads use the slot while waiting; the next attempt
+                // resumes from the bytes already in the .part file
+                reporter.app.state::<DownloadQueue>().release(reporter.url);
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = control_rx.changed() => {}
+                }
+            }
+            Err(FetchError::Fatal(e)) => {
+                // Keep the .part file so the download can be resumed later
+                reporter.emit("failed");
+                return Err(e);
+            }
+        }
+    };
+
+    let algorithm = hasher.algorithm();
+    let digest = hasher.finalize_hex();
+    if let Some(expected) = expected {
+        reporter.emit("verifying");
+        if let Err(e) = crate::checksum::check(digest.clone(), expected) {
+            // A corrupted or tampered file must never reach the target path
+            discard_partial(target_path);
+            reporter.emit("failed");
+            return Err(e);
+        }
+    }
+
+    std::fs::rename(part_path(target_path), target_path).map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(state_path(target_path));
+
+    // Final emit
+    reporter.emit("completed");
+
+    let digest = ExpectedDigest {
+        algorithm,
+        hex: digest,
+    };
+    Ok((target_path.to_string_lossy().to_string(), digest.to_string()))
+}
+
+/// Response of a (possibly resumed) request, with the `.part.json` sidecar already
+/// describing it.
+struct StartedFetch {
+    response: reqwest::Response,
+    /// Whether the body continues the `.part` file rather than replacing it.
+    resumed: bool,
+    /// Bytes already on disk that the body follows on from.
+    downloaded: u64,
+    total: u64,
+}
+
+/// Sends the request for `url`, asking only for the missing bytes when a `.part` file
+/// recorded for the same URL exists, and records the response in the sidecar.
+async fn start_fetch(
+    client: &reqwest::Client,
+    url: &str,
+    filename: &str,
+    target_path: &Path,
+    expected: Option<&ExpectedDigest>,
+) -> Result<StartedFetch, FetchError> {
+    let part = part_path(target_path);
+
+    // Only trust a partial file that was recorded for this same URL
+    let previous = load_partial(target_path).filter(|state| state.url == url);
+    if previous.is_none() {
+        discard_partial(target_path);
+    }
+    let offset = match previous {
+        Some(_) => std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+        let validator = previous
+            .as_ref()
+            .and_then(|state| state.etag.clone().or_else(|| state.last_modified.clone()));
+        if let Some(validator) = validator {
+            request = request.header(IF_RANGE, validator);
+        }
+    }
+    let mut response = request.send().await.map_err(FetchError::network)?;
+
+    // 206 from the expected offset means we can append. A 416, or a 206 starting
+    // somewhere else, means the partial file is stale: restart from zero. A plain
+    // 200 is a full body and is handled as a restart below.
+    let range_start = header_string(&response, CONTENT_RANGE)
+        .and_then(|value| parse_content_range(&value));
+    let resumed = offset > 0
+        && response.status() == StatusCode::PARTIAL_CONTENT
+        && range_start.map(|(start, _)| start) == Some(offset);
+    if offset > 0
+        && (response.status() == StatusCode::RANGE_NOT_SATISFIABLE
+            || (response.status() == StatusCode::PARTIAL_CONTENT && !resumed))
+    {
+        response = client.get(url).send().await.map_err(FetchError::network)?;
+    }
+
+    if !response.status().is_success() {
+        let message = format!("HTTP 错误，状态码: {}", response.status());
+        return Err(
+            if crate::retry::is_transient(response.status(), response.headers()) {
+                FetchError::Transient {
+                    message,
+                    retry_after: crate::retry::server_delay(response.headers()),
+                }
+            } else {
+                FetchError::Fatal(message)
+            },
+        );
+    }
+
+    let downloaded = if resumed { offset } else { 0 };
+    let total = match range_start {
+        Some((_, Some(total))) if resumed => total,
+        _ => response
+            .content_length()
+            .map(|len| len + downloaded)
+            .unwrap_or(0),
+    };
+
+    save_partial(
+        target_path,
+        &PartialDownload {
+            url: url.to_string(),
+            filename: filename.to_string(),
+            total,
+            etag: header_string(&response, ETAG),
+            last_modified: header_string(&response, LAST_MODIFIED),
+            expected_hash: expected.map(|digest| digest.to_string()),
+            downloaded: 0,
+        },
+    )?;
+
+    Ok(StartedFetch {
+        response,
+        resumed,
+        downloaded,
+        total,
+    })
+}
+
+/// Performs one HTTP request, appending to the `.part` file until the body ends or the
+/// control channel asks to pause or cancel. Pausing drops the connection; the next call
+/// picks up from the bytes already on disk.
+async fn fetch_once(
+    client: &reqwest::Client,
+    reporter: &mut Reporter<'_>,
+    filename: &str,
+    target_path: &Path,
+    expected: Option<&ExpectedDigest>,
+    control_rx: &tokio::sync::watch::Receiver<DownloadControl>,
+) -> Result<FetchOutcome, FetchError> {
+    let url = reporter.url;
+    let part = part_path(target_path);
+    let StartedFetch {
+        response,
+        resumed,
+        downloaded,
+        total,
+    } = start_fetch(client, url, filename, target_path, expected).await?;
+    reporter.downloaded = downloaded;
+    reporter.total = total;
+
+    // When appending, the bytes already on disk have to be hashed first
+    let algorithm = expected.map_or(HashAlgorithm::Sha256, |digest| digest.algorithm);
+    let mut hasher = if resumed {
+        let part = part.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update_from_file(&part).map(|_| hasher)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?
+    } else {
+        Hasher::new(algorithm)
+    };
+
+    let mut file = if resumed {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&part)
+            .map_err(|e| e.to_string())?
+    } else {
+        std::fs::File::create(&part).map_err(|e| e.to_string())?
+    };
+    let mut stream = response.bytes_stream();
+    let app = reporter.app;
+    let limiter = app.state::<BandwidthLimiter>();
+    let throttle = limiter.task(url);
+
+    reporter.emit("downloading");
+
+    while let Some(chunk_result) = stream.next().await {
+        // Check for pause/cancel before processing each chunk
+        let control = *control_rx.borrow();
+        match control {
+            DownloadControl::Running => {}
+            DownloadControl::Paused => {
+                file.flush().map_err(|e| e.to_string())?;
+                return Ok(FetchOutcome::Paused);
+            }
+            DownloadControl::Cancelled => return Ok(FetchOutcome::Cancelled),
+        }
+
+        let chunk = chunk_result.map_err(FetchError::network)?;
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        hasher.update(&chunk);
+        reporter.downloaded += chunk.len() as u64;
+        reporter.tick();
+
+        // Stay within the bandwidth limits, but react to pause/cancel while waiting
+        let mut control = control_rx.clone();
+        tokio::select! {
+            _ = throttle.wait(chunk.len()) => {}
+            _ = control.changed() => {}
+        }
+    }
+
+    file.flush().map_err(|e| e.to_string())?;
+    Ok(FetchOutcome::Completed(Box::new(hasher)))
+}
+
+/// Sets the control state of an active download. Returns `false` if `url` is not running.
+pub(crate) fn set_control(app: &tauri::AppHandle, url: &str, control: DownloadControl) -> bool {
+    let registry = app.state::<DownloadRegistry>();
+    let map = registry.0.lock().unwrap();
+    match map.get(url) {
+        Some(tx) => {
+            tx.send_replace(control);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "openstore-download-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Answers one connection per entry of `responses` (status line and headers, body)
+    /// on a local port. Returns the URL and the requests as received, lowercased.
+    fn serve(
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> (String, std::sync::mpsc::Receiver<String>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/asset.bin", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for (head, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).unwrap();
+                let _ = tx.send(String::from_utf8_lossy(&request[..read]).to_lowercase());
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    head,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    /// Leaves `content` in the `.part` file of `target` as an earlier attempt would.
+    fn leave_partial(target: &Path, url: &str, content: &str, etag: Option<&str>) {
+        std::fs::write(part_path(target), content).unwrap();
+        let state = PartialDownload {
+            url: url.to_string(),
+            filename: "asset.bin".to_string(),
+            total: 11,
+            etag: etag.map(|etag| etag.to_string()),
+            last_modified: None,
+            expected_hash: None,
+            downloaded: 0,
+        };
+        save_partial(target, &state).unwrap();
+    }
+
+    /// Starts the fetch and returns `(resumed, downloaded, total, body)`.
+    fn fetch(url: &str, target: &Path) -> Result<(bool, u64, u64, String), String> {
+        tauri::async_runtime::block_on(async {
+            let client = reqwest::Client::new();
+            match start_fetch(&client, url, "asset.bin", target, None).await {
+                Ok(started) => {
+                    let body = started.response.text().await.map_err(|e| e.to_string())?;
+                    Ok((started.resumed, started.downloaded, started.total, body))
+                }
+                Err(FetchError::Transient { message, .. }) => Err(format!("重试: {}", message)),
+                Err(FetchError::Fatal(message)) => Err(message),
+            }
+        })
+    }
+
+    #[test]
+    fn partial_files_resume_with_a_range_request() {
+        let dir = scratch_dir("resume");
+        let target = dir.join("asset.bin");
+        let (url, requests) = serve(vec![(
+            "206 Partial Content\r\nContent-Range: bytes 6-10/11\r\nETag: \"v1\"",
+            "world",
+        )]);
+        leave_partial(&target, &url, "hello ", Some("\"v1\""));
+
+        assert_eq!(
+            fetch(&url, &target).unwrap(),
+            (true, 6, 11, "world".to_string())
+        );
+        let request = requests.recv().unwrap();
+        assert!(request.contains("range: bytes=6-\r\n"), "{}", request);
+        assert!(request.contains("if-range: \"v1\"\r\n"), "{}", request);
+        // The partial file is kept for the body to be appended to
+        assert_eq!(
+            std::fs::read_to_string(part_path(&target)).unwrap(),
+            "hello "
+        );
+        let state = load_partial(&target).unwrap();
+        assert_eq!((state.url, state.total), (url, 11));
+        assert_eq!(state.etag.as_deref(), Some("\"v1\""));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_restart_from_zero() {
+        let dir = scratch_dir("416");
+        let target = dir.join("asset.bin");
+        let (url, requests) = serve(vec![
+            ("416 Range Not Satisfiable\r\nContent-Range: bytes */5", ""),
+            ("200 OK\r\nETag: \"v2\"", "fresh"),
+        ]);
+        leave_partial(&target, &url, "stale bytes", Some("\"v1\""));
+
+        assert_eq!(
+            fetch(&url, &target).unwrap(),
+            (false, 0, 5, "fresh".to_string())
+        );
+        assert!(requests.recv().unwrap().contains("range: bytes=11-"));
+        assert!(!requests.recv().unwrap().contains("range:"));
+        let state = load_partial(&target).unwrap();
+        assert_eq!((state.total, state.etag.as_deref()), (5, Some("\"v2\"")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn changed_files_are_downloaded_again() {
+        let dir = scratch_dir("changed");
+        let target = dir.join("asset.bin");
+        // If-Range no longer matches, so the server sends the whole new file
+        let (url, requests) = serve(vec![(
+            "200 OK\r\nLast-Modified: Sat, 17 Oct 2026 08:00:00 GMT",
+            "a newer file",
+        )]);
+        leave_partial(&target, &url, "hello ", Some("\"v1\""));
+
+        assert_eq!(
+            fetch(&url, &target).unwrap(),
+            (false, 0, 12, "a newer file".to_string())
+        );
+        assert!(requests.recv().unwrap().contains("if-range: \"v1\""));
+        let state = load_partial(&target).unwrap();
+        assert_eq!(state.etag, None);
+        assert_eq!(
+            state.last_modified.as_deref(),
+            Some("Sat, 17 Oct 2026 08:00:00 GMT")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn partial_files_of_another_url_are_discarded() {
+        let dir = scratch_dir("other");
+        let target = dir.join("asset.bin");
+        let (url, requests) = serve(vec![("200 OK", "new")]);
+        leave_partial(&target, "https://example.com/other.bin", "old", None);
+
+        assert_eq!(
+            fetch(&url, &target).unwrap(),
+            (false, 0, 3, "new".to_string())
+        );
+        assert!(!requests.recv().unwrap().contains("range:"));
+        assert!(!part_path(&target).exists());
+        assert_eq!(load_partial(&target).unwrap().url, url);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn server_errors_are_retried_and_client_errors_are_not() {
+        let dir = scratch_dir("errors");
+        let target = dir.join("asset.bin");
+        let (url, _) = serve(vec![("503 Service Unavailable", ""), ("404 Not Found", "")]);
+
+        assert!(fetch(&url, &target).unwrap_err().starts_with("重试: "));
+        assert_eq!(
+            fetch(&url, &target).unwrap_err(),
+            "HTTP 错误，状态码: 404 Not Found"
+        );
+        // Nothing is recorded for a request that never produced a body
+        assert!(load_partial(&target).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn content_ranges_give_the_start_and_total() {
+        assert_eq!(parse_content_range("bytes 6-10/11"), Some((6, Some(11))));
+        assert_eq!(parse_content_range("bytes 0-99/*"), Some((0, None)));
+        assert_eq!(parse_content_range("bytes */11"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+}
@@ -0,0 +1,312 @@
+// Download queue: bounds how many release assets stream at once and decides which
+// waiting download gets the next free slot.
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+pub(crate) const DEFAULT_MAX_CONCURRENT: usize = 3;
+
+#[derive(serde::Serialize, Clone)]
+pub(crate) struct DownloadTask {
+    pub url: String,
+    pub filename: String,
+    pub priority: i32,
+    pub status: String, // "queued", "downloading", "paused", "verifying", ...
+    pub downloaded: u64,
+    pub total: u64,
+    /// Whether the task currently holds one of the concurrency slots.
+    pub running: bool,
+}
+
+struct QueueState {
+    max_concurrent: usize,
+    /// Every known task, in queue order. Running tasks keep their place.
+    tasks: Vec<DownloadTask>,
+}
+
+/// Managed state shared by all downloads.
+pub(crate) struct DownloadQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl DownloadQueue {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                max_concurrent: max_concurrent.max(1),
+                tasks: Vec::new(),
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Position where a waiting task of `priority` belongs: after every waiting task of
+    /// equal or higher priority, so ties stay first-come first-served.
+    fn insert_position(tasks: &[DownloadTask], priority: i32) -> usize {
+        tasks
+            .iter()
+            .position(|task| !task.running && task.priority < priority)
+            .unwrap_or(tasks.len())
+    }
+
+    /// Adds a download to the queue in the "queued" state.
+    pub fn enqueue(&self, url: &str, filename: &str, priority: i32) {
+        let mut state = self.state.lock().unwrap();
+        state.tasks.retain(|task| task.url != url);
+        let index = Self::insert_position(&state.tasks, priority);
+        state.tasks.insert(
+            index,
+            DownloadTask {
+                url: url.to_string(),
+                filename: filename.to_string(),
+                priority,
+                status: "queued".to_string(),
+                downloaded: 0,
+                total: 0,
+                running: false,
+            },
+        );
+    }
+
+    /// Claims a slot for `url` if one is free and no waiting task is ahead of it.
+    /// Paused tasks keep their position but never block the tasks behind them.
+    pub fn try_start(&self, url: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let running = state.tasks.iter().filter(|task| task.running).count();
+        if running >= state.max_concurrent {
+            return false;
+        }
+
+        let next = state
+            .tasks
+            .iter_mut()
+            .find(|task| !task.running && (task.status != "paused" || task.url == url));
+        match next {
+            Some(task) if task.url == url => {
+                task.running = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gives up the slot held by `url` (e.g. on pause) and wakes waiting downloads.
+    pub fn release(&self, url: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(task) = state.tasks.iter_mut().find(|task| task.url == url) {
+                task.running = false;
+            }
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// Drops `url` from the queue once it has finished, failed or been cancelled.
+    pub fn remove(&self, url: &str) {
+        self.state.lock().unwrap().tasks.retain(|task| task.url != url);
+        self.notify.notify_waiters();
+    }
+
+    /// Records the latest progress of `url` for `list_downloads`.
+    pub fn update(&self, url: &str, status: &str, downloaded: u64, total: u64) {
+        let pause_changed = {
+            let mut state = self.state.lock().unwrap();
+            match state.tasks.iter_mut().find(|task| task.url == url) {
+                Some(task) => {
+                    let pause_changed = (task.status == "paused") != (status == "paused");
+                    task.status = status.to_string();
+                    task.downloaded = downloaded;
+                    task.total = total;
+                    pause_changed
+                }
+                None => false,
+            }
+        };
+        // Pausing or resuming a waiting task can unblock the ones queued behind it
+        if pause_changed {
+            self.notify.notify_waiters();
+        }
+    }
+
+    /// Changes the priority of a waiting download and re-sorts it into the queue.
+    pub fn set_priority(&self, url: &str, priority: i32) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let index = state
+                .tasks
+                .iter()
+                .position(|task| task.url == url)
+                .ok_or_else(|| "未找到对应的下载任务".to_string())?;
+
+            let mut task = state.tasks.remove(index);
+            task.priority = priority;
+            if task.running {
+                state.tasks.insert(index, task);
+            } else {
+                let index = Self::insert_position(&state.tasks, priority);
+                state.tasks.insert(index, task);
+            }
+        }
+        self.notify.notify_waiters();
+        Ok(())
+    }
+
+    /// Moves `url` to `position` in the queue (clamped to the end of the list).
+    pub fn move_to(&self, url: &str, position: usize) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let index = state
+                .tasks
+                .iter()
+                .position(|task| task.url == url)
+                .ok_or_else(|| "未找到对应的下载任务".to_string())?;
+
+            let task = state.tasks.remove(index);
+            let position = position.min(state.tasks.len());
+            state.tasks.insert(position, task);
+        }
+        self.notify.notify_waiters();
+        Ok(())
+    }
+
+    pub fn set_max_concurrent(&self, max_concurrent: usize) {
+        self.state.lock().unwrap().max_concurrent = max_concurrent.max(1);
+        self.notify.notify_waiters();
+    }
+
+    pub fn list(&self) -> Vec<DownloadTask> {
+        self.state.lock().unwrap().tasks.clone()
+    }
+
+    /// Future that resolves the next time the queue changes. Create it *before*
+    /// calling `try_start` so a release in between is not missed.
+    pub fn changed(&self) -> tokio::sync::futures::Notified<'_> {
+        self.notify.notified()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(queue: &DownloadQueue) -> Vec<String> {
+        queue.list().into_iter().map(|task| task.url).collect()
+    }
+
+    #[test]
+    fn higher_priorities_wait_ahead_and_ties_keep_their_order() {
+        let queue = DownloadQueue::new(1);
+        queue.enqueue("a", "a.zip", 0);
+        queue.enqueue("b", "b.zip", 5);
+        queue.enqueue("c", "c.zip", 0);
+        queue.enqueue("d", "d.zip", 5);
+        assert_eq!(urls(&queue), ["b", "d", "a", "c"]);
+
+        // Re-enqueueing replaces the old entry
+        queue.enqueue("a", "a.zip", 10);
+        assert_eq!(urls(&queue), ["a", "b", "d", "c"]);
+
+        queue.set_priority("c", 7).unwrap();
+        assert_eq!(urls(&queue), ["a", "c", "b", "d"]);
+        assert!(queue.set_priority("missing", 1).is_err());
+    }
+
+    #[test]
+    fn running_tasks_keep_their_place() {
+        let queue = DownloadQueue::new(1);
+        queue.enqueue("a", "a.zip", 0);
+        assert!(queue.try_start("a"));
+        queue.enqueue("b", "b.zip", 9);
+        assert_eq!(urls(&queue), ["a", "b"]);
+        queue.set_priority("a", -1).unwrap();
+        assert_eq!(urls(&queue), ["a", "b"]);
+    }
+
+    #[test]
+    fn slots_are_bounded_by_the_limit() {
+        let queue = DownloadQueue::new(2);
+        for url in ["a", "b", "c"] {
+            queue.enqueue(url, url, 0);
+        }
+        // Only the first waiting task may take a slot
+        assert!(!queue.try_start("b"));
+        assert!(queue.try_start("a"));
+        assert!(queue.try_start("b"));
+        assert!(!queue.try_start("c"));
+
+        // A released task is still ahead of the ones that never started
+        queue.release("a");
+        assert!(!queue.try_start("c"));
+        assert!(queue.try_start("a"));
+        assert_eq!(queue.list().iter().filter(|task| task.running).count(), 2);
+
+        queue.remove("b");
+        assert!(queue.try_start("c"));
+        assert_eq!(urls(&queue), ["a", "c"]);
+    }
+
+    #[test]
+    fn paused_tasks_do_not_block_the_queue() {
+        let queue = DownloadQueue::new(1);
+        queue.enqueue("a", "a.zip", 0);
+        queue.enqueue("b", "b.zip", 0);
+        queue.update("a", "paused", 10, 100);
+        assert!(queue.try_start("b"));
+        queue.release("b");
+
+        let paused = &queue.list()[0];
+        assert_eq!(
+            (paused.status.as_str(), paused.downloaded, paused.total),
+            ("paused", 10, 100)
+        );
+        // A resumed task may still take the slot itself
+        assert!(queue.try_start("a"));
+    }
+
+    #[test]
+    fn tasks_can_be_moved_within_the_queue() {
+        let queue = DownloadQueue::new(1);
+        for url in ["a", "b", "c"] {
+            queue.enqueue(url, url, 0);
+        }
+        queue.move_to("c", 0).unwrap();
+        assert_eq!(urls(&queue), ["c", "a", "b"]);
+        queue.move_to("c", 99).unwrap();
+        assert_eq!(urls(&queue), ["a", "b", "c"]);
+        queue.move_to("a", 1).unwrap();
+        assert_eq!(urls(&queue), ["b", "a", "c"]);
+        assert!(queue.move_to("missing", 0).is_err());
+        assert!(queue.try_start("b"));
+    }
+
+    #[test]
+    fn the_limit_can_change_while_downloads_run() {
+        let queue = DownloadQueue::new(0);
+        for url in ["a", "b", "c"] {
+            queue.enqueue(url, url, 0);
+        }
+        assert!(queue.try_start("a"));
+        assert!(!queue.try_start("b"));
+
+        queue.set_max_concurrent(3);
+        assert!(queue.try_start("b"));
+        assert!(queue.try_start("c"));
+
+        // Lowering the limit never stops running tasks, it only holds back new ones
+        queue.set_max_concurrent(1);
+        queue.release("c");
+        assert!(!queue.try_start("c"));
+        queue.release("a");
+        queue.release("b");
+        assert!(queue.try_start("a"));
+    }
+
+    #[test]
+    fn changes_wake_waiting_downloads() {
+        let queue = DownloadQueue::new(1);
+        queue.enqueue("a", "a.zip", 0);
+        let changed = queue.changed();
+        queue.release("a");
+        tauri::async_runtime::block_on(changed);
+    }
+}
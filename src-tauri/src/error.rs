@@ -0,0 +1,41 @@
+// Error type for commands whose failures the frontend may want to tell apart. Plain
+// messages still serialise as a bare string, so existing `catch` handlers keep working.
+use crate::paths::InvalidName;
+use crate::updates::UpdateError;
+
+#[derive(Debug, serde::Serialize)]
+#[serde(untagged)]
+pub(crate) enum CommandError {
+    InvalidName(InvalidName),
+    Update(UpdateError),
+    Message(String),
+}
+
+impl CommandError {
+    /// Human-readable message of any variant.
+    pub fn message(&self) -> &str {
+        match self {
+            CommandError::InvalidName(error) => &error.message,
+            CommandError::Update(error) => &error.message,
+            CommandError::Message(message) => message,
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Message(message)
+    }
+}
+
+impl From<InvalidName> for CommandError {
+    fn from(error: InvalidName) -> Self {
+        CommandError::InvalidName(error)
+    }
+}
+
+impl From<UpdateError> for CommandError {
+    fn from(error: UpdateError) -> Self {
+        CommandError::Update(error)
+    }
+}
@@ -0,0 +1,998 @@
+// Code hosting platforms ("forges"). Each `Forge` knows how repository URLs on its host
+// look, where its API lives, how archives are requested and how tokens are sent, so the
+// rest of the backend does not have to special-case GitHub or Gitee by string matching.
+use crate::updates::CommitSummary;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ForgeKind {
+    GitHub,
+    Gitee,
+    GitLab,
+    /// Gitea and its fork Forgejo (e.g. Codeberg) share the same API.
+    Gitea,
+}
+
+/// A repository on a forge. `owner` may contain `/` for GitLab subgroups.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RepoRef {
+    pub owner: String,
+    pub name: String,
+}
+
+impl RepoRef {
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.owner, self.name)
+    }
+}
+
+pub(crate) trait Forge: Send + Sync {
+    /// Web origin of the instance, e.g. `https://gitlab.example.com`.
+    fn base_url(&self) -> &str;
+
+    /// Root of the REST API, e.g. `https://gitlab.example.com/api/v4`.
+    fn api_base(&self) -> &str;
+
+    /// Splits a repository URL on this instance into owner and name.
+    fn parse_repo(&self, url: &str) -> Option<RepoRef> {
+        let mut segments = repo_segments(self.base_url(), url)?.into_iter();
+        let owner = segments.next()?;
+        let name = segments.next()?;
+        Some(RepoRef { owner, name })
+    }
+
+    /// API endpoint describing the repository, used to look up its default branch.
+    fn repo_api_url(&self, repo: &RepoRef) -> String {
+        api_url(self.api_base(), &["repos", &repo.owner, &repo.name], &[])
+    }
+
+    /// API endpoint for the commit `reference` (a branch, tag or SHA) points to.
+    fn commit_api_url(&self, repo: &RepoRef, reference: &str) -> String {
+        api_url(
+            self.api_base(),
+            &["repos", &repo.owner, &repo.name, "commits", reference],
+            &[],
+        )
+    }
+
+    /// API endpoint listing the commits reachable from `head` but not from `base`.
+    fn compare_api_url(&self, repo: &RepoRef, base: &str, head: &str) -> String {
+        api_url(
+            self.api_base(),
+            &["repos", &repo.owner, &repo.name, "compare", &format!("{}...{}", base, head)],
+            &[],
+        )
+    }
+
+    /// API endpoint for the branch `name`, which is missing when `name` is a tag.
+    fn branch_api_url(&self, repo: &RepoRef, name: &str) -> String {
+        api_url(
+            self.api_base(),
+            &["repos", &repo.owner, &repo.name, "branches", name],
+            &[],
+        )
+    }
+
+    /// API endpoint listing the first page of releases, newest first.
+    fn releases_api_url(&self, repo: &RepoRef) -> String {
+        api_url(
+            self.api_base(),
+            &["repos", &repo.owner, &repo.name, "releases"],
+            &[("per_page", RELEASE_PAGE_SIZE)],
+        )
+    }
+
+    /// URL of a zip archive of `reference`, or of the default branch when `None`.
+    /// Returns `None` if this forge needs an explicit reference.
+    fn archive_url(&self, repo: &RepoRef, reference: Option<&str>) -> Option<String>;
+
+    /// Request for the raw content of the file at `path`, on `reference` or the default
+    /// branch.
+    fn raw_file_request(
+        &self,
+        client: &reqwest::Client,
+        repo: &RepoRef,
+        path: &str,
+        reference: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        let mut segments = vec!["repos", &repo.owner, &repo.name, "raw"];
+        segments.extend(path.split('/'));
+        let request = client.get(api_url(self.api_base(), &segments, &[]));
+        match reference {
+            Some(reference) => request.query(&[("ref", reference)]),
+            None => request,
+        }
+    }
+
+    /// Adds `token` to an API or archive request the way this forge expects it.
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder;
+}
+
+/// Releases fetched when looking for a newer one than the downloaded release.
+const RELEASE_PAGE_SIZE: &str = "30";
+
+/// `base` followed by `segments` and the `query` pairs, all percent-encoded. Each
+/// segment stays a single path segment (a `/` in it is encoded too), so refs and paths
+/// containing `?`, `#`, `&` or spaces cannot change the URL's meaning. Bases that do
+/// not parse are refused when a forge is added and are returned unchanged.
+fn api_url(base: &str, segments: &[&str], query: &[(&str, &str)]) -> String {
+    let Ok(mut url) = reqwest::Url::parse(base) else {
+        return base.to_string();
+    };
+    if let Ok(mut path) = url.path_segments_mut() {
+        path.pop_if_empty().extend(segments);
+    }
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+    url.into()
+}
+
+/// Path segments of `url` below `base_url`, without a trailing `.git`. Returns `None`
+/// when the URL is not on that instance.
+fn repo_segments(base_url: &str, url: &str) -> Option<Vec<String>> {
+    let strip_scheme = |u: &str| {
+        let u = u.trim();
+        u.split_once("://").map_or(u, |(_, rest)| rest).to_ascii_lowercase()
+    };
+    let base = strip_scheme(base_url);
+    let base = base.trim_end_matches('/');
+    let full = url.trim().split_once("://").map_or(url.trim(), |(_, rest)| rest);
+    // Drop any userinfo in front of the host
+    let full = match full.split_once('/') {
+        Some((authority, path)) => format!("{}/{}", authority.rsplit('@').next()?, path),
+        None => full.rsplit('@').next()?.to_string(),
+    };
+    if !full.to_ascii_lowercase().starts_with(&format!("{}/", base)) {
+        return None;
+    }
+
+    let path = full[base.len() + 1..].split(['?', '#']).next()?;
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.trim_end_matches(".git").to_string())
+        .collect();
+    if segments.len() < 2 {
+        return None;
+    }
+    Some(segments)
+}
+
+struct GitHub {
+    base_url: String,
+    api_base: String,
+}
+
+impl Forge for GitHub {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    fn archive_url(&self, repo: &RepoRef, reference: Option<&str>) -> Option<String> {
+        let mut segments = vec!["repos", &repo.owner, &repo.name, "zipball"];
+        segments.extend(reference);
+        Some(api_url(&self.api_base, &segments, &[]))
+    }
+    /// The contents API returns the file itself when asked for the raw media type.
+    fn raw_file_request(
+        &self,
+        client: &reqwest::Client,
+        repo: &RepoRef,
+        path: &str,
+        reference: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        let mut segments = vec!["repos", &repo.owner, &repo.name, "contents"];
+        segments.extend(path.split('/'));
+        let request = client
+            .get(api_url(&self.api_base, &segments, &[]))
+            .header(reqwest::header::ACCEPT, "application/vnd.github.raw");
+        match reference {
+            Some(reference) => request.query(&[("ref", reference)]),
+            None => request,
+        }
+    }
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+        request.bearer_auth(token)
+    }
+}
+
+struct Gitee {
+    base_url: String,
+    api_base: String,
+}
+
+impl Forge for Gitee {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    fn archive_url(&self, repo: &RepoRef, reference: Option<&str>) -> Option<String> {
+        let query = reference.map(|reference| ("ref", reference));
+        Some(api_url(
+            &self.api_base,
+            &["repos", &repo.owner, &repo.name, "zipball"],
+            query.as_slice(),
+        ))
+    }
+    /// Gitee lists releases oldest first unless asked otherwise.
+    fn releases_api_url(&self, repo: &RepoRef) -> String {
+        api_url(
+            &self.api_base,
+            &["repos", &repo.owner, &repo.name, "releases"],
+            &[("per_page", RELEASE_PAGE_SIZE), ("direction", "desc")],
+        )
+    }
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+        request.query(&[("access_token", token)])
+    }
+}
+
+struct GitLab {
+    base_url: String,
+    api_base: String,
+}
+
+impl GitLab {
+    /// API URL below the project, which the API addresses by its full path as one
+    /// encoded segment.
+    fn project_url(&self, repo: &RepoRef, segments: &[&str], query: &[(&str, &str)]) -> String {
+        let project = repo.path();
+        let mut all = vec!["projects", project.as_str()];
+        all.extend(segments);
+        api_url(&self.api_base, &all, query)
+    }
+}
+
+impl Forge for GitLab {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    /// GitLab nests projects in groups and subgroups; everything up to the `/-/`
+    /// marker of tree/blob pages belongs to the project path.
+    fn parse_repo(&self, url: &str) -> Option<RepoRef> {
+        let mut segments: Vec<String> = repo_segments(&self.base_url, url)?
+            .into_iter()
+            .take_while(|segment| segment != "-")
+            .collect();
+        let name = segments.pop()?;
+        if segments.is_empty() {
+            return None;
+        }
+        Some(RepoRef {
+            owner: segments.join("/"),
+            name,
+        })
+    }
+    fn repo_api_url(&self, repo: &RepoRef) -> String {
+        self.project_url(repo, &[], &[])
+    }
+    fn commit_api_url(&self, repo: &RepoRef, reference: &str) -> String {
+        self.project_url(repo, &["repository", "commits", reference], &[])
+    }
+    fn branch_api_url(&self, repo: &RepoRef, name: &str) -> String {
+        self.project_url(repo, &["repository", "branches", name], &[])
+    }
+    fn releases_api_url(&self, repo: &RepoRef) -> String {
+        self.project_url(repo, &["releases"], &[("per_page", RELEASE_PAGE_SIZE)])
+    }
+    fn compare_api_url(&self, repo: &RepoRef, base: &str, head: &str) -> String {
+        self.project_url(
+            repo,
+            &["repository", "compare"],
+            &[("from", base), ("to", head)],
+        )
+    }
+    fn archive_url(&self, repo: &RepoRef, reference: Option<&str>) -> Option<String> {
+        let query = reference.map(|reference| ("sha", reference));
+        Some(self.project_url(repo, &["repository", "archive.zip"], query.as_slice()))
+    }
+    fn raw_file_request(
+        &self,
+        client: &reqwest::Client,
+        repo: &RepoRef,
+        path: &str,
+        reference: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        let request =
+            client.get(self.project_url(repo, &["repository", "files", path, "raw"], &[]));
+        match reference {
+            Some(reference) => request.query(&[("ref", reference)]),
+            None => request,
+        }
+    }
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+        request.header("PRIVATE-TOKEN", token)
+    }
+}
+
+struct Gitea {
+    base_url: String,
+    api_base: String,
+}
+
+impl Forge for Gitea {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    fn commit_api_url(&self, repo: &RepoRef, reference: &str) -> String {
+        api_url(
+            &self.api_base,
+            &["repos", &repo.owner, &repo.name, "git", "commits", reference],
+            &[],
+        )
+    }
+    /// Gitea has no "default branch" archive endpoint.
+    fn archive_url(&self, repo: &RepoRef, reference: Option<&str>) -> Option<String> {
+        Some(api_url(
+            &self.api_base,
+            &["repos", &repo.owner, &repo.name, "archive", &format!("{}.zip", reference?)],
+            &[],
+        ))
+    }
+    fn releases_api_url(&self, repo: &RepoRef) -> String {
+        api_url(
+            &self.api_base,
+            &["repos", &repo.owner, &repo.name, "releases"],
+            &[("limit", RELEASE_PAGE_SIZE)],
+        )
+    }
+    fn authorize(&self, request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+        request.header(reqwest::header::AUTHORIZATION, format!("token {}", token))
+    }
+}
+
+/// A forge instance as configured by the user (or built in).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ForgeConfig {
+    pub kind: ForgeKind,
+    pub base_url: String,
+    /// Defaults to the usual API path of `kind` below `base_url`.
+    #[serde(default)]
+    pub api_base: Option<String>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+impl ForgeConfig {
+    fn builtin(kind: ForgeKind, base_url: &str, api_base: &str) -> Self {
+        Self {
+            kind,
+            base_url: base_url.to_string(),
+            api_base: Some(api_base.to_string()),
+            builtin: true,
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Forge> {
+        let base_url = self.base_url.trim().trim_end_matches('/').to_string();
+        let api_base = match &self.api_base {
+            Some(api_base) if !api_base.trim().is_empty() => {
+                api_base.trim().trim_end_matches('/').to_string()
+            }
+            _ => match self.kind {
+                ForgeKind::GitHub => format!("{}/api/v3", base_url),
+                ForgeKind::Gitee => format!("{}/api/v5", base_url),
+                ForgeKind::GitLab => format!("{}/api/v4", base_url),
+                ForgeKind::Gitea => format!("{}/api/v1", base_url),
+            },
+        };
+        match self.kind {
+            ForgeKind::GitHub => Box::new(GitHub { base_url, api_base }),
+            ForgeKind::Gitee => Box::new(Gitee { base_url, api_base }),
+            ForgeKind::GitLab => Box::new(GitLab { base_url, api_base }),
+            ForgeKind::Gitea => Box::new(Gitea { base_url, api_base }),
+        }
+    }
+}
+
+fn builtin_forges() -> Vec<ForgeConfig> {
+    vec![
+        ForgeConfig::builtin(ForgeKind::GitHub, "https://github.com", "https://api.github.com"),
+        ForgeConfig::builtin(ForgeKind::Gitee, "https://gitee.com", "https://gitee.com/api/v5"),
+        ForgeConfig::builtin(ForgeKind::GitLab, "https://gitlab.com", "https://gitlab.com/api/v4"),
+        ForgeConfig::builtin(ForgeKind::Gitea, "https://codeberg.org", "https://codeberg.org/api/v1"),
+    ]
+}
+
+/// Managed state: built-in forges plus self-hosted instances saved in `forges.json`.
+pub(crate) struct ForgeRegistry {
+    path: PathBuf,
+    custom: Mutex<Vec<ForgeConfig>>,
+}
+
+impl ForgeRegistry {
+    pub fn new(data_dir: PathBuf) -> Self {
+        let path = data_dir.join("forges.json");
+        let custom = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            custom: Mutex::new(custom),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ForgeConfig> {
+        let mut forges = builtin_forges();
+        forges.extend(self.custom.lock().unwrap().iter().cloned());
+        forges
+    }
+
+    fn save(&self, custom: &[ForgeConfig]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_vec_pretty(custom).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, data).map_err(|e| e.to_string())
+    }
+
+    /// Adds or replaces the self-hosted instance at `config.base_url`.
+    pub fn add(&self, mut config: ForgeConfig) -> Result<(), String> {
+        let base_url = config.base_url.trim().trim_end_matches('/').to_string();
+        if !base_url.starts_with("https://") && !base_url.starts_with("http://") {
+            return Err(format!("无效的实例地址: {}", config.base_url));
+        }
+        if builtin_forges().iter().any(|forge| forge.base_url == base_url) {
+            return Err(format!("内置平台无需添加: {}", base_url));
+        }
+        config.base_url = base_url;
+        config.builtin = false;
+        let forge = config.build();
+        if reqwest::Url::parse(forge.api_base()).is_err() {
+            return Err(format!("无效的 API 地址: {}", forge.api_base()));
+        }
+
+        let mut custom = self.custom.lock().unwrap();
+        custom.retain(|forge| forge.base_url != config.base_url);
+        custom.push(config);
+        self.save(&custom)
+    }
+
+    pub fn remove(&self, base_url: &str) -> Result<(), String> {
+        let base_url = base_url.trim().trim_end_matches('/');
+        let mut custom = self.custom.lock().unwrap();
+        custom.retain(|forge| forge.base_url != base_url);
+        self.save(&custom)
+    }
+
+    /// The forge whose API `url` lies under.
+    pub fn for_api_url(&self, url: &str) -> Option<Box<dyn Forge>> {
+        let url = url.trim().to_ascii_lowercase();
+        self.list().iter().map(ForgeConfig::build).find(|forge| {
+            let api_base = forge.api_base().to_ascii_lowercase();
+            url.strip_prefix(&api_base)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+        })
+    }
+
+    /// Finds the forge hosting `url` and the repository it points to.
+    pub fn resolve(&self, url: &str) -> Option<(Box<dyn Forge>, RepoRef)> {
+        self.list().iter().find_map(|config| {
+            let forge = config.build();
+            let repo = forge.parse_repo(url)?;
+            Some((forge, repo))
+        })
+    }
+}
+
+/// Start of the error returned when the forge's API rate limit is exhausted, so
+/// background checks can tell it apart and back off.
+pub(crate) const RATE_LIMITED: &str = "API 请求次数已达上限";
+
+/// GETs `url` with the forge's authorisation and parses the JSON body.
+async fn get_json(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    url: String,
+    token: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    let mut request = client.get(url);
+    if let Some(token) = token {
+        request = forge.authorize(request, token);
+    }
+    // Gitee sends the token in the query string, so errors leave out the URL
+    let response = request.send().await.map_err(|e| e.without_url().to_string())?;
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (status == reqwest::StatusCode::FORBIDDEN
+            && crate::retry::is_transient(status, response.headers()))
+    {
+        return Err(format!("{}，状态码: {}", RATE_LIMITED, status));
+    }
+    if !status.is_success() {
+        return Err(format!("无法获取仓库信息，状态码: {}", status));
+    }
+    response.json().await.map_err(|e| e.without_url().to_string())
+}
+
+/// Name of the repository's default branch.
+pub(crate) async fn default_branch(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    token: Option<&str>,
+) -> Result<String, String> {
+    let info = get_json(client, forge, forge.repo_api_url(repo), token).await?;
+    info["default_branch"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "无法确定仓库的默认分支".to_string())
+}
+
+/// Archive URL for `reference` (or the default branch), asking the API for the default
+/// branch on forges that cannot serve it implicitly.
+pub(crate) async fn resolve_archive_url(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    reference: Option<&str>,
+    token: Option<&str>,
+) -> Result<String, String> {
+    if let Some(url) = forge.archive_url(repo, reference) {
+        return Ok(url);
+    }
+    let branch = default_branch(client, forge, repo, token).await?;
+    forge
+        .archive_url(repo, Some(&branch))
+        .ok_or_else(|| "无法生成压缩包地址".to_string())
+}
+
+/// Reads a commit from any forge's JSON: GitHub, Gitee and Gitea nest the message and
+/// author under `commit`, GitLab keeps flat `id`/`title`/`author_name` fields.
+fn parse_commit(value: &serde_json::Value) -> Option<CommitSummary> {
+    let text = |candidates: &[&serde_json::Value]| {
+        candidates
+            .iter()
+            .find_map(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let sha = value["sha"].as_str().or(value["id"].as_str())?.to_string();
+    let message = text(&[&value["commit"]["message"], &value["message"], &value["title"]]);
+    Some(CommitSummary {
+        sha,
+        summary: message.lines().next().unwrap_or_default().to_string(),
+        author: text(&[&value["commit"]["author"]["name"], &value["author_name"]]),
+        date: text(&[
+            &value["commit"]["committer"]["date"],
+            &value["commit"]["author"]["date"],
+            &value["committed_date"],
+            &value["created_at"],
+        ]),
+    })
+}
+
+/// Whether `name` is a branch of the repository, rather than a tag or a commit.
+pub(crate) async fn is_branch(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    name: &str,
+    token: Option<&str>,
+) -> Result<bool, String> {
+    let mut request = client.get(forge.branch_api_url(repo, name));
+    if let Some(token) = token {
+        request = forge.authorize(request, token);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    match response.status() {
+        status if status.is_success() => Ok(true),
+        reqwest::StatusCode::NOT_FOUND => Ok(false),
+        status => Err(format!("无法获取分支信息，状态码: {}", status)),
+    }
+}
+
+/// Commit that `reference`, or the default branch when `None`, currently points to.
+pub(crate) async fn latest_commit(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    reference: Option<&str>,
+    token: Option<&str>,
+) -> Result<CommitSummary, String> {
+    let reference = match reference {
+        Some(reference) => reference.to_string(),
+        None => default_branch(client, forge, repo, token).await?,
+    };
+    let commit = get_json(client, forge, forge.commit_api_url(repo, &reference), token).await?;
+    parse_commit(&commit).ok_or_else(|| format!("无法解析提交信息: {}", reference))
+}
+
+/// Commits in `head` that are not in `base`, newest first.
+pub(crate) async fn compare_commits(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    base: &str,
+    head: &str,
+    token: Option<&str>,
+) -> Result<Vec<CommitSummary>, String> {
+    let comparison = get_json(client, forge, forge.compare_api_url(repo, base, head), token).await?;
+    let mut commits: Vec<CommitSummary> = comparison["commits"]
+        .as_array()
+        .map(|commits| commits.iter().filter_map(parse_commit).collect())
+        .unwrap_or_default();
+    commits.reverse();
+    Ok(commits)
+}
+
+/// Raw content of the file at `path`, or `None` if the repository has no such file.
+pub(crate) async fn fetch_file(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    path: &str,
+    reference: Option<&str>,
+    token: Option<&str>,
+) -> Result<Option<String>, String> {
+    let mut request = forge.raw_file_request(client, repo, path, reference);
+    if let Some(token) = token {
+        request = forge.authorize(request, token);
+    }
+    let response = request.send().await.map_err(|e| e.without_url().to_string())?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("无法读取仓库文件 {}，状态码: {}", path, response.status()));
+    }
+    response
+        .text()
+        .await
+        .map(Some)
+        .map_err(|e| e.without_url().to_string())
+}
+
+/// Tag of the newest stable release if it is newer than `current`. Releases are
+/// compared by their order in the list rather than by tag, and drafts and pre-releases
+/// are skipped, so a pre-release ahead of the newest stable one is not "updated" back.
+pub(crate) async fn newer_release_tag(
+    client: &reqwest::Client,
+    forge: &dyn Forge,
+    repo: &RepoRef,
+    current: &str,
+    token: Option<&str>,
+) -> Result<Option<String>, String> {
+    let releases = get_json(client, forge, forge.releases_api_url(repo), token).await?;
+    let releases = releases
+        .as_array()
+        .ok_or_else(|| "无法解析发布版本列表".to_string())?;
+    Ok(newer_release(releases, current))
+}
+
+/// The first stable release in `releases` (newest first) listed before `current`.
+fn newer_release(releases: &[serde_json::Value], current: &str) -> Option<String> {
+    for release in releases {
+        let Some(tag) = release["tag_name"].as_str() else {
+            continue;
+        };
+        if tag == current {
+            return None;
+        }
+        let unstable = ["draft", "prerelease", "upcoming_release"]
+            .iter()
+            .any(|flag| release[*flag].as_bool().unwrap_or(false));
+        if !unstable {
+            return Some(tag.to_string());
+        }
+    }
+    None
+}
+
+/// A forge API response, as handed to the frontend.
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ApiResponse {
+    pub status: u16,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// GETs `url` with `headers` for the frontend, authorised with `token` the way `forge`
+/// expects. Credentials the caller put in `headers` are dropped, and errors leave out
+/// the URL, which carries Gitee's token.
+pub(crate) async fn api_request(
+    client: &reqwest::Client,
+    forge: Option<&dyn Forge>,
+    url: &str,
+    headers: &HashMap<String, String>,
+    token: Option<&str>,
+) -> Result<ApiResponse, String> {
+    let mut request = client.get(url);
+    for (name, value) in headers {
+        let lower = name.to_ascii_lowercase();
+        if !matches!(lower.as_str(), "authorization" | "private-token" | "cookie") {
+            request = request.header(name, value);
+        }
+    }
+    if let (Some(forge), Some(token)) = (forge, token) {
+        request = forge.authorize(request, token);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    Ok(ApiResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-forge-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn forge(kind: ForgeKind, base_url: &str) -> Box<dyn Forge> {
+        ForgeConfig {
+            kind,
+            base_url: base_url.to_string(),
+            api_base: None,
+            builtin: false,
+        }
+        .build()
+    }
+
+    fn repo(owner: &str, name: &str) -> RepoRef {
+        RepoRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn resolve_finds_forge_and_repository() {
+        let dir = scratch_dir("resolve");
+        let registry = ForgeRegistry::new(dir.clone());
+        registry
+            .add(ForgeConfig {
+                kind: ForgeKind::GitLab,
+                base_url: "https://git.example.com/".to_string(),
+                api_base: None,
+                builtin: false,
+            })
+            .unwrap();
+
+        let (forge, found) = registry
+            .resolve("https://user@GitHub.com/owner/name.git")
+            .unwrap();
+        assert_eq!(forge.api_base(), "https://api.github.com");
+        assert_eq!(found, repo("owner", "name"));
+
+        let (forge, found) = registry
+            .resolve("https://git.example.com/group/sub/project/-/tree/main")
+            .unwrap();
+        assert_eq!(forge.api_base(), "https://git.example.com/api/v4");
+        assert_eq!(found, repo("group/sub", "project"));
+
+        assert!(registry.resolve("https://gitee.com/owner").is_none());
+        assert!(registry.resolve("https://unknown.example/owner/name").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn custom_forges_round_trip_through_the_file() {
+        let dir = scratch_dir("round-trip");
+        let registry = ForgeRegistry::new(dir.clone());
+        let gitea = ForgeConfig {
+            kind: ForgeKind::Gitea,
+            base_url: "https://gitea.example.com/".to_string(),
+            api_base: Some("https://gitea.example.com/api/v1".to_string()),
+            builtin: true,
+        };
+        registry.add(gitea).unwrap();
+        assert!(registry
+            .add(ForgeConfig::builtin(
+                ForgeKind::GitHub,
+                "https://github.com",
+                "https://api.github.com"
+            ))
+            .is_err());
+        assert!(registry
+            .add(ForgeConfig {
+                kind: ForgeKind::GitHub,
+                base_url: "ftp://example.com".to_string(),
+                api_base: None,
+                builtin: false,
+            })
+            .is_err());
+
+        let reloaded = ForgeRegistry::new(dir.clone());
+        let custom: Vec<ForgeConfig> = reloaded
+            .list()
+            .into_iter()
+            .filter(|forge| !forge.builtin)
+            .collect();
+        assert_eq!(custom.len(), 1);
+        assert_eq!(custom[0].kind, ForgeKind::Gitea);
+        assert_eq!(custom[0].base_url, "https://gitea.example.com");
+        assert_eq!(
+            custom[0].api_base.as_deref(),
+            Some("https://gitea.example.com/api/v1")
+        );
+
+        reloaded.remove("https://gitea.example.com/").unwrap();
+        assert!(ForgeRegistry::new(dir.clone())
+            .list()
+            .iter()
+            .all(|forge| forge.builtin));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn github_urls_encode_refs() {
+        let github = forge(ForgeKind::GitHub, "https://github.example.com");
+        let repo = repo("owner", "name");
+        assert_eq!(
+            github.archive_url(&repo, None).unwrap(),
+            "https://github.example.com/api/v3/repos/owner/name/zipball"
+        );
+        assert_eq!(
+            github.archive_url(&repo, Some("feature/a?b#c")).unwrap(),
+            "https://github.example.com/api/v3/repos/owner/name/zipball/feature%2Fa%3Fb%23c"
+        );
+        assert_eq!(
+            github.compare_api_url(&repo, "v1", "main"),
+            "https://github.example.com/api/v3/repos/owner/name/compare/v1...main"
+        );
+        assert_eq!(
+            github.commit_api_url(&repo, "a b"),
+            "https://github.example.com/api/v3/repos/owner/name/commits/a%20b"
+        );
+        let request = github
+            .raw_file_request(&reqwest::Client::new(), &repo, "docs/read me.md", Some("x&y"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://github.example.com/api/v3/repos/owner/name/contents/docs/read%20me.md?ref=x%26y"
+        );
+    }
+
+    #[test]
+    fn gitee_urls_encode_refs() {
+        let gitee = forge(ForgeKind::Gitee, "https://gitee.com");
+        let repo = repo("owner", "name");
+        assert_eq!(
+            gitee.archive_url(&repo, Some("v1&access_token=x")).unwrap(),
+            "https://gitee.com/api/v5/repos/owner/name/zipball?ref=v1%26access_token%3Dx"
+        );
+        assert_eq!(
+            gitee.releases_api_url(&repo),
+            "https://gitee.com/api/v5/repos/owner/name/releases?per_page=30&direction=desc"
+        );
+        let request = gitee
+            .authorize(
+                reqwest::Client::new().get(gitee.repo_api_url(&repo)),
+                "secret",
+            )
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://gitee.com/api/v5/repos/owner/name?access_token=secret"
+        );
+    }
+
+    #[test]
+    fn gitlab_urls_encode_project_paths_and_refs() {
+        let gitlab = forge(ForgeKind::GitLab, "https://gitlab.com");
+        let repo = repo("group/sub", "project");
+        assert_eq!(
+            gitlab.repo_api_url(&repo),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject"
+        );
+        assert_eq!(
+            gitlab.commit_api_url(&repo, "release/1.0"),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject/repository/commits/release%2F1.0"
+        );
+        assert_eq!(
+            gitlab.compare_api_url(&repo, "a&b", "c d"),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject/repository/compare?from=a%26b&to=c+d"
+        );
+        assert_eq!(
+            gitlab.branch_api_url(&repo, "feature/x"),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject/repository/branches/feature%2Fx"
+        );
+        assert_eq!(
+            gitlab.archive_url(&repo, Some("v1#x")).unwrap(),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject/repository/archive.zip?sha=v1%23x"
+        );
+        let request = gitlab
+            .raw_file_request(&reqwest::Client::new(), &repo, "src/main.rs", None)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject/repository/files/src%2Fmain.rs/raw"
+        );
+    }
+
+    #[test]
+    fn gitea_urls_need_a_reference() {
+        let gitea = forge(ForgeKind::Gitea, "https://codeberg.org");
+        let repo = repo("owner", "name");
+        assert_eq!(gitea.archive_url(&repo, None), None);
+        assert_eq!(
+            gitea.archive_url(&repo, Some("v1.0")).unwrap(),
+            "https://codeberg.org/api/v1/repos/owner/name/archive/v1.0.zip"
+        );
+        assert_eq!(
+            gitea.commit_api_url(&repo, "main"),
+            "https://codeberg.org/api/v1/repos/owner/name/git/commits/main"
+        );
+        assert_eq!(
+            gitea.releases_api_url(&repo),
+            "https://codeberg.org/api/v1/repos/owner/name/releases?limit=30"
+        );
+    }
+
+    #[test]
+    fn only_newer_stable_releases_count() {
+        let releases: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                { "tag_name": "v3.0-rc1", "prerelease": true },
+                { "tag_name": "v2.1", "draft": true },
+                { "tag_name": "v2.0" },
+                { "tag_name": "v2.0-beta", "prerelease": true },
+                { "tag_name": "v1.9" }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(newer_release(&releases, "v1.9"), Some("v2.0".to_string()));
+        assert_eq!(
+            newer_release(&releases, "v2.0-beta"),
+            Some("v2.0".to_string())
+        );
+        assert_eq!(newer_release(&releases, "v2.0"), None);
+        // A pre-release ahead of the newest stable release is not an older version
+        assert_eq!(newer_release(&releases, "v3.0-rc1"), None);
+        // Tags beyond the first page are taken as old
+        assert_eq!(newer_release(&releases, "v0.1"), Some("v2.0".to_string()));
+
+        let gitlab: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[{ "tag_name": "v2", "upcoming_release": true }, { "tag_name": "v1" }]"#,
+        )
+        .unwrap();
+        assert_eq!(newer_release(&gitlab, "v1"), None);
+        assert_eq!(newer_release(&[], "v1"), None);
+    }
+}
@@ -0,0 +1,183 @@
+// The git operations behind installs and updates, with two implementations: libgit2
+// linked into the app, so clones and updates work without a git installation, and the
+// git CLI, which remains the fallback for what libgit2 cannot do (SSH remotes, sparse
+// checkouts and Git LFS).
+use crate::error::CommandError;
+use crate::git::{self, CloneOptions};
+use crate::libgit::LibGit;
+use crate::process::{self, Program};
+use crate::progress::GitProgress;
+use crate::updates::{self, UpdateStatus, UpdateStrategy};
+use std::path::Path;
+
+pub(crate) trait GitBackend: Send + Sync {
+    /// Shown in progress messages.
+    fn name(&self) -> &'static str;
+
+    /// Clones `repo_url` into `path` (which must not exist yet) and checks out the
+    /// version selected by `options`.
+    fn clone_repo(
+        &self,
+        repo_url: &str,
+        path: &Path,
+        options: &CloneOptions,
+        token: Option<&str>,
+        on_progress: &mut dyn FnMut(GitProgress),
+    ) -> Result<(), String>;
+
+    /// URL of the `origin` remote, with any credentials embedded by older versions of
+    /// the app removed from it.
+    fn remote_url(&self, repo_path: &Path) -> Option<String>;
+
+    /// Fetches `origin` and compares `HEAD` with its upstream.
+    fn check(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+    ) -> Result<UpdateStatus, String>;
+
+    /// Updates the checkout to its upstream, keeping the previous state for `rollback`.
+    fn pull(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+        strategy: UpdateStrategy,
+    ) -> Result<String, CommandError>;
+
+    /// Restores the revision and local changes recorded by the last `pull`.
+    fn rollback(&self, repo_path: &Path) -> Result<String, CommandError>;
+}
+
+/// Remotes libgit2 is built to talk to; SSH is left to the CLI and its key setup.
+fn library_supports_url(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    ["https://", "http://", "git://", "file://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// Backend for cloning `repo_url` with `options`, or `None` when neither can, in which
+/// case the install falls back to the ZIP archive.
+pub(crate) fn for_clone(repo_url: &str, options: &CloneOptions) -> Option<Box<dyn GitBackend>> {
+    let needs_cli =
+        !library_supports_url(repo_url) || !options.sparse_paths.is_empty() || options.lfs;
+    if !needs_cli {
+        Some(Box::new(LibGit))
+    } else if git::cli_installed() {
+        Some(Box::new(CliGit))
+    } else {
+        None
+    }
+}
+
+/// Backend for updating the checkout at `repo_path`, or `None` if it is not a git
+/// checkout. Sparse and LFS checkouts go to the CLI when it is installed, since
+/// libgit2 would fill in the whole tree and leave LFS pointer files.
+pub(crate) fn for_repository(repo_path: &Path) -> Option<Box<dyn GitBackend>> {
+    if !repo_path.join(".git").exists() {
+        return None;
+    }
+    let Some(checkout) = LibGit::inspect(repo_path) else {
+        return git::cli_installed().then(|| Box::new(CliGit) as Box<dyn GitBackend>);
+    };
+    let prefers_cli = !library_supports_url(&checkout.remote_url)
+        || checkout.sparse
+        || git::RepoFeatures::detect(repo_path).lfs;
+    if prefers_cli && git::cli_installed() {
+        Some(Box::new(CliGit))
+    } else {
+        Some(Box::new(LibGit))
+    }
+}
+
+/// What the git backends can do on this system.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub(crate) struct GitSupport {
+    /// libgit2 is linked in, so clones, updates and rollbacks work without a git
+    /// installation.
+    pub library: bool,
+    /// A `git` executable is available for SSH remotes and sparse checkouts.
+    pub cli: bool,
+    /// The `git lfs` extension is installed, so LFS objects can be fetched.
+    pub lfs: bool,
+}
+
+pub(crate) fn support() -> GitSupport {
+    let cli = git::cli_installed();
+    GitSupport {
+        library: true,
+        cli,
+        lfs: cli && git::lfs_installed(),
+    }
+}
+
+/// The `git` executable, started directly with an argument list so URLs and paths
+/// never pass through a shell.
+pub(crate) struct CliGit;
+
+impl GitBackend for CliGit {
+    fn name(&self) -> &'static str {
+        "Git CLI"
+    }
+
+    fn clone_repo(
+        &self,
+        repo_url: &str,
+        path: &Path,
+        options: &CloneOptions,
+        token: Option<&str>,
+        on_progress: &mut dyn FnMut(GitProgress),
+    ) -> Result<(), String> {
+        if options.lfs && !git::lfs_installed() {
+            return Err("未安装 Git LFS，无法拉取 LFS 文件".to_string());
+        }
+        let mut command = process::command(
+            Program::Git,
+            options.clone_args(repo_url, &path.to_string_lossy()),
+        );
+        git::authenticate(&mut command, repo_url, token);
+
+        let (success, stderr) = git::run_with_progress(command, &mut *on_progress)
+            .map_err(|e| format!("无法运行 git 进程: {}", e))?;
+        if !success {
+            return Err(stderr);
+        }
+
+        // Narrow the checkout and pin the commit once the clone itself succeeded
+        let steps = options.post_clone_steps();
+        if !steps.is_empty() {
+            on_progress(GitProgress::new("checkout", 0, 0, None));
+            git::run_steps(path, &steps, repo_url, token)?;
+        }
+        Ok(())
+    }
+
+    fn remote_url(&self, repo_path: &Path) -> Option<String> {
+        git::clean_remote_url(repo_path)
+    }
+
+    fn check(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+    ) -> Result<UpdateStatus, String> {
+        updates::check_git(repo_path, remote_url, token)
+    }
+
+    fn pull(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+        strategy: UpdateStrategy,
+    ) -> Result<String, CommandError> {
+        updates::pull_git(repo_path, remote_url, token, strategy)
+    }
+
+    fn rollback(&self, repo_path: &Path) -> Result<String, CommandError> {
+        updates::rollback_git(repo_path)
+    }
+}
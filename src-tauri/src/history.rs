@@ -0,0 +1,74 @@
+// Download journal persisted in the app data directory as JSON Lines, so the app
+// still knows what it downloaded, where, and how it ended after a restart.
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub(crate) struct HistoryEntry {
+    pub url: String,
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    /// Digest of the finished file as `algorithm:hex`.
+    pub hash: Option<String>,
+    /// Unix timestamps in seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub status: String, // "completed", "failed", "cancelled"
+    pub error: Option<String>,
+}
+
+/// Managed state wrapping the journal file; the mutex serialises appends.
+pub(crate) struct DownloadHistory {
+    path: Mutex<PathBuf>,
+}
+
+impl DownloadHistory {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            path: Mutex::new(data_dir.join("download_history.jsonl")),
+        }
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<(), String> {
+        let path = self.path.lock().unwrap();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&*path)
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+
+    /// Returns the journal newest first. Unreadable lines are skipped.
+    pub fn load(&self) -> Result<Vec<HistoryEntry>, String> {
+        let path = self.path.lock().unwrap();
+        let file = match std::fs::File::open(&*path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut entries: Vec<HistoryEntry> = std::io::BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let path = self.path.lock().unwrap();
+        match std::fs::remove_file(&*path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,772 @@
+// The installed repositories, kept in the `installed_repos` table of the app database
+// (see db.rs), together with the download directories installs were made in. Clones,
+// updates and uninstalls record their outcome here, and `uninstall_repository` only
+// deletes a folder that is a recorded install inside one of those directories, so a
+// corrupted library row cannot point it at a home directory or anything else.
+use crate::git::CloneOptions;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// One installed repository, as the Library lists it. Field names follow the table's
+/// columns, which the frontend has always used.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct InstalledRepo {
+    pub url: String,
+    pub owner: String,
+    pub repo: String,
+    pub name: String,
+    /// Install folder; empty until the clone has started.
+    pub path: String,
+    /// `cloning`, `downloading_zip`, `pulling`, `completed` or `failed`.
+    pub status: String,
+    pub message: String,
+    pub date_installed: String,
+    pub description: String,
+    pub primary_language: String,
+    pub stars: i64,
+}
+
+const COLUMNS: &str = "url, owner, repo, name, path, status, message, dateInstalled, description, primaryLanguage, stars";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<InstalledRepo> {
+    let text = |index: usize| -> rusqlite::Result<String> {
+        Ok(row.get::<_, Option<String>>(index)?.unwrap_or_default())
+    };
+    Ok(InstalledRepo {
+        url: text(0)?,
+        owner: text(1)?,
+        repo: text(2)?,
+        name: text(3)?,
+        path: text(4)?,
+        status: text(5)?,
+        message: text(6)?,
+        date_installed: text(7)?,
+        description: text(8)?,
+        primary_language: text(9)?,
+        stars: row.get::<_, Option<i64>>(10)?.unwrap_or_default(),
+    })
+}
+
+/// Inserts `repo` or updates the row with the same URL, setting `path` to `path_sql`.
+fn upsert(conn: &Connection, repo: &InstalledRepo, path_sql: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "INSERT INTO installed_repos ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(url) DO UPDATE SET owner = excluded.owner, repo = excluded.repo,
+                 name = excluded.name, path = {}, status = excluded.status,
+                 message = excluded.message, dateInstalled = excluded.dateInstalled,
+                 description = excluded.description,
+                 primaryLanguage = excluded.primaryLanguage, stars = excluded.stars",
+            COLUMNS, path_sql
+        ),
+        params![
+            repo.url,
+            repo.owner,
+            repo.repo,
+            repo.name,
+            repo.path,
+            repo.status,
+            repo.message,
+            repo.date_installed,
+            repo.description,
+            repo.primary_language,
+            repo.stars
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// Owner and name of the repository at `url`, from its last two path segments.
+pub(crate) fn owner_and_repo(url: &str) -> (String, String) {
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    // scp-like `git@host:owner/repo.git`
+    let path = path.split_once(':').map_or(path, |(_, rest)| rest);
+    let mut segments = path
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit('/')
+        .filter(|segment| !segment.is_empty());
+    let repo = segments.next().unwrap_or_default().to_string();
+    let owner = segments.next().unwrap_or_default().to_string();
+    (owner, repo)
+}
+
+/// `path` made absolute with symlinks resolved, as far as it exists, so the same folder
+/// always compares equal. A folder that does not exist yet resolves through its parent.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = std::fs::canonicalize(path) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            normalize(parent).join(name)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Today's date (UTC) as `YYYY/M/D`, the way the Library shows install dates.
+pub(crate) fn today() -> String {
+    let (year, month, day) = crate::dates::civil_date((crate::dates::unix_now() / 86_400) as i64);
+    format!("{}/{}/{}", year, month, day)
+}
+
+/// Name of the folder the app installs into inside a download directory.
+pub(crate) const INSTALL_DIR_NAME: &str = "GitAppStore";
+
+/// The folder installs go into for the download directory `dir` set in the settings:
+/// `dir` itself when it already is a GitAppStore folder, else the one inside it. Only
+/// such folders become managed roots, so whatever directory is configured, uninstall
+/// never deletes from a folder the app did not create for itself.
+pub(crate) fn install_dir(dir: &Path) -> PathBuf {
+    if dir.file_name().is_some_and(|name| name == INSTALL_DIR_NAME) {
+        dir.to_path_buf()
+    } else {
+        dir.join(INSTALL_DIR_NAME)
+    }
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+    format!("数据库错误: {}", e)
+}
+
+/// Managed state: the connection to the app database.
+pub(crate) struct InstallStore {
+    conn: Mutex<Connection>,
+}
+
+impl InstallStore {
+    /// Opens the database at `db_path`, taking over the install records an earlier
+    /// version kept in installs.json in `data_dir`.
+    pub fn open(db_path: &Path, data_dir: &Path) -> Result<Self, String> {
+        let store = Self {
+            conn: Mutex::new(crate::db::open(db_path)?),
+        };
+        store.import_legacy(&data_dir.join("installs.json"))?;
+        store.mark_interrupted()?;
+        Ok(store)
+    }
+
+    /// Installs and updates still marked as running were cut short when the app last
+    /// quit; flag them so they do not show a spinner forever.
+    fn mark_interrupted(&self) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE installed_repos SET status = 'failed', message = '操作被中断，请重试或修复'
+             WHERE status IN ('cloning', 'downloading_zip', 'pulling')",
+            [],
+        )
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn import_legacy(&self, file: &Path) -> Result<(), String> {
+        #[derive(serde::Deserialize)]
+        struct Legacy {
+            roots: Vec<String>,
+            installs: Vec<LegacyInstall>,
+        }
+        #[derive(serde::Deserialize)]
+        struct LegacyInstall {
+            path: String,
+            url: String,
+        }
+
+        let Ok(data) = std::fs::read(file) else {
+            return Ok(());
+        };
+        if let Ok(legacy) = serde_json::from_slice::<Legacy>(&data) {
+            let mut conn = self.conn.lock().unwrap();
+            let tx = conn.transaction().map_err(db_error)?;
+            let roots = legacy.roots.iter().filter(|root| {
+                Path::new(root)
+                    .file_name()
+                    .is_some_and(|name| name == INSTALL_DIR_NAME)
+            });
+            for root in roots {
+                tx.execute(
+                    "INSERT OR IGNORE INTO install_roots (path) VALUES (?1)",
+                    [root],
+                )
+                .map_err(db_error)?;
+            }
+            for install in &legacy.installs {
+                let (owner, repo) = owner_and_repo(&install.url);
+                tx.execute(
+                    "INSERT OR IGNORE INTO installed_repos (url, owner, repo, name, path, status, message)
+                     VALUES (?1, ?2, ?3, ?4, ?5, 'completed', '')",
+                    params![install.url, owner, repo, format!("{}/{}", owner, repo), install.path],
+                )
+                .map_err(db_error)?;
+            }
+            tx.commit().map_err(db_error)?;
+        }
+        std::fs::remove_file(file).map_err(|e| e.to_string())
+    }
+
+    pub fn list(&self) -> Result<Vec<InstalledRepo>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT {} FROM installed_repos ORDER BY rowid DESC",
+                COLUMNS
+            ))
+            .map_err(db_error)?;
+        let rows = statement.query_map([], from_row).map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Finished installs with a folder: what update checks and batch updates look at.
+    pub fn completed(&self) -> Result<Vec<InstalledRepo>, String> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|repo| repo.status == "completed" && !repo.path.is_empty())
+            .collect())
+    }
+
+    pub fn get(&self, url: &str) -> Result<Option<InstalledRepo>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM installed_repos WHERE url = ?1", COLUMNS),
+            [url],
+            from_row,
+        )
+        .optional()
+        .map_err(db_error)
+    }
+
+    /// The install recorded at `path`, compared after normalizing both.
+    pub fn find_by_path(&self, path: &Path) -> Result<Option<InstalledRepo>, String> {
+        let path = normalize(path);
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|repo| !repo.path.is_empty() && normalize(Path::new(&repo.path)) == path))
+    }
+
+    /// The recorded install at `path`, for commands that act on a folder the frontend
+    /// names. With `managed` the folder must also lie inside a managed root, as it must
+    /// for anything that deletes or replaces it; imported checkouts may lie elsewhere.
+    pub fn installed_at(&self, path: &Path, managed: bool) -> Result<InstalledRepo, String> {
+        if managed && !self.is_managed(path)? {
+            return Err(format!(
+                "拒绝操作：该目录不在下载目录内: {}",
+                path.display()
+            ));
+        }
+        self.find_by_path(path)?
+            .ok_or_else(|| format!("拒绝操作：该目录不是由应用安装的仓库: {}", path.display()))
+    }
+
+    /// Inserts `repo` or replaces the row with the same URL. An empty `path` keeps the
+    /// recorded one. Only for installs the backend found or made itself; details from the
+    /// frontend go through `record_details`.
+    pub fn record(&self, repo: &InstalledRepo) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        upsert(&conn, repo, "COALESCE(NULLIF(excluded.path, ''), path)")
+    }
+
+    /// Saves the Library details the frontend keeps for `repo`. The install folder is
+    /// not the frontend's to choose, since uninstall deletes it: a recorded `path` is
+    /// kept as it is, and `repo.path` is only taken for a row without one when it lies
+    /// inside a managed root (installs carried over from an older version's storage).
+    pub fn record_details(&self, repo: &InstalledRepo) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_error)?;
+        let mut repo = repo.clone();
+        if !repo.path.is_empty() {
+            let path = normalize(Path::new(&repo.path));
+            repo.path = if Self::in_root(&tx, &path)? {
+                path.to_string_lossy().to_string()
+            } else {
+                String::new()
+            };
+        }
+        upsert(&tx, &repo, "COALESCE(NULLIF(path, ''), excluded.path)")?;
+        tx.commit().map_err(db_error)
+    }
+
+    pub fn forget(&self, url: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM installed_repos WHERE url = ?1", [url])
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Manages the install folder of the download directory `dir` (see `install_dir`),
+    /// creating it if needed, and returns it.
+    pub fn add_download_dir(&self, dir: &Path) -> Result<PathBuf, String> {
+        let root = install_dir(dir);
+        std::fs::create_dir_all(&root).map_err(|e| format!("无法创建下载目录: {}", e))?;
+        let root = normalize(&root);
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO install_roots (path) VALUES (?1)",
+            [root.to_string_lossy()],
+        )
+        .map_err(db_error)?;
+        Ok(root)
+    }
+
+    /// The managed download directories.
+    pub fn roots(&self) -> Result<Vec<PathBuf>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT path FROM install_roots")
+            .map_err(db_error)?;
+        let roots = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(db_error)?;
+        roots
+            .map(|root| root.map(PathBuf::from))
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    /// Whether `path` lies inside a managed root.
+    pub fn is_managed(&self, path: &Path) -> Result<bool, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_error)?;
+        Self::in_root(&tx, &normalize(path))
+    }
+
+    /// The options `url` was cloned with; defaults for installs recorded without them.
+    pub fn clone_options(&self, url: &str) -> Result<CloneOptions, String> {
+        let conn = self.conn.lock().unwrap();
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT cloneOptions FROM installed_repos WHERE url = ?1",
+                [url],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?
+            .flatten();
+        Ok(json
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set_clone_options(&self, url: &str, options: &CloneOptions) -> Result<(), String> {
+        let json = serde_json::to_string(options).map_err(|e| e.to_string())?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE installed_repos SET cloneOptions = ?2 WHERE url = ?1",
+            params![url, json],
+        )
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Records a checkout made outside the store together with the options it is
+    /// treated as cloned with, in one transaction. Fails if `repo.url` is recorded.
+    pub fn record_import(
+        &self,
+        repo: &InstalledRepo,
+        options: &CloneOptions,
+    ) -> Result<(), String> {
+        let options = serde_json::to_string(options).map_err(|e| e.to_string())?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_error)?;
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT path FROM installed_repos WHERE url = ?1",
+                [&repo.url],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?;
+        if let Some(path) = existing {
+            return Err(format!("该仓库已安装在 {}", path));
+        }
+        upsert(&tx, repo, "excluded.path")?;
+        tx.execute(
+            "UPDATE installed_repos SET cloneOptions = ?2 WHERE url = ?1",
+            params![repo.url, options],
+        )
+        .map_err(db_error)?;
+        tx.commit().map_err(db_error)
+    }
+
+    /// Whether a managed root strictly contains the normalized `path`.
+    fn in_root(tx: &Transaction, path: &Path) -> Result<bool, String> {
+        let mut statement = tx
+            .prepare("SELECT path FROM install_roots")
+            .map_err(db_error)?;
+        let roots = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(db_error)?;
+        for root in roots {
+            let root = PathBuf::from(root.map_err(db_error)?);
+            if path.starts_with(&root) && path != root {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Records that `url` is being installed into `path` with `options`; `path` must
+    /// lie inside a managed root. Library details already stored for `url` are kept.
+    pub fn begin_install(
+        &self,
+        url: &str,
+        path: &Path,
+        options: &CloneOptions,
+    ) -> Result<(), String> {
+        let path = normalize(path);
+        let options = serde_json::to_string(options).map_err(|e| e.to_string())?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_error)?;
+        if !Self::in_root(&tx, &path)? {
+            return Err(format!("安装目录不在下载目录内: {}", path.display()));
+        }
+        let (owner, repo) = owner_and_repo(url);
+        tx.execute(
+            "INSERT INTO installed_repos (url, owner, repo, name, path, status, message, cloneOptions)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'cloning', '', ?6)
+             ON CONFLICT(url) DO UPDATE SET path = excluded.path, status = 'cloning', message = '',
+                 cloneOptions = excluded.cloneOptions",
+            params![
+                url,
+                owner,
+                repo,
+                format!("{}/{}", owner, repo),
+                path.to_string_lossy(),
+                options
+            ],
+        )
+        .map_err(db_error)?;
+        tx.commit().map_err(db_error)
+    }
+
+    /// Sets the status and message shown for `url`.
+    pub fn set_status(&self, url: &str, status: &str, message: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE installed_repos SET status = ?2, message = ?3 WHERE url = ?1",
+            params![url, status, message],
+        )
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Uninstalls the install at `path`: checks it is a recorded install inside a managed
+    /// root, then, unless this is a dry run, removes its row and its folders. The row is
+    /// restored if removing the folders fails.
+    pub fn uninstall(
+        &self,
+        path: &Path,
+        mode: UninstallMode,
+        dry_run: bool,
+    ) -> Result<UninstallReport, String> {
+        let normalized = normalize(path);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_error)?;
+        if !Self::in_root(&tx, &normalized)? {
+            return Err(format!(
+                "拒绝删除：该目录不在下载目录内: {}",
+                path.display()
+            ));
+        }
+        let urls: Vec<String> = {
+            let mut statement = tx
+                .prepare(
+                    "SELECT url, path FROM installed_repos WHERE path IS NOT NULL AND path != ''",
+                )
+                .map_err(db_error)?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })
+                .map_err(db_error)?;
+            rows.filter_map(Result::ok)
+                .filter(|(_, recorded)| normalize(Path::new(recorded)) == normalized)
+                .map(|(url, _)| url)
+                .collect()
+        };
+        if urls.is_empty() {
+            return Err(format!(
+                "拒绝删除：该目录不是由应用安装的仓库: {}",
+                path.display()
+            ));
+        }
+
+        let report = plan_uninstall(&normalized, mode, dry_run);
+        if dry_run {
+            return Ok(report);
+        }
+        let mut removed = Vec::new();
+        for url in &urls {
+            removed.push(
+                tx.query_row(
+                    &format!(
+                        "SELECT {}, cloneOptions FROM installed_repos WHERE url = ?1",
+                        COLUMNS
+                    ),
+                    [url],
+                    |row| Ok((from_row(row)?, row.get::<_, Option<String>>(11)?)),
+                )
+                .map_err(db_error)?,
+            );
+            tx.execute("DELETE FROM installed_repos WHERE url = ?1", [url])
+                .map_err(db_error)?;
+        }
+        // Files only go once the records are gone for good
+        tx.commit().map_err(db_error)?;
+        if let Err(e) = remove_targets(&report) {
+            // Put the records back, so what is left can still be found and uninstalled
+            for (repo, options) in &removed {
+                let _ = upsert(&conn, repo, "excluded.path");
+                let _ = conn.execute(
+                    "UPDATE installed_repos SET cloneOptions = ?2 WHERE url = ?1",
+                    params![repo.url, options],
+                );
+            }
+            return Err(e);
+        }
+        Ok(report)
+    }
+}
+
+/// How `uninstall_repository` gets rid of an install.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UninstallMode {
+    /// Delete the folders permanently.
+    #[default]
+    Delete,
+    /// Move them to the system trash / recycle bin.
+    Trash,
+}
+
+/// What an uninstall removed, or would remove on a dry run.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct UninstallReport {
+    pub mode: UninstallMode,
+    pub dry_run: bool,
+    /// The install folder and the ZIP rollback snapshot next to it, if present.
+    pub targets: Vec<String>,
+    /// Files and bytes below the targets, symlinks counted but not followed.
+    pub files: u64,
+    pub bytes: u64,
+}
+
+fn measure(path: &Path, files: &mut u64, bytes: &mut u64) {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return;
+    };
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            measure(&entry.path(), files, bytes);
+        }
+    } else {
+        *files += 1;
+        *bytes += metadata.len();
+    }
+}
+
+/// Lists what uninstalling the install at `path` touches.
+fn plan_uninstall(path: &Path, mode: UninstallMode, dry_run: bool) -> UninstallReport {
+    let mut targets = Vec::new();
+    if std::fs::symlink_metadata(path).is_ok() {
+        targets.push(path.to_path_buf());
+    }
+    // The tree kept for rolling back a ZIP update goes with the install
+    if let Some(snapshot) = crate::updates::zip_snapshot_path(path).filter(|s| s.is_dir()) {
+        targets.push(snapshot);
+    }
+    let (mut files, mut bytes) = (0, 0);
+    for target in &targets {
+        measure(target, &mut files, &mut bytes);
+    }
+    UninstallReport {
+        mode,
+        dry_run,
+        targets: targets
+            .iter()
+            .map(|t| t.to_string_lossy().to_string())
+            .collect(),
+        files,
+        bytes,
+    }
+}
+
+/// Removes every target of `report`.
+fn remove_targets(report: &UninstallReport) -> Result<(), String> {
+    for target in &report.targets {
+        let target = Path::new(target);
+        match report.mode {
+            UninstallMode::Trash => {
+                trash::delete(target).map_err(|e| format!("移至回收站失败: {}", e))?
+            }
+            UninstallMode::Delete if target.is_dir() && !target.is_symlink() => {
+                crate::paths::remove_tree(target).map_err(|e| e.to_string())?
+            }
+            UninstallMode::Delete => std::fs::remove_file(target).map_err(|e| e.to_string())?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store in a fresh folder, with the download directory `downloads` inside it.
+    fn scratch_store(name: &str) -> (InstallStore, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "openstore-installs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = InstallStore::open(&dir.join("store.db"), &dir).unwrap();
+        let root = store.add_download_dir(&dir.join("downloads")).unwrap();
+        (store, dir, root)
+    }
+
+    fn installed(url: &str, path: &Path) -> InstalledRepo {
+        InstalledRepo {
+            url: url.to_string(),
+            path: path.to_string_lossy().to_string(),
+            status: "completed".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unrecorded_folders_are_refused() {
+        let (store, dir, root) = scratch_store("installed-at");
+        let recorded = root.join("recorded");
+        let unrecorded = root.join("unrecorded");
+        let imported = dir.join("elsewhere");
+        for folder in [&recorded, &unrecorded, &imported] {
+            std::fs::create_dir_all(folder).unwrap();
+        }
+        store
+            .record(&installed("https://github.com/o/recorded", &recorded))
+            .unwrap();
+        store
+            .record(&installed("https://github.com/o/imported", &imported))
+            .unwrap();
+
+        let repo = store.installed_at(&recorded, true).unwrap();
+        assert_eq!(repo.url, "https://github.com/o/recorded");
+        assert!(store.installed_at(&unrecorded, false).is_err());
+        assert!(store.installed_at(&unrecorded, true).is_err());
+        assert!(store.installed_at(&dir, false).is_err());
+        assert!(store.installed_at(&root, true).is_err());
+        // A recorded checkout outside the download directories can be updated, but
+        // nothing may replace it
+        assert!(store.installed_at(&imported, false).is_ok());
+        assert!(store.installed_at(&imported, true).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn legacy_installs_are_imported_once() {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-installs-legacy-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let root = dir.join("downloads").join(INSTALL_DIR_NAME);
+        let legacy = serde_json::json!({
+            "roots": [root, dir.join("home")],
+            "installs": [{ "path": root.join("r"), "url": "https://github.com/o/r.git" }],
+        });
+        std::fs::write(dir.join("installs.json"), legacy.to_string()).unwrap();
+
+        let store = InstallStore::open(&dir.join("store.db"), &dir).unwrap();
+        assert!(!dir.join("installs.json").exists());
+        assert_eq!(store.roots().unwrap(), std::slice::from_ref(&root));
+        let repo = store.get("https://github.com/o/r.git").unwrap().unwrap();
+        assert_eq!((repo.owner.as_str(), repo.repo.as_str()), ("o", "r"));
+        assert_eq!(repo.name, "o/r");
+        assert_eq!(repo.status, "completed");
+        assert_eq!(Path::new(&repo.path), root.join("r"));
+
+        // Reopening finds nothing left to import
+        drop(store);
+        let store = InstallStore::open(&dir.join("store.db"), &dir).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn frontend_details_never_move_an_install() {
+        let (store, dir, root) = scratch_store("details");
+        let url = "https://github.com/o/r";
+        store
+            .begin_install(url, &root.join("r"), &CloneOptions::default())
+            .unwrap();
+
+        let mut details = installed(url, &dir.join("elsewhere"));
+        details.description = "描述".to_string();
+        details.stars = 42;
+        store.record_details(&details).unwrap();
+        let repo = store.get(url).unwrap().unwrap();
+        assert_eq!(Path::new(&repo.path), normalize(&root.join("r")));
+        assert_eq!(repo.description, "描述");
+        assert_eq!(repo.stars, 42);
+
+        // A row without a folder only takes one inside a download directory
+        store
+            .record_details(&installed("https://github.com/o/outside", &dir.join("x")))
+            .unwrap();
+        assert_eq!(
+            store
+                .get("https://github.com/o/outside")
+                .unwrap()
+                .unwrap()
+                .path,
+            ""
+        );
+        store
+            .record_details(&installed(
+                "https://github.com/o/inside",
+                &root.join("inside"),
+            ))
+            .unwrap();
+        let inside = store.get("https://github.com/o/inside").unwrap().unwrap();
+        assert_eq!(Path::new(&inside.path), normalize(&root.join("inside")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn install_status_survives_a_restart_as_interrupted() {
+        let (store, dir, root) = scratch_store("status");
+        let url = "https://github.com/o/r";
+        let options = CloneOptions {
+            reference: Some("v1.0".to_string()),
+            depth: Some(1),
+            ..CloneOptions::default()
+        };
+        assert!(store
+            .begin_install(url, &dir.join("outside"), &options)
+            .is_err());
+        store.begin_install(url, &root.join("r"), &options).unwrap();
+        assert_eq!(store.get(url).unwrap().unwrap().status, "cloning");
+        assert_eq!(
+            store.clone_options(url).unwrap().reference.as_deref(),
+            Some("v1.0")
+        );
+        assert!(store.completed().unwrap().is_empty());
+
+        store.set_status(url, "completed", "安装完成").unwrap();
+        assert_eq!(store.completed().unwrap().len(), 1);
+
+        store.set_status(url, "pulling", "").unwrap();
+        drop(store);
+        let store = InstallStore::open(&dir.join("store.db"), &dir).unwrap();
+        let repo = store.get(url).unwrap().unwrap();
+        assert_eq!(repo.status, "failed");
+        assert_eq!(repo.message, "操作被中断，请重试或修复");
+        assert_eq!(store.clone_options(url).unwrap().depth, Some(1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,473 @@
+// Consistency between the recorded installs and what is on disk. Folders get deleted,
+// moved or damaged by hand; `scan_installations` lists every mismatch together with the
+// repairs that apply to it, and `repair_installation` carries one out.
+use crate::installs::{self, InstallStore, InstalledRepo};
+use crate::updates::InstallMarker;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IssueKind {
+    /// The recorded folder no longer exists.
+    MissingFolder,
+    /// The folder exists but its `.git` cannot be opened or has no valid `HEAD`.
+    BrokenGit,
+    /// The folder has neither a `.git` nor the marker a ZIP install leaves, as when a
+    /// clone or download was cut short.
+    Incomplete,
+    /// `HEAD` is detached although the install was not pinned to a commit or tag.
+    DetachedHead,
+    /// A folder in a download directory that no install is recorded for.
+    UnknownFolder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RepairAction {
+    /// Keep the folder as it is and record it as installed.
+    Adopt,
+    /// Delete what is left of the folder and clone the repository again.
+    Reclone,
+    /// Drop the record, leaving the disk untouched.
+    Forget,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct InstallIssue {
+    pub kind: IssueKind,
+    pub path: String,
+    /// The recorded URL, or for unknown folders the one found in the folder.
+    pub url: Option<String>,
+    pub detail: String,
+    pub actions: Vec<RepairAction>,
+}
+
+/// What a git checkout on disk says about itself.
+pub(crate) enum CheckoutState {
+    Healthy {
+        /// Commit checked out.
+        head: String,
+        detached: bool,
+        /// A tag points at the checked out commit.
+        at_tag: bool,
+    },
+    Broken(String),
+}
+
+pub(crate) fn checkout_state(path: &Path) -> CheckoutState {
+    let repo = match git2::Repository::open(path) {
+        Ok(repo) => repo,
+        Err(e) => return CheckoutState::Broken(e.message().to_string()),
+    };
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) => return CheckoutState::Broken(e.message().to_string()),
+    };
+    let Some(oid) = head.target() else {
+        return CheckoutState::Broken("HEAD 未指向任何提交".to_string());
+    };
+    if repo.find_commit(oid).is_err() {
+        return CheckoutState::Broken(format!("HEAD 指向的提交 {} 不存在", oid));
+    }
+    let at_tag = repo
+        .tag_names(None)
+        .map(|names| {
+            names.iter().flatten().any(|name| {
+                repo.revparse_single(&format!("refs/tags/{}", name))
+                    .and_then(|object| object.peel_to_commit())
+                    .is_ok_and(|commit| commit.id() == oid)
+            })
+        })
+        .unwrap_or(false);
+    CheckoutState::Healthy {
+        head: oid.to_string(),
+        detached: repo.head_detached().unwrap_or(false),
+        at_tag,
+    }
+}
+
+/// The repository URL a folder was installed from: its `origin` remote, or the URL in
+/// the marker of a ZIP install.
+pub(crate) fn source_url(path: &Path) -> Option<String> {
+    if path.join(".git").exists() {
+        crate::git_backend::for_repository(path)?.remote_url(path)
+    } else {
+        InstallMarker::read(path).map(|marker| marker.url)
+    }
+}
+
+fn issue(
+    kind: IssueKind,
+    path: &Path,
+    url: Option<&str>,
+    detail: String,
+    actions: &[RepairAction],
+) -> InstallIssue {
+    InstallIssue {
+        kind,
+        path: path.to_string_lossy().to_string(),
+        url: url.map(str::to_string),
+        detail,
+        actions: actions.to_vec(),
+    }
+}
+
+fn check_install(
+    store: &InstallStore,
+    repo: &InstalledRepo,
+) -> Result<Option<InstallIssue>, String> {
+    use RepairAction::*;
+
+    let path = Path::new(&repo.path);
+    if !path.exists() {
+        return Ok(Some(issue(
+            IssueKind::MissingFolder,
+            path,
+            Some(&repo.url),
+            "安装目录已不存在，可能已被手动删除或移动".to_string(),
+            &[Reclone, Forget],
+        )));
+    }
+    if !path.join(".git").exists() {
+        // ZIP installs have no repository to check, only their marker
+        if InstallMarker::read(path).is_some() {
+            return Ok(None);
+        }
+        return Ok(Some(issue(
+            IssueKind::Incomplete,
+            path,
+            Some(&repo.url),
+            "安装目录中既没有 Git 仓库也没有安装记录，安装可能未完成".to_string(),
+            &[Reclone, Forget],
+        )));
+    }
+    match checkout_state(path) {
+        CheckoutState::Broken(error) => Ok(Some(issue(
+            IssueKind::BrokenGit,
+            path,
+            Some(&repo.url),
+            format!("Git 仓库已损坏: {}", error),
+            &[Reclone, Forget],
+        ))),
+        CheckoutState::Healthy {
+            head,
+            detached: true,
+            at_tag,
+        } => {
+            let options = store.clone_options(&repo.url)?;
+            if options.commit.is_some() || (options.reference.is_some() && at_tag) {
+                return Ok(None);
+            }
+            Ok(Some(issue(
+                IssueKind::DetachedHead,
+                path,
+                Some(&repo.url),
+                format!(
+                    "HEAD 处于游离状态（提交 {}），将无法跟随分支更新",
+                    crate::updates::short(&head)
+                ),
+                &[Adopt, Reclone, Forget],
+            )))
+        }
+        CheckoutState::Healthy { .. } => Ok(None),
+    }
+}
+
+/// Checks every recorded install and every folder in the download directories.
+/// Installs still being cloned or updated are skipped.
+pub(crate) fn scan(store: &InstallStore) -> Result<Vec<InstallIssue>, String> {
+    let mut issues = Vec::new();
+    let mut known = HashSet::new();
+    for repo in store.list()? {
+        if repo.path.is_empty() {
+            continue;
+        }
+        known.insert(installs::normalize(Path::new(&repo.path)));
+        if matches!(
+            repo.status.as_str(),
+            "cloning" | "downloading_zip" | "pulling"
+        ) {
+            continue;
+        }
+        issues.extend(check_install(store, &repo)?);
+    }
+
+    let roots = store.roots()?;
+    for root in &roots {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            continue;
+        };
+        let mut folders: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            // Hidden entries include the snapshots ZIP updates keep for rollback
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let path = installs::normalize(path);
+                !known.contains(&path) && !roots.contains(&path)
+            })
+            .collect();
+        folders.sort();
+        for path in folders {
+            let url = source_url(&path);
+            let (detail, actions) = match &url {
+                Some(_) => (
+                    "下载目录中有未被记录的仓库".to_string(),
+                    vec![RepairAction::Adopt],
+                ),
+                None => (
+                    "下载目录中有未被记录的文件夹，且无法识别其来源".to_string(),
+                    Vec::new(),
+                ),
+            };
+            issues.push(InstallIssue {
+                kind: IssueKind::UnknownFolder,
+                path: path.to_string_lossy().to_string(),
+                url,
+                detail,
+                actions,
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Records the folder at `path` as installed, as it is. A recorded install with a
+/// detached `HEAD` is pinned to the commit it is on; an unknown folder in a download
+/// directory is added to the Library under the URL it was installed from.
+pub(crate) fn adopt(store: &InstallStore, path: &Path) -> Result<String, String> {
+    if let Some(repo) = store.find_by_path(path)? {
+        let CheckoutState::Healthy { head, .. } = checkout_state(path) else {
+            return Err("该目录不是有效的 Git 仓库，无法保留".to_string());
+        };
+        let mut options = store.clone_options(&repo.url)?;
+        options.commit = Some(head.clone());
+        store.set_clone_options(&repo.url, &options)?;
+        return Ok(format!("已固定在提交 {}", crate::updates::short(&head)));
+    }
+
+    if !path.is_dir() || !store.is_managed(path)? {
+        return Err(format!("该目录不在下载目录内: {}", path.display()));
+    }
+    let url = source_url(path).ok_or("无法识别该目录的来源仓库")?;
+    if let Some(existing) = store.get(&url)? {
+        return Err(format!("该仓库已安装在 {}", existing.path));
+    }
+    let (owner, repo) = installs::owner_and_repo(&url);
+    store.record(&InstalledRepo {
+        name: format!("{}/{}", owner, repo),
+        url,
+        owner,
+        repo,
+        path: path.to_string_lossy().to_string(),
+        status: "completed".to_string(),
+        message: "已加入应用库".to_string(),
+        date_installed: installs::today(),
+        ..Default::default()
+    })?;
+    Ok("已加入应用库".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::CloneOptions;
+
+    /// A store in a fresh folder, with the download directory `downloads` inside it.
+    fn scratch_store(name: &str) -> (InstallStore, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "openstore-integrity-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = InstallStore::open(&dir.join("store.db"), &dir).unwrap();
+        let root = store.add_download_dir(&dir.join("downloads")).unwrap();
+        (store, dir, root)
+    }
+
+    fn record(store: &InstallStore, url: &str, path: &Path) {
+        store
+            .record(&InstalledRepo {
+                url: url.to_string(),
+                path: path.to_string_lossy().to_string(),
+                status: "completed".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    /// A repository at `path` with one commit and `origin` pointing at `url`.
+    fn checkout(path: &Path, url: &str) -> git2::Oid {
+        let repo = git2::Repository::init(path).unwrap();
+        repo.remote("origin", url).unwrap();
+        std::fs::write(path.join("README.md"), "readme").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.invalid").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap()
+    }
+
+    fn kinds(issues: &[InstallIssue]) -> Vec<(IssueKind, String)> {
+        issues
+            .iter()
+            .map(|issue| {
+                let name = Path::new(&issue.path).file_name().unwrap();
+                (issue.kind, name.to_string_lossy().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn healthy_installs_have_no_issues() {
+        let (store, dir, root) = scratch_store("healthy");
+        checkout(&root.join("git"), "https://github.com/o/git");
+        record(&store, "https://github.com/o/git", &root.join("git"));
+        std::fs::create_dir_all(root.join("zip")).unwrap();
+        InstallMarker {
+            url: "https://github.com/o/zip".to_string(),
+            reference: None,
+            commit: "abc".to_string(),
+            pinned: false,
+        }
+        .write(&root.join("zip"))
+        .unwrap();
+        record(&store, "https://github.com/o/zip", &root.join("zip"));
+        // Snapshots kept for rollback are not unknown folders
+        std::fs::create_dir_all(root.join(".zip.previous")).unwrap();
+
+        assert!(scan(&store).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn damaged_installs_are_classified() {
+        let (store, dir, root) = scratch_store("damaged");
+        record(
+            &store,
+            "https://github.com/o/missing",
+            &root.join("missing"),
+        );
+        std::fs::create_dir_all(root.join("broken").join(".git")).unwrap();
+        record(&store, "https://github.com/o/broken", &root.join("broken"));
+        std::fs::create_dir_all(root.join("incomplete")).unwrap();
+        record(
+            &store,
+            "https://github.com/o/incomplete",
+            &root.join("incomplete"),
+        );
+        // Installs still in progress are left alone
+        record(
+            &store,
+            "https://github.com/o/cloning",
+            &root.join("cloning"),
+        );
+        store
+            .set_status("https://github.com/o/cloning", "cloning", "")
+            .unwrap();
+
+        let issues = scan(&store).unwrap();
+        let mut found = kinds(&issues);
+        found.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(
+            found,
+            [
+                (IssueKind::BrokenGit, "broken".to_string()),
+                (IssueKind::Incomplete, "incomplete".to_string()),
+                (IssueKind::MissingFolder, "missing".to_string()),
+            ]
+        );
+        for issue in &issues {
+            assert_eq!(issue.actions, [RepairAction::Reclone, RepairAction::Forget]);
+            assert!(issue.url.is_some());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn detached_heads_are_flagged_unless_pinned() {
+        let (store, dir, root) = scratch_store("detached");
+        let url = "https://github.com/o/detached";
+        let path = root.join("detached");
+        let head = checkout(&path, url);
+        git2::Repository::open(&path)
+            .unwrap()
+            .set_head_detached(head)
+            .unwrap();
+        record(&store, url, &path);
+
+        let issues = scan(&store).unwrap();
+        assert_eq!(
+            kinds(&issues),
+            [(IssueKind::DetachedHead, "detached".to_string())]
+        );
+        assert_eq!(
+            issues[0].actions,
+            [
+                RepairAction::Adopt,
+                RepairAction::Reclone,
+                RepairAction::Forget
+            ]
+        );
+
+        // Adopting pins the install to the commit it is on
+        adopt(&store, &path).unwrap();
+        assert_eq!(
+            store.clone_options(url).unwrap().commit,
+            Some(head.to_string())
+        );
+        assert!(scan(&store).unwrap().is_empty());
+
+        // A tag checkout is expected to be detached
+        let options = CloneOptions {
+            reference: Some("v1".to_string()),
+            ..Default::default()
+        };
+        store.set_clone_options(url, &options).unwrap();
+        assert_eq!(scan(&store).unwrap().len(), 1);
+        let repo = git2::Repository::open(&path).unwrap();
+        repo.tag_lightweight("v1", &repo.find_object(head, None).unwrap(), false)
+            .unwrap();
+        assert!(scan(&store).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unknown_folders_can_be_adopted_when_their_source_is_known() {
+        let (store, dir, root) = scratch_store("unknown");
+        checkout(&root.join("clone"), "https://github.com/o/clone");
+        std::fs::create_dir_all(root.join("stray")).unwrap();
+        std::fs::write(root.join("loose.txt"), "").unwrap();
+
+        let issues = scan(&store).unwrap();
+        assert_eq!(
+            kinds(&issues),
+            [
+                (IssueKind::UnknownFolder, "clone".to_string()),
+                (IssueKind::UnknownFolder, "stray".to_string()),
+            ]
+        );
+        assert_eq!(issues[0].url.as_deref(), Some("https://github.com/o/clone"));
+        assert_eq!(issues[0].actions, [RepairAction::Adopt]);
+        assert_eq!(issues[1].url, None);
+        assert!(issues[1].actions.is_empty());
+
+        assert!(adopt(&store, &root.join("stray")).is_err());
+        adopt(&store, &root.join("clone")).unwrap();
+        let repo = store.get("https://github.com/o/clone").unwrap().unwrap();
+        assert_eq!(
+            (repo.name.as_str(), repo.status.as_str()),
+            ("o/clone", "completed")
+        );
+        assert_eq!(
+            kinds(&scan(&store).unwrap()),
+            [(IssueKind::UnknownFolder, "stray".to_string())]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
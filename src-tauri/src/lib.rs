@@ -3,16 +3,47 @@ use tauri::Emitter;
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-
-
-/// Managed state: maps download URL -> tokio watch Sender<bool> (true = cancel requested)
-struct DownloadRegistry(Mutex<HashMap<String, tokio::sync::watch::Sender<bool>>>);
-
-#[derive(serde::Serialize, Clone)]
-struct ProgressPayload {
-    repo_url: String,
-    status: String, // "cloning", "downloading_zip", "completed", "failed", "pulling"
-    message: String,
+mod checksum;
+mod credentials;
+mod dates;
+mod db;
+mod download;
+mod download_queue;
+mod error;
+mod forge;
+mod git;
+mod git_backend;
+mod history;
+mod importer;
+mod installs;
+mod integrity;
+mod libgit;
+mod paths;
+mod process;
+mod progress;
+mod retry;
+mod scheduler;
+mod throttle;
+mod updates;
+mod zipball;
+
+use credentials::CredentialStore;
+use download::DownloadRegistry;
+use download_queue::DownloadQueue;
+use error::CommandError;
+use forge::{ForgeConfig, ForgeRegistry};
+use history::DownloadHistory;
+use progress::ProgressPayload;
+use retry::{RetryPolicy, RetrySettings};
+use scheduler::UpdateScheduler;
+use throttle::BandwidthLimiter;
+
+/// Shared HTTP client settings for release assets and repository zipballs.
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .user_agent("GitAppStore")
+        .build()
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -20,28 +51,13 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Which git features are available: the built-in library, and the git CLI and LFS
+/// extension that sparse checkouts, SSH remotes and LFS objects fall back to.
 #[tauri::command]
-fn check_git_installed() -> bool {
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("cmd")
-            .args(&["/c", "git --version"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|status| status.success())
-            .unwrap_or(false)
-    }
-    #[cfg(not(target_os = "windows"))]
-    {
-        std::process::Command::new("git")
-            .arg("--version")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|status| status.success())
-            .unwrap_or(false)
-    }
+async fn check_git_support() -> Result<git_backend::GitSupport, String> {
+    tauri::async_runtime::spawn_blocking(git_backend::support)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -49,390 +65,726 @@ fn get_default_download_dir(app: tauri::AppHandle) -> Result<String, String> {
     use tauri::Manager;
     let download_dir = app.path().download_dir().map_err(|e| e.to_string())?;
 
-    let mut git_apps_dir = download_dir;
-    git_apps_dir.push("GitAppStore");
-
-    // Create directory if it doesn't exist
-    if !git_apps_dir.exists() {
-        std::fs::create_dir_all(&git_apps_dir).map_err(|e| e.to_string())?;
-    }
+    // Creates Downloads/GitAppStore if it doesn't exist
+    let git_apps_dir = app
+        .state::<installs::InstallStore>()
+        .add_download_dir(&download_dir)?;
 
     Ok(git_apps_dir.to_string_lossy().to_string())
 }
 
+/// Installs `repo_url` into a new folder in the GitAppStore folder of `target_dir`, which
+/// must be a registered download directory, and records it in the
+/// `InstallStore` under `record_as` (the URL the Library lists it by, when cloning from
+/// a mirror) or `repo_url`.
 #[tauri::command]
 async fn clone_repository(
     app: tauri::AppHandle,
     repo_url: String,
     target_dir: String,
     folder_name: String,
-    github_token: Option<String>,
-    gitee_token: Option<String>,
     use_zip: bool,
+    options: Option<git::CloneOptions>,
+    record_as: Option<String>,
+) -> Result<String, CommandError> {
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    git::validate_repo_url(&repo_url)?;
+    // Installs always go into the GitAppStore folder, which `register_download_dir` manages
+    let parent_path = installs::install_dir(std::path::Path::new(&target_dir));
+    let folder_name = paths::sanitize_name(&folder_name)?;
+    let path = paths::numbered_path(&parent_path, &folder_name, |candidate| !candidate.exists());
+
+    let record_url = record_as.unwrap_or_else(|| repo_url.clone());
+    Ok(install_recorded(app, &record_url, repo_url, path, use_zip, &options).await?)
+}
+
+/// `install_repository`, recorded in the `InstallStore` under `record_url`. The record
+/// is made before the clone starts, so a cancelled or half-finished install can still
+/// be uninstalled.
+async fn install_recorded(
+    app: tauri::AppHandle,
+    record_url: &str,
+    repo_url: String,
+    path: std::path::PathBuf,
+    use_zip: bool,
+    options: &git::CloneOptions,
 ) -> Result<String, String> {
-    let mut path = std::path::PathBuf::from(&target_dir);
-    let parent_path = path.clone();
-    path.push(&folder_name);
+    use tauri::Manager;
+
+    let store = app.state::<installs::InstallStore>();
+    store.begin_install(record_url, &path, options)?;
+    let result = install_repository(app.clone(), repo_url, path, use_zip, options).await;
+    let _ = match &result {
+        Ok(_) => store.set_status(record_url, "completed", "安装成功"),
+        Err(e) => store.set_status(record_url, "failed", e),
+    };
+    result
+}
+
+/// Clones `repo_url` into `path` with git, or installs its zipball there when git is
+/// unavailable or `use_zip` is set. An existing folder is replaced by the zip path.
+/// The token for the repository's host comes from the `CredentialStore`; `options`
+/// select the ref, depth and sparse paths on either path. LFS files are skipped, with
+/// a warning, when git-lfs is not installed.
+async fn install_repository(
+    app: tauri::AppHandle,
+    repo_url: String,
+    path: std::path::PathBuf,
+    use_zip: bool,
+    options: &git::CloneOptions,
+) -> Result<String, String> {
+    use tauri::Manager;
+
+    let parent_path = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
 
     let path_str = path.to_string_lossy().to_string();
-    let _parent_path_str = parent_path.to_string_lossy().to_string();
 
-    let git_installed = check_git_installed();
-    let is_gitee = repo_url.contains("gitee.com/");
+    let token = app.state::<CredentialStore>().token_for_url(&repo_url);
+    // Tokens must never show up in emitted messages or returned errors
+    let secrets = [token.as_deref()];
+    let redact = |text: &str| git::redact(text, &secrets);
+
+    // LFS is an extra on top of the clone: without git-lfs, clone anyway and say so
+    let mut options = options.clone();
+    let mut lfs_skipped = false;
+    if options.lfs && !use_zip && !(git::cli_installed() && git::lfs_installed()) {
+        options.lfs = false;
+        lfs_skipped = true;
+    }
+    let options = &options;
 
-    if !use_zip && git_installed {
+    let backend = if use_zip {
+        None
+    } else {
+        git_backend::for_clone(&repo_url, options)
+    };
+    if let Some(backend) = backend {
         let _ = app.emit(
             "download-progress",
-            ProgressPayload {
-                repo_url: repo_url.clone(),
-                status: "cloning".to_string(),
-                message: "正在使用 Git 克隆仓库...".to_string(),
-            },
+            ProgressPayload::new(
+                &repo_url,
+                "cloning",
+                format!("正在使用 Git 克隆仓库 ({})...", backend.name()),
+            ),
         );
 
-        // Inject Personal Access Token if provided
-        let clone_url = if is_gitee {
-            if let Some(ref token) = gitee_token {
-                if !token.trim().is_empty() {
-                    if repo_url.contains("gitee.com/") {
-                        repo_url.replace(
-                            "https://gitee.com/",
-                            &format!("https://oauth2:{}@gitee.com/", token),
-                        )
-                    } else {
-                        repo_url.replace("https://", &format!("https://oauth2:{}@", token))
-                    }
-                } else {
-                    repo_url.clone()
-                }
-            } else {
-                repo_url.clone()
-            }
-        } else {
-            if let Some(ref token) = github_token {
-                if !token.trim().is_empty() {
-                    if repo_url.contains("github.com/") {
-                        repo_url.replace(
-                            "https://github.com/",
-                            &format!("https://oauth2:{}@github.com/", token),
-                        )
-                    } else {
-                        repo_url.replace("https://", &format!("https://oauth2:{}@", token))
-                    }
-                } else {
-                    repo_url.clone()
-                }
-            } else {
-                repo_url.clone()
-            }
-        };
-
         // Create target directory parent if not exists
         let _ = std::fs::create_dir_all(&parent_path);
 
-        let output = if cfg!(target_os = "windows") {
-            std::process::Command::new("cmd")
-                .args(&[
-                    "/c",
-                    &format!("git clone \"{}\" \"{}\"", clone_url, path_str),
-                ])
-                .output()
-        } else {
-            std::process::Command::new("git")
-                .args(&["clone", &clone_url, &path_str])
-                .output()
-        };
+        // Forward clone progress as download-progress events
+        let progress_app = app.clone();
+        let progress_url = repo_url.clone();
+        let (clone_url, clone_path, clone_token, clone_options) =
+            (repo_url.clone(), path.clone(), token.clone(), options.clone());
+        let output = tauri::async_runtime::spawn_blocking(move || {
+            let mut last_percent = None;
+            let mut on_progress = |progress: progress::GitProgress| {
+                if last_percent == Some(progress.percent) {
+                    return;
+                }
+                last_percent = Some(progress.percent);
+                let message = match progress.phase {
+                    "receiving" => format!("正在接收对象 ({}/{})...", progress.current, progress.total),
+                    "resolving" => format!("正在处理差异 ({}/{})...", progress.current, progress.total),
+                    _ => "正在检出文件...".to_string(),
+                };
+                let _ = progress_app.emit(
+                    "download-progress",
+                    ProgressPayload::new(&progress_url, "cloning", message).with_progress(
+                        progress.received_bytes.unwrap_or(0),
+                        0,
+                        progress.percent,
+                    ),
+                );
+            };
+            backend.clone_repo(
+                &clone_url,
+                &clone_path,
+                &clone_options,
+                clone_token.as_deref(),
+                &mut on_progress,
+            )
+        })
+        .await
+        .map_err(|e| e.to_string())?;
 
         match output {
-            Ok(out) => {
-                if out.status.success() {
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "completed".to_string(),
-                            message: "克隆成功！".to_string(),
-                        },
-                    );
-                    Ok(path_str)
+            Ok(()) => {
+                let message = if lfs_skipped {
+                    "克隆成功！但未安装 Git LFS，已跳过 LFS 文件，这些文件只是指针文件。"
                 } else {
-                    let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "failed".to_string(),
-                            message: format!("Git 克隆失败: {}", stderr),
-                        },
-                    );
-                    Err(stderr)
-                }
+                    "克隆成功！"
+                };
+                let _ = app.emit(
+                    "download-progress",
+                    ProgressPayload::new(&repo_url, "completed", message)
+                        .with_progress(0, 0, 100),
+                );
+                Ok(path_str)
             }
-            Err(e) => {
-                let err_msg = format!("无法运行 git 进程: {}", e);
+            Err(stderr) => {
+                let stderr = redact(&stderr);
                 let _ = app.emit(
                     "download-progress",
-                    ProgressPayload {
-                        repo_url: repo_url.clone(),
-                        status: "failed".to_string(),
-                        message: err_msg.clone(),
-                    },
+                    ProgressPayload::new(&repo_url, "failed", format!("Git 克隆失败: {}", stderr)),
                 );
-                Err(err_msg)
+                Err(stderr)
             }
         }
     } else {
-        // Fallback to PowerShell web download & unzip
+        // Fallback to native zipball download & unzip
         let _ = app.emit(
             "download-progress",
-            ProgressPayload {
-                repo_url: repo_url.clone(),
-                status: "downloading_zip".to_string(),
-                message: "系统未检测到 Git 或选择 ZIP 方式下载，正在请求 ZIP 压缩包...".to_string(),
-            },
+            ProgressPayload::new(
+                &repo_url,
+                "downloading_zip",
+                "系统未检测到 Git 或选择 ZIP 方式下载，正在请求 ZIP 压缩包...",
+            ),
         );
 
-        let split_marker = if is_gitee { "gitee.com/" } else { "github.com/" };
-        let parts: Vec<&str> = repo_url.split(split_marker).collect();
-        if parts.len() < 2 {
-            return Err("无效的仓库 URL".to_string());
-        }
-        let repo_path_parts: Vec<&str> = parts[1].split('/').collect();
-        if repo_path_parts.len() < 2 {
-            return Err("无效的仓库 URL".to_string());
+        let (forge, repo) = app
+            .state::<ForgeRegistry>()
+            .resolve(&repo_url)
+            .ok_or_else(|| format!("不支持的代码托管平台，请先在设置中添加该实例: {}", repo_url))?;
+        let client = http_client()?;
+        // Download the exact commit the ref points to, so the marker records what was installed
+        let commit = forge::latest_commit(
+            &client,
+            forge.as_ref(),
+            &repo,
+            options.archive_ref(),
+            token.as_deref(),
+        )
+        .await
+        .ok()
+        .map(|commit| commit.sha);
+        let zip_url = forge::resolve_archive_url(
+            &client,
+            forge.as_ref(),
+            &repo,
+            commit.as_deref().or(options.archive_ref()),
+            token.as_deref(),
+        )
+        .await
+        .map_err(|e| redact(&e))?;
+        let mut request = client.get(&zip_url);
+        if let Some(token) = token.as_deref() {
+            request = forge.authorize(request, token);
         }
-        let owner = repo_path_parts[0];
-        let repo = repo_path_parts[1].trim_end_matches(".git");
-
-        let zip_url = if is_gitee {
-            format!("https://gitee.com/api/v5/repos/{}/{}/zipball", owner, repo)
-        } else {
-            format!("https://api.github.com/repos/{}/{}/zipball", owner, repo)
-        };
-
-        let mut temp_zip = parent_path.clone();
-        temp_zip.push(format!("{}_{}.zip", owner, repo));
-        let temp_zip_str = temp_zip.to_string_lossy().to_string();
-
-        let _ = std::fs::create_dir_all(&parent_path);
-
-        let active_token = if is_gitee { gitee_token.clone() } else { github_token.clone() };
-        let auth_header = if let Some(ref token) = active_token {
-            if !token.trim().is_empty() {
-                format!("'Authorization'='Bearer {}'; ", token)
+        let limiter = app.state::<BandwidthLimiter>();
+
+        // Emit byte progress every 1% (or every 256 KiB when the size is unknown)
+        let mut last_mark = 0;
+        let mut on_progress = |downloaded: u64, total: u64| {
+            let (mark, percent) = if total > 0 {
+                let percent = (downloaded as f64 / total as f64 * 100.0) as u32;
+                (percent as u64, percent)
             } else {
-                "".to_string()
+                (downloaded / (256 * 1024), 0)
+            };
+            if downloaded > 0 && mark == last_mark {
+                return;
             }
-        } else {
-            "".to_string()
+            last_mark = mark;
+            let _ = app.emit(
+                "download-progress",
+                ProgressPayload::new(&repo_url, "downloading_zip", "正在下载 ZIP 压缩包...")
+                    .with_progress(downloaded, total, percent),
+            );
         };
 
-        let mut temp_extract_dir = parent_path.clone();
-        temp_extract_dir.push(format!("{}_{}_temp_extract", owner, repo));
-        let temp_extract_str = temp_extract_dir.to_string_lossy().to_string();
-
-        // PowerShell script to download, extract, delete zip, rename first extracted directory, clean up temp dir.
-        let ps_script = format!(
-            "$ProgressPreference = 'SilentlyContinue'; \
-             if (Test-Path '{temp_extract_str}') {{ Remove-Item -Recurse -Force '{temp_extract_str}' }} \
-             New-Item -ItemType Directory -Path '{temp_extract_str}' -Force | Out-Null; \
-             $headers = @{{ 'User-Agent'='GitAppStore'; {auth_header} }}; \
-             Invoke-WebRequest -Headers $headers -Uri '{zip_url}' -OutFile '{temp_zip_str}'; \
-             Expand-Archive -Path '{temp_zip_str}' -DestinationPath '{temp_extract_str}' -Force; \
-             Remove-Item '{temp_zip_str}' -Force; \
-             $extracted = Get-ChildItem -Path '{temp_extract_str}' -Directory | Select-Object -First 1; \
-             $finalPath = '{path_str}'; \
-             if (Test-Path $finalPath) {{ Remove-Item -Recurse -Force $finalPath }} \
-             Move-Item -Path $extracted.FullName -Destination $finalPath -Force; \
-             Remove-Item -Recurse -Force '{temp_extract_str}';"
-        );
-
-        let output = std::process::Command::new("powershell")
-            .args(&["-ExecutionPolicy", "Bypass", "-Command", &ps_script])
-            .output();
-
-        match output {
-            Ok(out) => {
-                if out.status.success() {
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "completed".to_string(),
-                            message: "ZIP 下载并解压完成。".to_string(),
-                        },
-                    );
-                    Ok(path_str)
-                } else {
-                    let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "failed".to_string(),
-                            message: format!("ZIP 下载/解压失败: {}", stderr),
-                        },
-                    );
-                    Err(stderr)
+        let result = zipball::install_zipball(
+            request,
+            &format!("{}_{}", repo.owner.replace('/', "_"), repo.name),
+            &path,
+            &options.sparse_paths,
+            updates::zip_snapshot_path(&path).as_deref(),
+            &limiter.task(&repo_url),
+            &mut on_progress,
+        )
+        .await;
+
+        match result {
+            Ok(()) => {
+                if let Some(commit) = commit {
+                    // A tag is pinned like a commit, as a git clone of it is detached; if
+                    // the lookup fails the reference is followed like a branch
+                    let tag = match &options.reference {
+                        Some(reference) if options.commit.is_none() => !forge::is_branch(
+                            &client,
+                            forge.as_ref(),
+                            &repo,
+                            reference,
+                            token.as_deref(),
+                        )
+                        .await
+                        .unwrap_or(true),
+                        _ => false,
+                    };
+                    let marker = updates::InstallMarker {
+                        url: repo_url.clone(),
+                        reference: options.reference.clone(),
+                        commit,
+                        pinned: options.commit.is_some() || tag,
+                    };
+                    let _ = marker.write(&path);
                 }
+                // Archives leave submodule folders empty and may hold LFS pointer files
+                let features = git::RepoFeatures::detect(&path);
+                let message = match (features.submodules, features.lfs) {
+                    (false, false) => "ZIP 下载并解压完成。",
+                    (true, false) => "ZIP 下载并解压完成，但 ZIP 不包含子模块内容，源码可能不完整。",
+                    (false, true) => "ZIP 下载并解压完成，但 LFS 文件可能只是指针文件，源码可能不完整。",
+                    (true, true) => {
+                        "ZIP 下载并解压完成，但 ZIP 不包含子模块和 LFS 文件内容，源码可能不完整。"
+                    }
+                };
+                let _ = app.emit(
+                    "download-progress",
+                    ProgressPayload::new(&repo_url, "completed", message)
+                        .with_progress(0, 0, 100),
+                );
+                Ok(path_str)
             }
             Err(e) => {
-                let err_msg = format!("执行 PowerShell 命令失败: {}", e);
+                let e = redact(&e);
                 let _ = app.emit(
                     "download-progress",
-                    ProgressPayload {
-                        repo_url: repo_url.clone(),
-                        status: "failed".to_string(),
-                        message: err_msg.clone(),
-                    },
+                    ProgressPayload::new(&repo_url, "failed", format!("ZIP 下载/解压失败: {}", e)),
                 );
-                Err(err_msg)
+                Err(e)
             }
         }
     }
 }
 
+/// Checks whether an installed repository has new commits without changing its files:
+/// git installs fetch and compare with their upstream, ZIP installs ask the forge API.
 #[tauri::command]
-async fn pull_repository(
+async fn check_repository_updates(
     app: tauri::AppHandle,
     repo_path: String,
     repo_url: String,
-    github_token: Option<String>,
-    gitee_token: Option<String>,
-) -> Result<String, String> {
+) -> Result<updates::UpdateStatus, String> {
+    use tauri::Manager;
+
     let path = std::path::PathBuf::from(&repo_path);
-    let git_dir = path.join(".git");
+    if !path.is_dir() {
+        return Err(format!("安装目录不存在: {}", repo_path));
+    }
+    if let Some(backend) = git_backend::for_repository(&path) {
+        let remote_url = backend.remote_url(&path).unwrap_or_else(|| repo_url.clone());
+        let token = app.state::<CredentialStore>().token_for_url(&remote_url);
+        let secret = token.clone();
+        return tauri::async_runtime::spawn_blocking(move || {
+            backend.check(&path, &remote_url, token.as_deref())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| git::redact(&e, &[secret.as_deref()]));
+    }
+
+    let marker = updates::InstallMarker::read(&path);
+    let url = marker.as_ref().map_or(repo_url.as_str(), |marker| marker.url.as_str());
+    let (forge, repo) = app
+        .state::<ForgeRegistry>()
+        .resolve(url)
+        .ok_or_else(|| format!("不支持的代码托管平台: {}", url))?;
+    let token = app.state::<CredentialStore>().token_for_url(url);
+    let client = http_client()?;
+    updates::check_zip(&client, forge.as_ref(), &repo, marker.clone(), token.as_deref())
+        .await
+        .map_err(|e| git::redact(&e, &[token.as_deref()]))
+}
+
+/// Reports whether the repository at `repo_url` uses submodules or Git LFS, so the UI
+/// can offer them for git clones and warn that a ZIP install will be incomplete.
+#[tauri::command]
+async fn inspect_repository(
+    app: tauri::AppHandle,
+    repo_url: String,
+    reference: Option<String>,
+) -> Result<git::RepoFeatures, String> {
+    use tauri::Manager;
+
+    let (forge, repo) = app
+        .state::<ForgeRegistry>()
+        .resolve(&repo_url)
+        .ok_or_else(|| format!("不支持的代码托管平台: {}", repo_url))?;
+    let token = app.state::<CredentialStore>().token_for_url(&repo_url);
+    let client = http_client()?;
+    let fetch = |path: &'static str| {
+        forge::fetch_file(
+            &client,
+            forge.as_ref(),
+            &repo,
+            path,
+            reference.as_deref(),
+            token.as_deref(),
+        )
+    };
+    // Gitee takes the token in the query string, which request errors repeat
+    let redact = |e: String| git::redact(&e, &[token.as_deref()]);
+    let gitmodules = fetch(".gitmodules").await.map_err(redact)?;
+    let gitattributes = fetch(".gitattributes").await.map_err(redact)?;
+    Ok(git::RepoFeatures::from_files(
+        gitmodules.as_deref(),
+        gitattributes.as_deref(),
+    ))
+}
 
-    if git_dir.exists() && check_git_installed() {
+/// Updates the recorded install at `repo_path`. Git installs fast-forward to their
+/// upstream, handling local changes according to `strategy`; ZIP installs download the
+/// ref they follow again, which replaces the folder, so they must lie inside a download
+/// directory. Either way the previous state is kept for `rollback_repository`.
+/// The outcome is recorded in the `InstallStore`.
+#[tauri::command]
+async fn pull_repository(
+    app: tauri::AppHandle,
+    repo_path: String,
+    strategy: Option<updates::UpdateStrategy>,
+) -> Result<String, CommandError> {
+    use tauri::Manager;
+
+    let store = app.state::<installs::InstallStore>();
+    let path = std::path::PathBuf::from(&repo_path);
+    let repo_url = store.installed_at(&path, false)?.url;
+    let _ = store.set_status(&repo_url, "pulling", "正在检查更新...");
+    let result = update_installed(app.clone(), path, repo_url.clone(), strategy).await;
+    let _ = match &result {
+        Ok(message) => store.set_status(&repo_url, "completed", message),
+        Err(e) => store.set_status(&repo_url, "failed", e.message()),
+    };
+    result
+}
+
+async fn update_installed(
+    app: tauri::AppHandle,
+    path: std::path::PathBuf,
+    repo_url: String,
+    strategy: Option<updates::UpdateStrategy>,
+) -> Result<String, CommandError> {
+    use tauri::Manager;
+
+    if let Some(backend) = git_backend::for_repository(&path) {
         let _ = app.emit(
             "download-progress",
-            ProgressPayload {
-                repo_url: repo_url.clone(),
-                status: "pulling".to_string(),
-                message: "正在使用 git pull 检查更新...".to_string(),
-            },
+            ProgressPayload::new(&repo_url, "pulling", "正在使用 git 检查并拉取更新..."),
         );
 
-        let output = if cfg!(target_os = "windows") {
-            std::process::Command::new("cmd")
-                .args(&["/c", "git pull"])
-                .current_dir(&repo_path)
-                .output()
-        } else {
-            std::process::Command::new("git")
-                .arg("pull")
-                .current_dir(&repo_path)
-                .output()
-        };
+        // Authenticate against wherever origin points, which may be a fallback mirror
+        let remote_url = backend.remote_url(&path).unwrap_or_else(|| repo_url.clone());
+        let token = app.state::<CredentialStore>().token_for_url(&remote_url);
+        let secret = token.clone();
 
-        match output {
-            Ok(out) => {
-                if out.status.success() {
-                    let msg = String::from_utf8_lossy(&out.stdout).to_string();
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "completed".to_string(),
-                            message: format!("更新成功: {}", msg),
-                        },
-                    );
-                    Ok(msg)
-                } else {
-                    let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-                    let _ = app.emit(
-                        "download-progress",
-                        ProgressPayload {
-                            repo_url: repo_url.clone(),
-                            status: "failed".to_string(),
-                            message: format!("更新失败: {}", stderr),
-                        },
-                    );
-                    Err(stderr)
-                }
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            backend.pull(&path, &remote_url, token.as_deref(), strategy.unwrap_or_default())
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match result {
+            Ok(msg) => {
+                let _ = app.emit(
+                    "download-progress",
+                    ProgressPayload::new(&repo_url, "completed", format!("更新成功: {}", msg))
+                        .with_progress(0, 0, 100),
+                );
+                Ok(msg)
             }
             Err(e) => {
-                let err_msg = format!("执行 git pull 失败: {}", e);
+                let e = match e {
+                    CommandError::Message(message) => {
+                        CommandError::Message(git::redact(&message, &[secret.as_deref()]))
+                    }
+                    other => other,
+                };
                 let _ = app.emit(
                     "download-progress",
-                    ProgressPayload {
-                        repo_url: repo_url.clone(),
-                        status: "failed".to_string(),
-                        message: err_msg.clone(),
-                    },
+                    ProgressPayload::new(&repo_url, "failed", format!("更新失败: {}", e.message())),
                 );
-                Err(err_msg)
+                Err(e)
             }
         }
     } else {
-        // Zip fallback update: download again to overwrite
-        if let Some(parent) = path.parent() {
-            if let Some(folder_name) = path.file_name() {
-                let parent_dir = parent.to_string_lossy().to_string();
-                let folder_name_str = folder_name.to_string_lossy().to_string();
-
-                return clone_repository(
-                    app,
-                    repo_url,
-                    parent_dir,
-                    folder_name_str,
-                    github_token,
-                    gitee_token,
-                    true,
-                )
-                .await;
+        // Zip fallback update: download the followed ref again, keeping the old tree aside
+        if path.parent().is_some() && path.file_name().is_some() {
+            app.state::<installs::InstallStore>().installed_at(&path, true)?;
+            let marker = updates::InstallMarker::read(&path);
+            if let Some(marker) = marker.as_ref().filter(|marker| marker.pinned) {
+                return Ok(format!("已固定在提交 {}，不会更新", updates::short(&marker.commit)));
             }
+            let options = git::CloneOptions {
+                reference: marker.and_then(|marker| marker.reference),
+                ..Default::default()
+            };
+            return Ok(install_repository(app, repo_url, path, true, &options).await?);
         }
-        Err("未能获取仓库路径或名称".to_string())
+        Err("未能获取仓库路径或名称".to_string().into())
     }
 }
 
+/// Checks every completed install and updates the ones that are behind, at most
+/// `max_concurrent` at a time. Git installs only fast-forward, so repositories with
+/// local changes are reported as failed rather than touched, and installs pinned to a
+/// commit or tag are skipped. Each finished repository is emitted as
+/// `update-all-progress`.
 #[tauri::command]
-fn uninstall_repository(repo_path: String) -> Result<(), String> {
-    let path = std::path::PathBuf::from(&repo_path);
-    if path.exists() {
-        if path.is_dir() {
-            #[cfg(target_os = "windows")]
+async fn update_all_repositories(
+    app: tauri::AppHandle,
+    max_concurrent: Option<usize>,
+) -> Result<updates::UpdateSummary, String> {
+    use tauri::Manager;
+    use updates::BatchStatus;
+
+    let repos = app.state::<installs::InstallStore>().completed()?;
+    let update = |repo: installs::InstalledRepo| {
+        let app = app.clone();
+        async move {
+            match check_repository_updates(app.clone(), repo.path.clone(), repo.url.clone()).await
             {
-                // PowerShell recursive force remove is much more robust on Windows
-                let ps_cmd = format!("Remove-Item -Recurse -Force \"{}\"", repo_path);
-                let output = std::process::Command::new("powershell")
-                    .args(&["-Command", &ps_cmd])
-                    .output();
-                match output {
-                    Ok(out) => {
-                        if out.status.success() {
-                            Ok(())
-                        } else {
-                            Err(String::from_utf8_lossy(&out.stderr).to_string())
-                        }
-                    }
-                    Err(e) => Err(e.to_string()),
+                Ok(check) if !check.has_updates => {
+                    let message = if check.pinned {
+                        "已固定版本，跳过更新".to_string()
+                    } else {
+                        "已是最新版本".to_string()
+                    };
+                    let _ = app.emit(
+                        "download-progress",
+                        ProgressPayload::new(&repo.url, "completed", message.clone())
+                            .with_progress(0, 0, 100),
+                    );
+                    (BatchStatus::UpToDate, message, 0)
+                }
+                Ok(check) => match pull_repository(app.clone(), repo.path.clone(), None).await {
+                    Ok(message) => (BatchStatus::Updated, message, check.behind),
+                    Err(e) => (BatchStatus::Failed, e.message().to_string(), 0),
+                },
+                Err(e) => {
+                    let _ = app.emit(
+                        "download-progress",
+                        ProgressPayload::new(&repo.url, "failed", format!("检查更新失败: {}", e)),
+                    );
+                    (BatchStatus::Failed, e, 0)
                 }
             }
-            #[cfg(not(target_os = "windows"))]
-            {
-                std::fs::remove_dir_all(&path).map_err(|e| e.to_string())
+        }
+    };
+    let summary = updates::run_batch(repos, max_concurrent, update, |progress| {
+        let _ = app.emit("update-all-progress", progress);
+    })
+    .await;
+    Ok(summary)
+}
+
+#[tauri::command]
+fn get_update_check_interval(scheduler: tauri::State<'_, UpdateScheduler>) -> u64 {
+    scheduler.interval()
+}
+
+/// Sets the minutes between background update checks; 0 turns them off.
+#[tauri::command]
+fn set_update_check_interval(scheduler: tauri::State<'_, UpdateScheduler>, minutes: u64) {
+    scheduler.set_interval(minutes);
+}
+
+/// Runs a round of update checks right away, announcing new findings like the
+/// scheduler does, and returns every update found.
+#[tauri::command]
+async fn check_for_updates_now(app: tauri::AppHandle) -> Vec<scheduler::UpdateNotice> {
+    let notices = scheduler::run_round(&app).await;
+    scheduler::announce(&app, &notices);
+    notices
+}
+
+/// Restores the recorded install at `repo_path` to its state before the last
+/// `pull_repository`, including local changes that were stashed or reset away. A ZIP
+/// install's folder is swapped for the saved one, so it must lie inside a download
+/// directory.
+#[tauri::command]
+async fn rollback_repository(
+    app: tauri::AppHandle,
+    repo_path: String,
+) -> Result<String, CommandError> {
+    use tauri::Manager;
+
+    let path = std::path::PathBuf::from(&repo_path);
+    let backend = git_backend::for_repository(&path);
+    app.state::<installs::InstallStore>()
+        .installed_at(&path, backend.is_none())?;
+    tauri::async_runtime::spawn_blocking(move || match backend {
+        Some(backend) => backend.rollback(&path),
+        None => updates::rollback_zip(&path),
+    })
+    .await
+    .map_err(|e| CommandError::Message(e.to_string()))?
+}
+
+/// Removes an install made by the app, either permanently or by moving it to the
+/// trash, together with its Library record. Folders that are not recorded installs
+/// inside a download directory are refused. With `dry_run` nothing is touched and the
+/// report lists what would go.
+#[tauri::command]
+fn uninstall_repository(
+    app: tauri::AppHandle,
+    repo_path: String,
+    mode: Option<installs::UninstallMode>,
+    dry_run: Option<bool>,
+) -> Result<installs::UninstallReport, String> {
+    use tauri::Manager;
+
+    app.state::<installs::InstallStore>().uninstall(
+        std::path::Path::new(&repo_path),
+        mode.unwrap_or_default(),
+        dry_run.unwrap_or(false),
+    )
+}
+
+/// Compares the recorded installs with the disk and lists what no longer matches,
+/// together with the repairs `repair_installation` can make.
+#[tauri::command]
+async fn scan_installations(
+    app: tauri::AppHandle,
+) -> Result<Vec<integrity::InstallIssue>, String> {
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        integrity::scan(&app.state::<installs::InstallStore>())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Repairs the install at `path` as reported by `scan_installations`. Re-cloning
+/// replaces whatever is left of the folder with a fresh clone made with the options
+/// it was first installed with.
+#[tauri::command]
+async fn repair_installation(
+    app: tauri::AppHandle,
+    path: String,
+    action: integrity::RepairAction,
+) -> Result<String, CommandError> {
+    use integrity::RepairAction;
+    use tauri::Manager;
+
+    let path = std::path::PathBuf::from(&path);
+    let store = app.state::<installs::InstallStore>();
+    match action {
+        RepairAction::Adopt => {
+            let app = app.clone();
+            Ok(tauri::async_runtime::spawn_blocking(move || {
+                integrity::adopt(&app.state::<installs::InstallStore>(), &path)
+            })
+            .await
+            .map_err(|e| e.to_string())??)
+        }
+        RepairAction::Forget => {
+            let repo = store
+                .find_by_path(&path)?
+                .ok_or_else(|| "应用库中没有该目录的记录".to_string())?;
+            store.forget(&repo.url)?;
+            Ok("已从应用库中移除".to_string())
+        }
+        RepairAction::Reclone => {
+            let repo = store
+                .find_by_path(&path)?
+                .ok_or_else(|| "应用库中没有该目录的记录".to_string())?;
+            if !store.is_managed(&path)? {
+                return Err(format!("该目录不在下载目录内: {}", path.display()).into());
             }
-        } else {
-            std::fs::remove_file(&path).map_err(|e| e.to_string())
+            let options = store.clone_options(&repo.url)?;
+            if std::fs::symlink_metadata(&path).is_ok() {
+                paths::remove_tree(&path).map_err(|e| e.to_string())?;
+            }
+            Ok(install_recorded(app.clone(), &repo.url, repo.url.clone(), path, false, &options).await?)
         }
-    } else {
-        Ok(())
     }
 }
 
+/// Adds the git checkout at `path`, cloned outside the store, to the Library without
+/// moving or re-cloning it.
 #[tauri::command]
-fn open_in_explorer(path: String) -> Result<(), String> {
-    let output = if cfg!(target_os = "windows") {
-        std::process::Command::new("explorer").arg(&path).output()
-    } else if cfg!(target_os = "macos") {
-        std::process::Command::new("open").arg(&path).output()
-    } else {
-        std::process::Command::new("xdg-open").arg(&path).output()
-    };
+async fn import_local_repository(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<importer::LocalRepo, String> {
+    use tauri::Manager;
 
-    match output {
+    tauri::async_runtime::spawn_blocking(move || {
+        importer::import(
+            &app.state::<installs::InstallStore>(),
+            std::path::Path::new(&path),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Imports every git checkout found in `dir`, up to `max_depth` folders down.
+#[tauri::command]
+async fn import_local_repositories(
+    app: tauri::AppHandle,
+    dir: String,
+    max_depth: Option<usize>,
+) -> Result<importer::ImportSummary, String> {
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        importer::import_all(
+            &app.state::<installs::InstallStore>(),
+            std::path::Path::new(&dir),
+            max_depth.unwrap_or(importer::DEFAULT_SCAN_DEPTH),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Makes `dir`, the download directory set in the settings, one installs can be made
+/// in and uninstalled from. Only its GitAppStore folder is managed; returns that folder.
+#[tauri::command]
+fn register_download_dir(app: tauri::AppHandle, dir: String) -> Result<String, String> {
+    use tauri::Manager;
+    let root = app
+        .state::<installs::InstallStore>()
+        .add_download_dir(std::path::Path::new(&dir))?;
+    Ok(root.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn list_installed(app: tauri::AppHandle) -> Result<Vec<installs::InstalledRepo>, String> {
+    use tauri::Manager;
+    app.state::<installs::InstallStore>().list()
+}
+
+#[tauri::command]
+fn get_installed(
+    app: tauri::AppHandle,
+    url: String,
+) -> Result<Option<installs::InstalledRepo>, String> {
+    use tauri::Manager;
+    app.state::<installs::InstallStore>().get(&url)
+}
+
+/// Saves a Library entry, replacing the one with the same URL. The install folder is
+/// set by the backend when it installs or imports a repository, never from here.
+#[tauri::command]
+fn record_install(app: tauri::AppHandle, repo: installs::InstalledRepo) -> Result<(), String> {
+    use tauri::Manager;
+    app.state::<installs::InstallStore>().record_details(&repo)
+}
+
+/// Drops a Library entry without touching its folder.
+#[tauri::command]
+fn forget_install(app: tauri::AppHandle, url: String) -> Result<(), String> {
+    use tauri::Manager;
+    app.state::<installs::InstallStore>().forget(&url)
+}
+
+#[tauri::command]
+fn open_in_explorer(path: String) -> Result<(), String> {
+    match process::open_path(std::path::Path::new(&path)).output() {
         Ok(_) => Ok(()),
         Err(e) => Err(format!("无法打开文件夹: {}", e)),
     }
@@ -440,13 +792,11 @@ fn open_in_explorer(path: String) -> Result<(), String> {
 
 #[tauri::command]
 fn open_in_vscode(path: String) -> Result<(), String> {
-    let output = if cfg!(target_os = "windows") {
-        std::process::Command::new("cmd")
-            .args(&["/c", "code", &path])
-            .output()
-    } else {
-        std::process::Command::new("code").arg(&path).output()
-    };
+    let output = process::command(
+        process::Program::VsCode,
+        [process::operand(std::path::Path::new(&path))],
+    )
+    .output();
 
     match output {
         Ok(out) => {
@@ -464,52 +814,57 @@ fn open_in_vscode(path: String) -> Result<(), String> {
 async fn run_installer(
     app: tauri::AppHandle,
     filename: String,
-) -> Result<(), String> {
+    expected_hash: Option<String>,
+    hash_algorithm: Option<String>,
+) -> Result<(), CommandError> {
     use tauri::Manager;
 
+    let filename = paths::sanitize_name(&filename)?;
     let download_dir = app.path().download_dir().map_err(|e| e.to_string())?;
     let mut path = download_dir;
     path.push("GitAppStore");
     path.push(&filename);
 
     if !path.exists() {
-        return Err(format!("安装包文件不存在：{}", path.display()));
+        return Err(format!("安装包文件不存在：{}", path.display()).into());
     }
 
-    let path_str = path.to_string_lossy().to_string();
-
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("cmd")
-            .args(&["/c", "start", "", &path_str])
-            .spawn()
-            .map_err(|e| format!("无法启动安装包: {}", e))?;
-    }
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&path_str)
-            .spawn()
-            .map_err(|e| format!("无法启动安装包: {}", e))?;
-    }
-    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&path_str)
-            .spawn()
-            .map_err(|e| format!("无法启动安装包: {}", e))?;
+    // Refuse to launch an installer whose content does not match the published digest
+    if let Some(expected) = parse_expected_digest(expected_hash, hash_algorithm)? {
+        let file_path = path.clone();
+        tauri::async_runtime::spawn_blocking(move || checksum::verify_file(&file_path, &expected))
+            .await
+            .map_err(|e| e.to_string())??;
     }
 
+    process::open_path(&path)
+        .spawn()
+        .map_err(|e| format!("无法启动安装包: {}", e))?;
+
     Ok(())
 }
 
-#[derive(serde::Serialize, Clone)]
-struct AssetProgressPayload {
-    url: String,
-    downloaded: u64,
-    total: u64,
-    percent: u32,
-    status: String,
+/// Sends the "下载完成" notification for a finished release asset.
+fn notify_download_complete(app: &tauri::AppHandle, filename: &str) {
+    use tauri_plugin_notification::NotificationExt;
+
+    let _ = app
+        .notification()
+        .builder()
+        .title("下载完成")
+        .body(format!("{} 已成功下载并保存至下载文件夹。", filename))
+        .show();
+}
+
+/// Parses the optional digest arguments shared by the download and installer commands.
+fn parse_expected_digest(
+    expected_hash: Option<String>,
+    hash_algorithm: Option<String>,
+) -> Result<Option<checksum::ExpectedDigest>, String> {
+    match expected_hash.filter(|hash| !hash.trim().is_empty()) {
+        Some(hash) => checksum::ExpectedDigest::parse(&hash, hash_algorithm.as_deref()).map(Some),
+        None => Ok(None),
+    }
 }
 
 #[tauri::command]
@@ -517,131 +872,235 @@ async fn download_release_asset(
     app: tauri::AppHandle,
     url: String,
     filename: String,
-) -> Result<String, String> {
-    use tauri::Manager;
-    use tauri_plugin_notification::NotificationExt;
-    use futures_util::StreamExt;
-    use std::io::Write;
+    expected_hash: Option<String>,
+    hash_algorithm: Option<String>,
+    priority: Option<i32>,
+    rate_limit: Option<u64>,
+) -> Result<String, CommandError> {
+    let expected = parse_expected_digest(expected_hash, hash_algorithm)?;
+    let filename = paths::sanitize_name(&filename)?;
+    let target_path = download::unique_target(&app, &download::store_dir(&app)?, &filename, &url);
+    let filename = target_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(filename);
+    let path = download::run_download(
+        &app,
+        &url,
+        &filename,
+        &target_path,
+        expected.as_ref(),
+        priority.unwrap_or(0),
+        rate_limit,
+    )
+    .await?;
+    notify_download_complete(&app, &filename);
+    Ok(path)
+}
 
-    // 1. Get the download directory
-    let download_dir = app.path().download_dir().map_err(|e| e.to_string())?;
+/// Resumes a paused download, or restarts an unfinished one left in the GitAppStore
+/// folder (e.g. after the app was closed or the connection dropped). Returns as soon
+/// as the download is running again; progress arrives via `asset-download-progress`.
+#[tauri::command]
+async fn resume_download(app: tauri::AppHandle, url: String) -> Result<(), String> {
+    if download::set_control(&app, &url, download::DownloadControl::Running) {
+        return Ok(());
+    }
 
-    let mut target_path = download_dir;
-    target_path.push("GitAppStore");
+    let store_dir = download::store_dir(&app)?;
+    let partial = download::find_partial(&store_dir, &url)
+        .ok_or_else(|| "未找到可恢复的下载任务".to_string())?;
+
+    let expected = parse_expected_digest(partial.expected_hash.clone(), None)?;
+
+    tauri::async_runtime::spawn(async move {
+        let target_path = store_dir.join(&partial.filename);
+        let result = download::run_download(
+            &app,
+            &url,
+            &partial.filename,
+            &target_path,
+            expected.as_ref(),
+            0,
+            None,
+        )
+        .await;
+        if result.is_ok() {
+            notify_download_complete(&app, &partial.filename);
+        }
+    });
 
-    // Create directory if it doesn't exist
-    if !target_path.exists() {
-        std::fs::create_dir_all(&target_path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_download(app: tauri::AppHandle, url: String) -> Result<(), String> {
+    if download::set_control(&app, &url, download::DownloadControl::Paused) {
+        Ok(())
+    } else {
+        Err("未找到对应的下载任务".to_string())
     }
+}
 
-    target_path.push(&filename);
-    let target_path_str = target_path.to_string_lossy().to_string();
+#[tauri::command]
+fn list_downloads(queue: tauri::State<'_, DownloadQueue>) -> Vec<download_queue::DownloadTask> {
+    queue.list()
+}
 
-    // 2. Register a cancellation watch channel for this URL
-    let (cancel_tx, cancel_rx) = tokio::sync::watch::channel(false);
-    {
-        let registry = app.state::<DownloadRegistry>();
-        let mut map = registry.0.lock().unwrap();
-        map.insert(url.clone(), cancel_tx);
-    }
+#[tauri::command]
+fn set_download_priority(
+    queue: tauri::State<'_, DownloadQueue>,
+    url: String,
+    priority: i32,
+) -> Result<(), String> {
+    queue.set_priority(&url, priority)
+}
 
-    // Helper: cleanup on cancel or error
-    let cleanup = |url: &str, path: &std::path::PathBuf, app: &tauri::AppHandle| {
-        let _ = std::fs::remove_file(path);
-        let registry = app.state::<DownloadRegistry>();
-        let mut map = registry.0.lock().unwrap();
-        map.remove(url);
-    };
+#[tauri::command]
+fn move_download(
+    queue: tauri::State<'_, DownloadQueue>,
+    url: String,
+    position: usize,
+) -> Result<(), String> {
+    queue.move_to(&url, position)
+}
 
-    // 3. Download the file using reqwest stream
-    let client = reqwest::Client::builder()
-        .user_agent("GitAppStore")
-        .build()
-        .map_err(|e| e.to_string())?;
+/// Stores the token used for git operations against `host` (e.g. "github.com").
+/// An empty token removes it.
+#[tauri::command]
+fn set_credential(
+    store: tauri::State<'_, CredentialStore>,
+    host: String,
+    token: String,
+) -> Result<(), String> {
+    store.set(&host, &token)
+}
 
-    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+#[tauri::command]
+fn delete_credential(store: tauri::State<'_, CredentialStore>, host: String) -> Result<(), String> {
+    store.delete(&host)
+}
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP 错误，状态码: {}", response.status()));
-    }
+#[tauri::command]
+fn has_credential(store: tauri::State<'_, CredentialStore>, host: String) -> Result<bool, String> {
+    store.has(&host)
+}
 
-    let total_size = response.content_length().unwrap_or(0);
-    
-    // Create the file
-    let mut file = std::fs::File::create(&target_path).map_err(|e| e.to_string())?;
-    let mut downloaded: u64 = 0;
-    let mut stream = response.bytes_stream();
-    
-    // Initial emit
-    let _ = app.emit("asset-download-progress", AssetProgressPayload {
-        url: url.clone(),
-        downloaded: 0,
-        total: total_size,
-        percent: 0,
-        status: "downloading".to_string(),
+/// GETs a forge API `url` for the frontend's listings, searches and repository pages.
+/// The token stored for the forge's host is added here, so stored tokens never reach
+/// the webview. `kind` says how to send a token when `url` is on no registered forge,
+/// and `token` is only for testing one the user has just typed.
+#[tauri::command]
+async fn forge_api_request(
+    app: tauri::AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+    kind: Option<forge::ForgeKind>,
+    token: Option<String>,
+) -> Result<forge::ApiResponse, String> {
+    use tauri::Manager;
+
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("无效的 API 地址: {}", url));
+    }
+    let registered = app.state::<ForgeRegistry>().for_api_url(&url);
+    let token = token.filter(|token| !token.trim().is_empty()).or_else(|| {
+        let host = registered.as_ref().map_or(url.as_str(), |forge| forge.base_url());
+        app.state::<CredentialStore>().token_for_url(host)
+    });
+    let forge = registered.or_else(|| {
+        kind.map(|kind| {
+            ForgeConfig {
+                kind,
+                base_url: url.clone(),
+                api_base: Some(url.clone()),
+                builtin: false,
+            }
+            .build()
+        })
     });
+    let client = http_client()?;
+    forge::api_request(
+        &client,
+        forge.as_deref(),
+        &url,
+        &headers.unwrap_or_default(),
+        token.as_deref(),
+    )
+    .await
+    .map_err(|e| git::redact(&e, &[token.as_deref()]))
+}
 
-    let mut last_emitted_percent = 0;
-
-    while let Some(chunk_result) = stream.next().await {
-        // Check for cancellation before processing each chunk
-        if *cancel_rx.borrow() {
-            cleanup(&url, &target_path, &app);
-            let _ = app.emit("asset-download-progress", AssetProgressPayload {
-                url: url.clone(),
-                downloaded,
-                total: total_size,
-                percent: last_emitted_percent,
-                status: "cancelled".to_string(),
-            });
-            return Err("已取消下载".to_string());
-        }
+/// Built-in forges followed by the user's self-hosted instances.
+#[tauri::command]
+fn list_forges(registry: tauri::State<'_, ForgeRegistry>) -> Vec<ForgeConfig> {
+    registry.list()
+}
 
-        let chunk = chunk_result.map_err(|e| e.to_string())?;
-        file.write_all(&chunk).map_err(|e| e.to_string())?;
-        downloaded += chunk.len() as u64;
-
-        if total_size > 0 {
-            let percent = (downloaded as f64 / total_size as f64 * 100.0) as u32;
-            // Throttle emissions to avoid flooding the IPC bridge (emit every 1% change)
-            if percent > last_emitted_percent {
-                last_emitted_percent = percent;
-                let _ = app.emit("asset-download-progress", AssetProgressPayload {
-                    url: url.clone(),
-                    downloaded,
-                    total: total_size,
-                    percent,
-                    status: "downloading".to_string(),
-                });
-            }
-        }
-    }
+/// Registers a self-hosted GitHub Enterprise, Gitee, GitLab or Gitea/Forgejo instance,
+/// replacing any existing entry with the same base URL.
+#[tauri::command]
+fn add_forge(registry: tauri::State<'_, ForgeRegistry>, forge: ForgeConfig) -> Result<(), String> {
+    registry.add(forge)
+}
 
-    // Final emit
-    let _ = app.emit("asset-download-progress", AssetProgressPayload {
-        url: url.clone(),
-        downloaded: total_size,
-        total: total_size,
-        percent: 100,
-        status: "completed".to_string(),
-    });
+#[tauri::command]
+fn remove_forge(registry: tauri::State<'_, ForgeRegistry>, base_url: String) -> Result<(), String> {
+    registry.remove(&base_url)
+}
+
+#[tauri::command]
+fn set_max_concurrent_downloads(queue: tauri::State<'_, DownloadQueue>, max: usize) {
+    queue.set_max_concurrent(max);
+}
 
-    // Remove from registry
-    {
-        let registry = app.state::<DownloadRegistry>();
-        let mut map = registry.0.lock().unwrap();
-        map.remove(&url);
+/// Sets a bandwidth limit in bytes per second (0 = unlimited). With a `url` the limit
+/// applies to that download only, otherwise it is shared by all downloads.
+#[tauri::command]
+fn set_download_rate_limit(
+    limiter: tauri::State<'_, BandwidthLimiter>,
+    url: Option<String>,
+    bytes_per_second: u64,
+) {
+    match url {
+        Some(url) => limiter.set_task_limit(&url, bytes_per_second),
+        None => limiter.set_global_limit(bytes_per_second),
     }
+}
 
-    // 5. Send notification
-    let _ = app
-        .notification()
-        .builder()
-        .title("下载完成")
-        .body(format!("{} 已成功下载并保存至下载文件夹。", filename))
-        .show();
+#[tauri::command]
+fn get_download_rate_limits(
+    limiter: tauri::State<'_, BandwidthLimiter>,
+) -> throttle::BandwidthLimits {
+    limiter.limits()
+}
 
-    Ok(target_path_str)
+#[tauri::command]
+fn get_download_retry_policy(settings: tauri::State<'_, RetrySettings>) -> RetryPolicy {
+    *settings.0.lock().unwrap()
+}
+
+/// Replaces the retry policy; downloads already running keep the one they started with.
+#[tauri::command]
+fn set_download_retry_policy(settings: tauri::State<'_, RetrySettings>, policy: RetryPolicy) {
+    *settings.0.lock().unwrap() = policy;
+}
+
+#[tauri::command]
+fn get_download_history(
+    history: tauri::State<'_, DownloadHistory>,
+) -> Result<Vec<history::HistoryEntry>, String> {
+    history.load()
+}
+
+#[tauri::command]
+fn clear_download_history(history: tauri::State<'_, DownloadHistory>) -> Result<(), String> {
+    history.clear()
+}
+
+#[tauri::command]
+fn list_partial_downloads(app: tauri::AppHandle) -> Result<Vec<download::PartialDownload>, String> {
+    Ok(download::list_partials(&download::store_dir(&app)?))
 }
 
 #[tauri::command]
@@ -649,14 +1108,18 @@ async fn cancel_download(
     app: tauri::AppHandle,
     url: String,
 ) -> Result<(), String> {
-    use tauri::Manager;
-    let registry = app.state::<DownloadRegistry>();
-    let map = registry.0.lock().unwrap();
-    if let Some(tx) = map.get(&url) {
-        let _ = tx.send(true);
-        Ok(())
-    } else {
-        Err("未找到对应的下载任务".to_string())
+    if download::set_control(&app, &url, download::DownloadControl::Cancelled) {
+        return Ok(());
+    }
+
+    // Not running: drop the leftover partial file of an interrupted download instead
+    let store_dir = download::store_dir(&app)?;
+    match download::find_partial(&store_dir, &url) {
+        Some(partial) => {
+            download::discard_partial(&store_dir.join(&partial.filename));
+            Ok(())
+        }
+        None => Err("未找到对应的下载任务".to_string()),
     }
 }
 
@@ -668,6 +1131,31 @@ async fn cancel_download(
 pub fn run() {
     tauri::Builder::default()
         .manage(DownloadRegistry(Mutex::new(HashMap::new())))
+        .manage(DownloadQueue::new(download_queue::DEFAULT_MAX_CONCURRENT))
+        .manage(BandwidthLimiter::new(0))
+        .manage(RetrySettings(Mutex::new(RetryPolicy::default())))
+        .manage(UpdateScheduler::new(scheduler::DEFAULT_INTERVAL_MINUTES))
+        .setup(|app| {
+            use tauri::Manager;
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(DownloadHistory::new(data_dir.clone()));
+            app.manage(ForgeRegistry::new(data_dir.clone()));
+            // The same file the frontend opens as `sqlite:openstore.db`
+            let db_path = app.path().app_config_dir()?.join("openstore.db");
+            let installs = installs::InstallStore::open(&db_path, &data_dir)?;
+            if let Ok(download_dir) = app.path().download_dir() {
+                let _ = installs.add_download_dir(&download_dir);
+            }
+            app.manage(installs);
+            let credentials = CredentialStore::for_platform(&app.config().identifier, data_dir);
+            // Data-source tokens the frontend once saved in plain text move to the store;
+            // whatever fails to move is tried again on the next start
+            let _ = db::open(&db_path)
+                .and_then(|conn| credentials::migrate_source_tokens(&conn, &credentials));
+            app.manage(credentials);
+            scheduler::spawn(app.handle().clone());
+            Ok(())
+        })
         .plugin(tauri_plugin_sql::Builder::new().build())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_fs::init())
@@ -675,15 +1163,51 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             greet,
-            check_git_installed,
+            check_git_support,
             get_default_download_dir,
             clone_repository,
+            inspect_repository,
+            check_repository_updates,
             pull_repository,
+            rollback_repository,
+            update_all_repositories,
+            get_update_check_interval,
+            set_update_check_interval,
+            check_for_updates_now,
             uninstall_repository,
+            register_download_dir,
+            scan_installations,
+            repair_installation,
+            import_local_repository,
+            import_local_repositories,
+            list_installed,
+            get_installed,
+            record_install,
+            forget_install,
             open_in_explorer,
             open_in_vscode,
             run_installer,
             download_release_asset,
+            pause_download,
+            resume_download,
+            list_partial_downloads,
+            list_downloads,
+            set_download_priority,
+            move_download,
+            set_max_concurrent_downloads,
+            set_credential,
+            delete_credential,
+            has_credential,
+            forge_api_request,
+            list_forges,
+            add_forge,
+            remove_forge,
+            set_download_rate_limit,
+            get_download_rate_limits,
+            get_download_retry_policy,
+            set_download_retry_policy,
+            get_download_history,
+            clear_download_history,
             cancel_download
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,683 @@
+// The libgit2 git backend. It mirrors what the CLI backend does with `git clone`,
+// `git fetch` and friends, including the rollback refs of `updates`, so either backend
+// can update or roll back a checkout made by the other.
+use crate::error::CommandError;
+use crate::git::{self, CloneOptions};
+use crate::git_backend::GitBackend;
+use crate::progress::GitProgress;
+use crate::updates::{
+    short, CommitSummary, UpdateError, UpdateErrorKind, UpdateStatus, UpdateStrategy, MAX_COMMITS,
+    ROLLBACK_CHANGES, ROLLBACK_HEAD,
+};
+use git2::build::CheckoutBuilder;
+use git2::{
+    ApplyLocation, AutotagOption, BranchType, DiffOptions, FetchOptions, Oid, ProxyOptions,
+    RemoteCallbacks, Repository, ResetType, Signature, StashFlags, StatusOptions,
+    SubmoduleUpdateOptions,
+};
+use std::path::Path;
+
+pub(crate) struct LibGit;
+
+/// What backend selection needs to know about an existing checkout.
+pub(crate) struct CheckoutInfo {
+    pub remote_url: String,
+    /// `core.sparseCheckout` is on, which libgit2 ignores.
+    pub sparse: bool,
+}
+
+impl LibGit {
+    pub fn inspect(repo_path: &Path) -> Option<CheckoutInfo> {
+        let repo = Repository::open(repo_path).ok()?;
+        let remote_url = repo.find_remote("origin").ok()?.url()?.to_string();
+        let sparse = repo
+            .config()
+            .and_then(|config| config.get_bool("core.sparseCheckout"))
+            .unwrap_or(false);
+        Some(CheckoutInfo { remote_url, sparse })
+    }
+}
+
+fn message(error: git2::Error) -> String {
+    error.message().to_string()
+}
+
+/// Fetch options for `remote_url`: the token as an auth header, the system proxy and,
+/// for shallow clones, the depth.
+fn fetch_options<'a>(
+    remote_url: &str,
+    token: Option<&str>,
+    depth: Option<u32>,
+) -> FetchOptions<'a> {
+    let mut fetch = FetchOptions::new();
+    let mut proxy = ProxyOptions::new();
+    proxy.auto();
+    fetch.proxy_options(proxy);
+    if let (Some(header), Some(_)) = (git::auth_header(token), git::origin_of(remote_url)) {
+        fetch.custom_headers(&[&header]);
+    }
+    if let Some(depth) = depth {
+        fetch.depth(depth.min(i32::MAX as u32) as i32);
+    }
+    fetch
+}
+
+/// Reports received objects, then resolved deltas, like `git clone --progress`.
+fn with_progress<'a>(fetch: &mut FetchOptions<'a>, on_progress: &'a mut dyn FnMut(GitProgress)) {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.transfer_progress(move |stats| {
+        let progress = if stats.received_objects() < stats.total_objects() {
+            GitProgress::new(
+                "receiving",
+                stats.received_objects() as u64,
+                stats.total_objects() as u64,
+                Some(stats.received_bytes() as u64),
+            )
+        } else {
+            GitProgress::new(
+                "resolving",
+                stats.indexed_deltas() as u64,
+                stats.total_deltas() as u64,
+                None,
+            )
+        };
+        on_progress(progress);
+        true
+    });
+    fetch.remote_callbacks(callbacks);
+}
+
+/// Overwrites the worktree and index with `target`, as the checkout of a fresh clone.
+fn checkout(
+    repo: &Repository,
+    target: &git2::Object,
+    on_progress: &mut dyn FnMut(GitProgress),
+) -> Result<(), git2::Error> {
+    let mut builder = CheckoutBuilder::new();
+    builder.force().progress(|_, current, total| {
+        on_progress(GitProgress::new(
+            "checkout",
+            current as u64,
+            total as u64,
+            None,
+        ))
+    });
+    repo.checkout_tree(target, Some(&mut builder))
+}
+
+fn clone_into(
+    repo: &Repository,
+    repo_url: &str,
+    options: &CloneOptions,
+    token: Option<&str>,
+    on_progress: &mut dyn FnMut(GitProgress),
+) -> Result<(), git2::Error> {
+    let mut remote = repo.remote("origin", repo_url)?;
+    {
+        let mut fetch = fetch_options(repo_url, token, options.depth);
+        with_progress(&mut fetch, &mut *on_progress);
+        // Tags too, so a tag works as `reference` like with `git clone --branch`
+        fetch.download_tags(AutotagOption::All);
+        remote.fetch(&[] as &[&str], Some(&mut fetch), None)?;
+    }
+    let default_branch = remote.default_branch().ok().and_then(|name| {
+        name.as_str()
+            .map(|name| name.trim_start_matches("refs/heads/").to_string())
+    });
+    if let Some(branch) = &default_branch {
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            &format!("refs/remotes/origin/{}", branch),
+            true,
+            "clone",
+        )?;
+    }
+
+    if let Some(commit) = &options.commit {
+        if options.depth.is_some() {
+            // A shallow clone only holds the branch tips, so fetch the pinned commit explicitly
+            remote.fetch(
+                &[commit.as_str()],
+                Some(&mut fetch_options(repo_url, token, options.depth)),
+                None,
+            )?;
+        }
+        let target = repo.revparse_single(commit)?.peel_to_commit()?;
+        checkout(repo, target.as_object(), on_progress)?;
+        repo.set_head_detached(target.id())?;
+    } else if let Some(name) = options.reference.as_ref().or(default_branch.as_ref()) {
+        if let Ok(remote_branch) = repo.find_branch(&format!("origin/{}", name), BranchType::Remote)
+        {
+            let target = remote_branch.get().peel_to_commit()?;
+            let mut branch = repo.branch(name, &target, true)?;
+            branch.set_upstream(Some(&format!("origin/{}", name)))?;
+            checkout(repo, target.as_object(), on_progress)?;
+            repo.set_head(&format!("refs/heads/{}", name))?;
+        } else if let Ok(tag) = repo.revparse_single(&format!("refs/tags/{}", name)) {
+            let target = tag.peel_to_commit()?;
+            checkout(repo, target.as_object(), on_progress)?;
+            repo.set_head_detached(target.id())?;
+        } else {
+            return Err(git2::Error::from_str(&format!(
+                "远程仓库中没有分支或标签: {}",
+                name
+            )));
+        }
+    }
+
+    if options.submodules {
+        update_submodules(repo, repo_url, token, options.depth)?;
+    }
+    Ok(())
+}
+
+/// Initialises and checks out every submodule recursively, like
+/// `git submodule update --init --recursive`.
+fn update_submodules(
+    repo: &Repository,
+    repo_url: &str,
+    token: Option<&str>,
+    depth: Option<u32>,
+) -> Result<(), git2::Error> {
+    for mut submodule in repo.submodules()? {
+        let url = submodule.url().unwrap_or_default().to_string();
+        // The token only goes to submodules on the same host, as with the CLI
+        let token = token.filter(|_| git::origin_of(&url) == git::origin_of(repo_url));
+        let mut update = SubmoduleUpdateOptions::new();
+        update.fetch(fetch_options(&url, token, depth));
+        submodule.update(true, Some(&mut update))?;
+        update_submodules(&submodule.open()?, &url, token, depth)?;
+    }
+    Ok(())
+}
+
+fn open(repo_path: &Path) -> Result<Repository, String> {
+    Repository::open(repo_path).map_err(message)
+}
+
+fn fetch_origin(
+    repo: &Repository,
+    remote_url: &str,
+    token: Option<&str>,
+) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote("origin")?;
+    remote.fetch(
+        &[] as &[&str],
+        Some(&mut fetch_options(remote_url, token, None)),
+        None,
+    )
+}
+
+/// The branch `HEAD` tracks, if it is on one that has an upstream.
+fn tracked_upstream(repo: &Repository) -> Option<Oid> {
+    let head = repo.head().ok()?;
+    let upstream = repo.branch_upstream_name(head.name()?).ok()?;
+    repo.refname_to_id(upstream.as_str()?).ok()
+}
+
+/// Formats a commit time like git's `%cI`.
+fn iso8601(time: git2::Time) -> String {
+    let offset = time.offset_minutes() as i64;
+    let local = time.seconds() + offset * 60;
+    let (days, seconds) = (local.div_euclid(86_400), local.rem_euclid(86_400));
+
+    let (year, month, day) = crate::dates::civil_date(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60,
+    )
+}
+
+/// Commits reachable from `latest` but not from `current`, newest first.
+fn new_commits(
+    repo: &Repository,
+    current: Oid,
+    latest: Oid,
+) -> Result<Vec<CommitSummary>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(git2::Sort::TIME)?;
+    walk.push(latest)?;
+    walk.hide(current)?;
+    let mut commits = Vec::new();
+    for oid in walk.take(MAX_COMMITS) {
+        let commit = repo.find_commit(oid?)?;
+        commits.push(CommitSummary {
+            sha: commit.id().to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            date: iso8601(commit.committer().when()),
+        });
+    }
+    Ok(commits)
+}
+
+/// Tracked files with uncommitted changes.
+fn dirty_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| entry.status() != git2::Status::CURRENT)
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+fn signature(repo: &Repository) -> Result<Signature<'static>, git2::Error> {
+    repo.signature()
+        .or_else(|_| Signature::now("OpenStore", "openstore@localhost"))
+}
+
+/// Puts the changes saved in stash commit `changes` back into the worktree, like
+/// `git stash apply`, untracked files included.
+fn restore_changes(repo: &Repository, repo_path: &Path, changes: Oid) -> Result<(), git2::Error> {
+    let stash = repo.find_commit(changes)?;
+    let mut diff_options = DiffOptions::new();
+    diff_options.show_binary(true);
+
+    if let Ok(untracked) = stash.parent(2) {
+        let tree = untracked.tree()?;
+        // Versions of these files created since are replaced by the saved ones
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let _ = std::fs::remove_file(
+                    repo_path.join(dir).join(entry.name().unwrap_or_default()),
+                );
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        let diff = repo.diff_tree_to_tree(None, Some(&tree), Some(&mut diff_options))?;
+        repo.apply(&diff, ApplyLocation::WorkDir, None)?;
+    }
+
+    let base = stash.parent(0)?.tree()?;
+    let diff =
+        repo.diff_tree_to_tree(Some(&base), Some(&stash.tree()?), Some(&mut diff_options))?;
+    repo.apply(&diff, ApplyLocation::WorkDir, None)
+}
+
+fn delete_reference(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    match repo.find_reference(name) {
+        Ok(mut reference) => reference.delete(),
+        Err(_) => Ok(()),
+    }
+}
+
+impl GitBackend for LibGit {
+    fn name(&self) -> &'static str {
+        "libgit2"
+    }
+
+    fn clone_repo(
+        &self,
+        repo_url: &str,
+        path: &Path,
+        options: &CloneOptions,
+        token: Option<&str>,
+        on_progress: &mut dyn FnMut(GitProgress),
+    ) -> Result<(), String> {
+        if path.exists() {
+            return Err(format!("目标目录已存在: {}", path.display()));
+        }
+        let repo = Repository::init(path).map_err(message)?;
+        let result = clone_into(&repo, repo_url, options, token, on_progress).map_err(message);
+        // Like `git clone`, leave nothing behind when the clone fails
+        if result.is_err() {
+            drop(repo);
+            let _ = std::fs::remove_dir_all(path);
+        }
+        result
+    }
+
+    fn remote_url(&self, repo_path: &Path) -> Option<String> {
+        let repo = Repository::open(repo_path).ok()?;
+        let url = repo.find_remote("origin").ok()?.url()?.to_string();
+        let clean = git::replace_userinfo(&url, "");
+        if clean != url {
+            let _ = repo.remote_set_url("origin", &clean);
+        }
+        Some(clean)
+    }
+
+    fn check(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+    ) -> Result<UpdateStatus, String> {
+        let repo = open(repo_path)?;
+        let mut remote = repo.find_remote("origin").map_err(message)?;
+        remote
+            .fetch(
+                &[] as &[&str],
+                Some(&mut fetch_options(remote_url, token, None)),
+                None,
+            )
+            .map_err(message)?;
+        let current = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(message)?
+            .id();
+        let pinned = repo.head_detached().unwrap_or(false);
+
+        let upstream =
+            tracked_upstream(&repo).or_else(|| repo.refname_to_id("refs/remotes/origin/HEAD").ok());
+        let Some(latest) = upstream else {
+            // Nothing to compare with locally; the fetch still saw what the remote's HEAD is
+            let latest = remote.list().ok().and_then(|heads| {
+                heads
+                    .iter()
+                    .find(|head| head.name() == "HEAD")
+                    .map(|head| head.oid())
+            });
+            return Ok(UpdateStatus {
+                method: "git",
+                has_updates: !pinned && latest.is_some_and(|latest| latest != current),
+                pinned,
+                current: Some(current.to_string()),
+                latest: latest.map(|latest| latest.to_string()),
+                ahead: 0,
+                behind: 0,
+                commits: Vec::new(),
+            });
+        };
+
+        let (ahead, behind) = repo.graph_ahead_behind(current, latest).map_err(message)?;
+        Ok(UpdateStatus {
+            method: "git",
+            has_updates: !pinned && behind > 0,
+            pinned,
+            current: Some(current.to_string()),
+            latest: Some(latest.to_string()),
+            ahead: ahead as u32,
+            behind: behind as u32,
+            commits: new_commits(&repo, current, latest).map_err(message)?,
+        })
+    }
+
+    fn pull(
+        &self,
+        repo_path: &Path,
+        remote_url: &str,
+        token: Option<&str>,
+        strategy: UpdateStrategy,
+    ) -> Result<String, CommandError> {
+        let mut repo = open(repo_path)?;
+        fetch_origin(&repo, remote_url, token).map_err(message)?;
+
+        let upstream = tracked_upstream(&repo).ok_or_else(|| {
+            "当前检出未跟踪远程分支（可能固定在某个标签或提交），无法更新".to_string()
+        })?;
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(message)?
+            .id();
+        if head == upstream || repo.graph_descendant_of(head, upstream).map_err(message)? {
+            return Ok("已是最新版本".to_string());
+        }
+
+        let files = dirty_files(&repo).map_err(message)?;
+        let dirty = !files.is_empty();
+        let fast_forward = repo.graph_descendant_of(upstream, head).map_err(message)?;
+
+        if strategy == UpdateStrategy::FfOnly && dirty {
+            return Err(UpdateError::new(UpdateErrorKind::DirtyWorktree, files).into());
+        }
+        if strategy != UpdateStrategy::Reset && !fast_forward {
+            return Err(UpdateError::new(UpdateErrorKind::Diverged, Vec::new()).into());
+        }
+
+        // Snapshot before anything changes
+        let stasher = signature(&repo).map_err(message)?;
+        let changes = match (strategy, dirty) {
+            (UpdateStrategy::Stash, true) => Some(
+                repo.stash_save(
+                    &stasher,
+                    "openstore: before update",
+                    Some(StashFlags::INCLUDE_UNTRACKED),
+                )
+                .map_err(message)?,
+            ),
+            // Only recorded for rollback: the reset discards the changes anyway
+            (UpdateStrategy::Reset, true) => {
+                let changes = repo
+                    .stash_save(&stasher, "openstore: before update", None)
+                    .map_err(message)?;
+                repo.stash_drop(0).map_err(message)?;
+                Some(changes)
+            }
+            _ => None,
+        };
+        repo.reference(ROLLBACK_HEAD, head, true, "openstore: before update")
+            .map_err(message)?;
+        match changes {
+            Some(changes) => repo
+                .reference(ROLLBACK_CHANGES, changes, true, "openstore: before update")
+                .map(|_| ()),
+            None => delete_reference(&repo, ROLLBACK_CHANGES),
+        }
+        .map_err(message)?;
+
+        let updated = (|| {
+            let target = repo.find_object(upstream, None)?;
+            match strategy {
+                UpdateStrategy::Reset => repo.reset(&target, ResetType::Hard, None),
+                _ => {
+                    // Fast-forward: a safe checkout refuses to overwrite untracked files
+                    repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))?;
+                    repo.head()?
+                        .resolve()?
+                        .set_target(upstream, "openstore: fast-forward")?;
+                    Ok(())
+                }
+            }
+        })();
+        let mut message_text = format!(
+            "已从 {} 更新到 {}",
+            short(&head.to_string()),
+            short(&upstream.to_string())
+        );
+        if strategy == UpdateStrategy::Stash && changes.is_some() {
+            if repo.stash_pop(0, None).is_err() {
+                message_text.push_str("，但恢复本地修改时发生冲突，修改仍保存在 git stash 中");
+            } else {
+                message_text.push_str("，本地修改已恢复");
+            }
+        }
+        updated.map_err(message)?;
+        Ok(message_text)
+    }
+
+    fn rollback(&self, repo_path: &Path) -> Result<String, CommandError> {
+        let repo = open(repo_path)?;
+        let head = repo
+            .refname_to_id(ROLLBACK_HEAD)
+            .map_err(|_| UpdateError::new(UpdateErrorKind::NoSnapshot, Vec::new()))?;
+        let changes = repo.refname_to_id(ROLLBACK_CHANGES).ok();
+
+        let target = repo.find_object(head, None).map_err(message)?;
+        repo.reset(&target, ResetType::Hard, None)
+            .map_err(message)?;
+        if let Some(changes) = changes {
+            restore_changes(&repo, repo_path, changes).map_err(message)?;
+        }
+        delete_reference(&repo, ROLLBACK_HEAD).map_err(message)?;
+        let _ = delete_reference(&repo, ROLLBACK_CHANGES);
+
+        Ok(format!("已回滚到 {}", short(&head.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-libgit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Commits `file` with `content` on the checked out branch of the repository at `path`.
+    fn commit_file(path: &Path, file: &str, content: &str) -> Oid {
+        let repo = Repository::open(path).unwrap();
+        std::fs::write(path.join(file), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.invalid").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+            .unwrap()
+    }
+
+    /// A source repository with one commit of `a.txt` and `b.txt`, and a clone of it.
+    fn source_and_clone(name: &str) -> (PathBuf, PathBuf, String) {
+        let dir = scratch_dir(name);
+        let source = dir.join("source");
+        Repository::init(&source).unwrap();
+        commit_file(&source, "a.txt", "a1");
+        commit_file(&source, "b.txt", "b1");
+        let url = format!("file://{}", source.to_string_lossy().replace('\\', "/"));
+        let checkout = dir.join("checkout");
+        LibGit
+            .clone_repo(&url, &checkout, &CloneOptions::default(), None, &mut |_| {})
+            .unwrap();
+        (source, checkout, url)
+    }
+
+    fn head(path: &Path) -> Oid {
+        Repository::open(path)
+            .unwrap()
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap()
+            .id()
+    }
+
+    fn read(path: &Path, file: &str) -> String {
+        std::fs::read_to_string(path.join(file)).unwrap()
+    }
+
+    fn update_error(result: Result<String, CommandError>) -> UpdateErrorKind {
+        match result {
+            Err(CommandError::Update(error)) => error.kind,
+            other => panic!("expected an update error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fast_forward_only_refuses_local_changes() {
+        let (source, checkout, url) = source_and_clone("ff-only");
+        let latest = commit_file(&source, "a.txt", "a2");
+
+        std::fs::write(checkout.join("b.txt"), "local").unwrap();
+        let refused = LibGit.pull(&checkout, &url, None, UpdateStrategy::FfOnly);
+        assert_eq!(update_error(refused), UpdateErrorKind::DirtyWorktree);
+        assert_eq!(read(&checkout, "b.txt"), "local");
+
+        std::fs::write(checkout.join("b.txt"), "b1").unwrap();
+        LibGit
+            .pull(&checkout, &url, None, UpdateStrategy::FfOnly)
+            .unwrap();
+        assert_eq!(head(&checkout), latest);
+        assert_eq!(read(&checkout, "a.txt"), "a2");
+        let _ = std::fs::remove_dir_all(source.parent().unwrap());
+    }
+
+    #[test]
+    fn stash_reapplies_local_changes() {
+        let (source, checkout, url) = source_and_clone("stash");
+        let latest = commit_file(&source, "a.txt", "a2");
+
+        std::fs::write(checkout.join("b.txt"), "local").unwrap();
+        std::fs::write(checkout.join("untracked.txt"), "mine").unwrap();
+        let message = LibGit
+            .pull(&checkout, &url, None, UpdateStrategy::Stash)
+            .unwrap();
+        assert!(message.contains("本地修改已恢复"), "{}", message);
+        assert_eq!(head(&checkout), latest);
+        assert_eq!(read(&checkout, "a.txt"), "a2");
+        assert_eq!(read(&checkout, "b.txt"), "local");
+        assert_eq!(read(&checkout, "untracked.txt"), "mine");
+        let _ = std::fs::remove_dir_all(source.parent().unwrap());
+    }
+
+    #[test]
+    fn reset_discards_changes_and_rollback_restores_them() {
+        let (source, checkout, url) = source_and_clone("reset");
+        let latest = commit_file(&source, "a.txt", "a2");
+        // A local commit makes the checkout diverge from upstream
+        let local = commit_file(&checkout, "local.txt", "committed");
+        std::fs::write(checkout.join("b.txt"), "local").unwrap();
+
+        let refused = LibGit.pull(&checkout, &url, None, UpdateStrategy::Stash);
+        assert_eq!(update_error(refused), UpdateErrorKind::Diverged);
+
+        LibGit
+            .pull(&checkout, &url, None, UpdateStrategy::Reset)
+            .unwrap();
+        assert_eq!(head(&checkout), latest);
+        assert_eq!(read(&checkout, "a.txt"), "a2");
+        assert_eq!(read(&checkout, "b.txt"), "b1");
+        assert!(!checkout.join("local.txt").exists());
+
+        LibGit.rollback(&checkout).unwrap();
+        assert_eq!(head(&checkout), local);
+        assert_eq!(read(&checkout, "a.txt"), "a1");
+        assert_eq!(read(&checkout, "b.txt"), "local");
+        assert_eq!(read(&checkout, "local.txt"), "committed");
+
+        // The snapshot is used up
+        let again = LibGit.rollback(&checkout);
+        assert_eq!(update_error(again), UpdateErrorKind::NoSnapshot);
+        let _ = std::fs::remove_dir_all(source.parent().unwrap());
+    }
+
+    #[test]
+    fn pinned_commit_clones_stay_detached() {
+        let (source, _, url) = source_and_clone("pinned");
+        let pinned = head(&source);
+        commit_file(&source, "a.txt", "a2");
+
+        let checkout = source.parent().unwrap().join("pinned");
+        let options = CloneOptions {
+            commit: Some(pinned.to_string()),
+            ..CloneOptions::default()
+        };
+        LibGit
+            .clone_repo(&url, &checkout, &options, None, &mut |_| {})
+            .unwrap();
+        let repo = Repository::open(&checkout).unwrap();
+        assert!(repo.head_detached().unwrap());
+        assert_eq!(head(&checkout), pinned);
+        assert_eq!(read(&checkout, "a.txt"), "a1");
+
+        // A pinned checkout follows no branch, so it is never updated
+        let result = LibGit.pull(&checkout, &url, None, UpdateStrategy::Reset);
+        assert!(
+            matches!(result, Err(CommandError::Message(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(head(&checkout), pinned);
+        let _ = std::fs::remove_dir_all(source.parent().unwrap());
+    }
+}
@@ -0,0 +1,261 @@
+// Validation of caller-supplied file and folder names before they are joined onto a
+// managed directory, so a source adapter cannot escape it with `../` or an absolute path,
+// and removal of the trees created there.
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InvalidNameKind {
+    Empty,
+    Absolute,
+    Traversal,
+}
+
+/// A rejected name, serialised as `{ kind, name, message }`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct InvalidName {
+    pub kind: InvalidNameKind,
+    pub name: String,
+    pub message: String,
+}
+
+impl InvalidName {
+    fn new(kind: InvalidNameKind, name: &str) -> Self {
+        let message = match kind {
+            InvalidNameKind::Empty => "名称不能为空".to_string(),
+            InvalidNameKind::Absolute => format!("不允许使用绝对路径: {}", name),
+            InvalidNameKind::Traversal => format!("名称不能包含路径分隔符或 \"..\": {}", name),
+        };
+        Self {
+            kind,
+            name: name.to_string(),
+            message,
+        }
+    }
+}
+
+/// Device names Windows refuses to use as file names, whatever the extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest name we produce, leaving room for a ` (n)` suffix and `.part.json`.
+const MAX_NAME_BYTES: usize = 200;
+
+/// Splits `name` into stem and extension (`"app.tar.gz"` -> `("app.tar", ".gz")`).
+/// Dot files such as `.env` have no extension.
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(index) if index > 0 => name.split_at(index),
+        _ => (name, ""),
+    }
+}
+
+/// Checks that `name` is a single path component and makes it safe to create on every
+/// OS: characters Windows forbids become `_`, trailing dots and spaces are dropped,
+/// reserved device names get a `_` prefix and overlong names are shortened.
+pub(crate) fn sanitize_name(name: &str) -> Result<String, InvalidName> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return Err(InvalidName::new(InvalidNameKind::Empty, name));
+    }
+
+    let bytes = trimmed.as_bytes();
+    let drive_prefix = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if trimmed.starts_with(['/', '\\']) || drive_prefix || Path::new(trimmed).is_absolute() {
+        return Err(InvalidName::new(InvalidNameKind::Absolute, name));
+    }
+    if trimmed.contains(['/', '\\']) || trimmed == "." || trimmed == ".." {
+        return Err(InvalidName::new(InvalidNameKind::Traversal, name));
+    }
+
+    let replaced: String = trimmed
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"|?*".contains(c) { '_' } else { c })
+        .collect();
+    let mut clean = replaced.trim_end_matches(['.', ' ']).to_string();
+    if clean.is_empty() {
+        return Err(InvalidName::new(InvalidNameKind::Empty, name));
+    }
+
+    let (stem, _) = split_extension(&clean);
+    let base = stem.split('.').next().unwrap_or(stem);
+    if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(base)) {
+        clean.insert(0, '_');
+    }
+
+    if clean.len() > MAX_NAME_BYTES {
+        let (stem, extension) = split_extension(&clean);
+        let extension = if extension.len() <= 16 { extension } else { "" };
+        let mut end = (MAX_NAME_BYTES - extension.len()).min(stem.len());
+        while !stem.is_char_boundary(end) {
+            end -= 1;
+        }
+        clean = format!("{}{}", &stem[..end], extension);
+    }
+
+    Ok(clean)
+}
+
+/// Returns `dir/name`, or `dir/stem (n).ext` with the lowest `n` for which `is_free`
+/// accepts the candidate.
+pub(crate) fn numbered_path(dir: &Path, name: &str, is_free: impl Fn(&Path) -> bool) -> PathBuf {
+    let candidate = dir.join(name);
+    if is_free(&candidate) {
+        return candidate;
+    }
+
+    let (stem, extension) = split_extension(name);
+    let mut n = 1;
+    loop {
+        let candidate = dir.join(format!("{} ({}){}", stem, n, extension));
+        if is_free(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Deletes the directory `path` and everything below it without following symlinks.
+/// Git marks its object files read-only, which blocks deletion on Windows, so there
+/// read-only flags are cleared and the removal retried.
+pub(crate) fn remove_tree(path: &Path) -> std::io::Result<()> {
+    let result = std::fs::remove_dir_all(path);
+    #[cfg(target_os = "windows")]
+    if matches!(&result, Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied) {
+        make_writable(path);
+        return std::fs::remove_dir_all(path);
+    }
+    result
+}
+
+#[cfg(target_os = "windows")]
+#[allow(clippy::permissions_set_readonly_false)]
+fn make_writable(path: &Path) {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return;
+    };
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            make_writable(&entry.path());
+        }
+    }
+    let mut permissions = metadata.permissions();
+    if permissions.readonly() && !metadata.file_type().is_symlink() {
+        permissions.set_readonly(false);
+        let _ = std::fs::set_permissions(path, permissions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn rejected(name: &str) -> InvalidNameKind {
+        sanitize_name(name).unwrap_err().kind
+    }
+
+    #[test]
+    fn traversal_and_absolute_names_are_rejected() {
+        for name in [
+            "..",
+            ".",
+            " .. ",
+            "a/b",
+            "a\\b",
+            "../etc",
+            "..\\windows",
+            "x/../y",
+        ] {
+            assert_eq!(rejected(name), InvalidNameKind::Traversal, "{:?}", name);
+        }
+        for name in [
+            "/etc/passwd",
+            "\\server\\share",
+            "C:",
+            "c:\\Windows",
+            "D:foo",
+        ] {
+            assert_eq!(rejected(name), InvalidNameKind::Absolute, "{:?}", name);
+        }
+        let error = sanitize_name("../x").unwrap_err();
+        assert_eq!(error.name, "../x");
+        assert!(error.message.contains("../x"));
+    }
+
+    #[test]
+    fn empty_names_are_rejected() {
+        for name in ["", "   ", "\t", "...", ". . ."] {
+            assert_eq!(rejected(name), InvalidNameKind::Empty, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn names_are_made_safe_on_windows() {
+        let clean = |name: &str| sanitize_name(name).unwrap();
+        assert_eq!(clean("CON"), "_CON");
+        assert_eq!(clean("nul.txt"), "_nul.txt");
+        assert_eq!(clean("Com1.tar.gz"), "_Com1.tar.gz");
+        assert_eq!(clean("CONSOLE"), "CONSOLE");
+        assert_eq!(clean("name. . "), "name");
+        assert_eq!(clean("  app.zip  "), "app.zip");
+        assert_eq!(clean("a<b>c:d\"e|f?g*h"), "a_b_c_d_e_f_g_h");
+        assert_eq!(clean("tab\there"), "tab_here");
+        assert_eq!(clean(".env"), ".env");
+    }
+
+    #[test]
+    fn long_names_keep_their_extension() {
+        let long = format!("{}.zip", "长".repeat(100));
+        let clean = sanitize_name(&long).unwrap();
+        assert!(clean.len() <= MAX_NAME_BYTES);
+        assert!(clean.ends_with(".zip"));
+        assert!(clean.starts_with('长'));
+    }
+
+    #[test]
+    fn numbered_paths_skip_taken_names() {
+        let dir = Path::new("downloads");
+        let taken: HashSet<PathBuf> = ["app.zip", "app (1).zip", "app (2).zip", "repo", "repo (1)"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        let free = |path: &Path| !taken.contains(path);
+        assert_eq!(numbered_path(dir, "app.zip", free), dir.join("app (3).zip"));
+        assert_eq!(numbered_path(dir, "repo", free), dir.join("repo (2)"));
+        assert_eq!(numbered_path(dir, "other.zip", free), dir.join("other.zip"));
+        assert_eq!(
+            numbered_path(dir, ".env", |path| path != dir.join(".env")),
+            dir.join(".env (1)")
+        );
+        assert_eq!(
+            numbered_path(dir, "a.tar.gz", |path| path != dir.join("a.tar.gz")),
+            dir.join("a.tar (1).gz")
+        );
+    }
+
+    #[test]
+    fn remove_tree_deletes_hostile_names_only() {
+        let root = std::env::temp_dir().join(format!("openstore-paths-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let keep = root.join("keep");
+        std::fs::create_dir_all(&keep).unwrap();
+        std::fs::write(keep.join("file"), "kept").unwrap();
+
+        for name in ["\" & rmdir keep & \"", "$(rm -rf keep)", "-rf keep", "; rm -rf keep #"] {
+            let tree = root.join(sanitize_name(name).unwrap());
+            std::fs::create_dir_all(tree.join(".git/objects")).unwrap();
+            std::fs::write(tree.join(".git/objects/pack"), "x").unwrap();
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&keep, tree.join("link")).unwrap();
+
+            remove_tree(&tree).unwrap();
+            assert!(!tree.exists());
+            assert_eq!(std::fs::read_to_string(keep.join("file")).unwrap(), "kept");
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
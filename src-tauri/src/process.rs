@@ -0,0 +1,350 @@
+// The one place the app starts other programs. Programs are picked from `Program`
+// rather than named by strings, and arguments always travel as a vector: nothing goes
+// through `cmd /c`, PowerShell or `sh -c`, so URLs, paths and names taken from
+// repository data reach the program as single arguments that no shell re-parses.
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Program {
+    Git,
+    /// The VS Code command line launcher.
+    VsCode,
+    /// What opens a folder in the file manager, or a file with its default application.
+    SystemOpener,
+}
+
+impl Program {
+    fn executable(self) -> &'static str {
+        match self {
+            Program::Git => "git",
+            // A batch script on Windows. std escapes arguments for batch files itself and
+            // refuses to start one with an argument it cannot escape safely.
+            Program::VsCode if cfg!(target_os = "windows") => "code.cmd",
+            Program::VsCode => "code",
+            Program::SystemOpener if cfg!(target_os = "windows") => "explorer",
+            Program::SystemOpener if cfg!(target_os = "macos") => "open",
+            Program::SystemOpener => "xdg-open",
+        }
+    }
+}
+
+/// Transports git may use. `ext::` would run an arbitrary command named in the URL.
+const GIT_ALLOWED_PROTOCOLS: &str = "http:https:ssh:git:file";
+
+/// A command that starts `program` with `args`, each passed as exactly one argument.
+pub(crate) fn command<I, S>(program: Program, args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(program.executable());
+    command.args(args);
+    if program == Program::Git {
+        command.env("GIT_ALLOW_PROTOCOL", GIT_ALLOWED_PROTOCOLS);
+    }
+    command
+}
+
+/// `path` as a positional argument: a relative path starting with `-` gets a `./`
+/// prefix so the program cannot take it for an option.
+pub(crate) fn operand(path: &Path) -> OsString {
+    if path.as_os_str().to_string_lossy().starts_with('-') {
+        Path::new(".").join(path).into_os_string()
+    } else {
+        path.as_os_str().to_os_string()
+    }
+}
+
+/// Opens `path` with the system opener (file manager for folders, the associated
+/// application for files).
+pub(crate) fn open_path(path: &Path) -> Command {
+    command(Program::SystemOpener, [operand(path)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{self, CloneOptions};
+    use crate::git_backend::{CliGit, GitBackend};
+    use crate::installs::{InstallStore, InstalledRepo, UninstallMode};
+    use crate::libgit::LibGit;
+    use crate::updates::UpdateStrategy;
+    use std::path::PathBuf;
+
+    /// Strings a hostile source could put in a repository URL or folder name.
+    const HOSTILE: [&str; 9] = [
+        "$(touch PWNED)",
+        "`touch PWNED`",
+        "; touch PWNED",
+        "& echo PWNED > PWNED",
+        "| touch PWNED",
+        "\" & touch PWNED & \"",
+        "' ; touch PWNED ; '",
+        "%COMSPEC% /c echo PWNED > PWNED",
+        "--upload-pack=touch PWNED",
+    ];
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-process-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn assert_untouched(dir: &Path) {
+        assert!(
+            !dir.join("PWNED").exists(),
+            "a hostile argument was executed in {}",
+            dir.display()
+        );
+    }
+
+    /// Commits `file` with `content` on the checked out branch of the repository at `path`.
+    fn commit_file(path: &Path, file: &str, content: &str) {
+        let repo = git2::Repository::open(path).unwrap();
+        std::fs::write(path.join(file), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.invalid").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+            .unwrap();
+    }
+
+    fn backends() -> Vec<Box<dyn GitBackend>> {
+        let mut backends: Vec<Box<dyn GitBackend>> = vec![Box::new(LibGit)];
+        if git::cli_installed() {
+            backends.push(Box::new(CliGit));
+        }
+        backends
+    }
+
+    #[test]
+    fn arguments_are_passed_verbatim() {
+        for program in [Program::Git, Program::VsCode, Program::SystemOpener] {
+            for value in HOSTILE {
+                let command = command(program, ["--", value]);
+                let args: Vec<&OsStr> = command.get_args().collect();
+                assert_eq!(args, [OsStr::new("--"), OsStr::new(value)]);
+                assert_eq!(command.get_program(), program.executable());
+            }
+        }
+    }
+
+    #[test]
+    fn authentication_appends_to_existing_git_config() {
+        let mut command = command(Program::Git, ["fetch"]);
+        command
+            .env("GIT_CONFIG_COUNT", "2")
+            .env("GIT_CONFIG_KEY_0", "core.askPass")
+            .env("GIT_CONFIG_KEY_1", "http.proxy");
+        git::authenticate(&mut command, "https://github.com/o/r", Some("secret"));
+        let env = |key: &str| {
+            command
+                .get_envs()
+                .find(|(name, _)| *name == key)
+                .and_then(|(_, value)| value)
+                .map(|value| value.to_string_lossy().to_string())
+        };
+        assert_eq!(env("GIT_CONFIG_COUNT").as_deref(), Some("3"));
+        assert_eq!(env("GIT_CONFIG_KEY_1").as_deref(), Some("http.proxy"));
+        assert_eq!(
+            env("GIT_CONFIG_KEY_2").as_deref(),
+            Some("http.https://github.com/.extraHeader")
+        );
+        assert!(env("GIT_CONFIG_VALUE_2").is_some_and(|value| value.starts_with("Authorization")));
+        assert!(command
+            .get_args()
+            .all(|arg| !arg.to_string_lossy().contains("secret")));
+    }
+
+    #[test]
+    fn option_like_paths_become_operands() {
+        assert_eq!(
+            operand(Path::new("-rf")),
+            Path::new(".").join("-rf").into_os_string()
+        );
+        assert_eq!(
+            operand(Path::new("--help")),
+            Path::new(".").join("--help").into_os_string()
+        );
+        assert_eq!(operand(Path::new("dir/-x")), OsString::from("dir/-x"));
+
+        let command = open_path(Path::new("--version"));
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(args, [Path::new(".").join("--version").as_os_str()]);
+    }
+
+    #[test]
+    fn hostile_clone_urls_are_rejected_or_inert() {
+        let dir = scratch_dir("urls");
+        for value in HOSTILE {
+            let url = format!("https://example.invalid/{}", value);
+            let args = CloneOptions::default().clone_args(&url, "target");
+            // The URL follows `--`, so it can never be read as an option
+            let separator = args.iter().position(|arg| arg == "--").unwrap();
+            assert_eq!(args[separator + 1], url);
+            assert!(git::validate_repo_url(&url).is_err(), "accepted {:?}", url);
+        }
+        for url in [
+            "ext::sh -c touch% PWNED",
+            "-uhttps://example.com/r",
+            "https://a/b\nc",
+        ] {
+            assert!(git::validate_repo_url(url).is_err(), "accepted {:?}", url);
+        }
+        for url in [
+            "https://github.com/o/r",
+            "git@github.com:o/r.git",
+            "ssh://git@host/o/r",
+        ] {
+            assert!(git::validate_repo_url(url).is_ok(), "rejected {:?}", url);
+        }
+
+        if !git::cli_installed() {
+            return;
+        }
+        for value in HOSTILE.iter().chain(&["ext::sh -c touch% PWNED"]) {
+            let mut ls_remote = command(Program::Git, ["ls-remote", "--", value]);
+            ls_remote.current_dir(&dir).env("GIT_TERMINAL_PROMPT", "0");
+            assert!(!ls_remote.output().unwrap().status.success());
+            assert_untouched(&dir);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hostile_clone_paths_stay_single_folders() {
+        let dir = scratch_dir("paths");
+        let source = dir.join("source");
+        git2::Repository::init(&source).unwrap();
+        let url = format!("file://{}", source.to_string_lossy().replace('\\', "/"));
+
+        for backend in &backends() {
+            for (index, value) in HOSTILE.iter().enumerate() {
+                // Folder names are sanitized first, exactly as `clone_repository` does;
+                // names with path separators are rejected outright
+                let Ok(name) = crate::paths::sanitize_name(value) else {
+                    continue;
+                };
+                let target = dir.join(format!("{}-{}{}", backend.name(), index, name));
+                let result =
+                    backend.clone_repo(&url, &target, &CloneOptions::default(), None, &mut |_| {});
+                assert!(
+                    result.is_ok(),
+                    "{}: {:?}: {:?}",
+                    backend.name(),
+                    value,
+                    result
+                );
+                assert!(target.join(".git").is_dir());
+                assert_untouched(&dir);
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hostile_pulls_stay_inert() {
+        let dir = scratch_dir("pulls");
+        let source = dir.join("source");
+        git2::Repository::init(&source).unwrap();
+        commit_file(&source, "first.txt", "1");
+        let url = format!("file://{}", source.to_string_lossy().replace('\\', "/"));
+
+        let mut checkouts = Vec::new();
+        for backend in backends() {
+            for (index, value) in HOSTILE.iter().enumerate() {
+                let Ok(name) = crate::paths::sanitize_name(value) else {
+                    continue;
+                };
+                let target = dir.join(format!("{}-{}{}", backend.name(), index, name));
+                backend
+                    .clone_repo(&url, &target, &CloneOptions::default(), None, &mut |_| {})
+                    .unwrap();
+                checkouts.push((backend.name(), target));
+            }
+        }
+        commit_file(&source, "second.txt", "2");
+
+        for (name, target) in &checkouts {
+            let backend = crate::git_backend::for_repository(target).unwrap();
+            // A hostile recorded URL and token only feed authentication; `origin` is
+            // what gets fetched
+            for value in HOSTILE {
+                let _ = backend.pull(target, value, Some(value), UpdateStrategy::FfOnly);
+                assert_untouched(&dir);
+                assert_untouched(target);
+            }
+            let result = backend.pull(target, &url, None, UpdateStrategy::FfOnly);
+            assert!(result.is_ok(), "{}: {:?}", name, result.err());
+            assert!(target.join("second.txt").is_file());
+
+            // A hostile `origin` in the checkout itself fails without running anything
+            let repo = git2::Repository::open(target).unwrap();
+            for value in HOSTILE.iter().chain(&["ext::sh -c touch% PWNED"]) {
+                repo.remote_set_url("origin", value).unwrap();
+                let result = backend.pull(target, &url, None, UpdateStrategy::Reset);
+                assert!(result.is_err(), "{}: pulled from {:?}", name, value);
+                assert_untouched(&dir);
+                assert_untouched(target);
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hostile_uninstalls_stay_inside_the_root() {
+        let dir = scratch_dir("uninstall");
+        let store = InstallStore::open(&dir.join("installs.db"), &dir).unwrap();
+        let root = store.add_download_dir(&dir.join("downloads")).unwrap();
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("keep.txt"), "keep").unwrap();
+
+        for (index, value) in HOSTILE.iter().enumerate() {
+            let Ok(name) = crate::paths::sanitize_name(value) else {
+                continue;
+            };
+            let target = root.join(format!("{}{}", index, name));
+            std::fs::create_dir_all(&target).unwrap();
+            std::fs::write(target.join("app.txt"), value).unwrap();
+            store
+                .record(&InstalledRepo {
+                    url: format!("https://github.com/owner/repo{}", index),
+                    path: target.to_string_lossy().to_string(),
+                    status: "completed".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+
+            let report = store.uninstall(&target, UninstallMode::Delete, false);
+            assert!(report.is_ok(), "{:?}: {:?}", value, report.err());
+            assert!(!target.exists());
+            assert!(root.is_dir());
+            assert_untouched(&dir);
+            assert_untouched(&root);
+        }
+
+        // Option-like, escaping and unrecorded paths are refused, not handed on
+        for path in [
+            PathBuf::from("-rf"),
+            PathBuf::from("--no-preserve-root"),
+            root.join("..").join("outside"),
+            outside.clone(),
+            root.clone(),
+        ] {
+            let result = store.uninstall(&path, UninstallMode::Delete, false);
+            assert!(result.is_err(), "uninstalled {}", path.display());
+        }
+        assert!(outside.join("keep.txt").is_file());
+        assert!(root.is_dir());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,185 @@
+// Repository progress reporting shared by the git and zipball install paths.
+
+#[derive(serde::Serialize, Clone)]
+pub(crate) struct ProgressPayload {
+    pub repo_url: String,
+    pub status: String, // "cloning", "downloading_zip", "completed", "failed", "pulling"
+    pub message: String,
+    pub downloaded: u64,
+    pub total: u64,
+    pub percent: u32,
+}
+
+impl ProgressPayload {
+    pub fn new(repo_url: &str, status: &str, message: impl Into<String>) -> Self {
+        Self {
+            repo_url: repo_url.to_string(),
+            status: status.to_string(),
+            message: message.into(),
+            downloaded: 0,
+            total: 0,
+            percent: 0,
+        }
+    }
+
+    pub fn with_progress(mut self, downloaded: u64, total: u64, percent: u32) -> Self {
+        self.downloaded = downloaded;
+        self.total = total;
+        self.percent = percent.min(100);
+        self
+    }
+}
+
+/// Clone progress from a `git clone --progress` line or from libgit2's transfer
+/// statistics, already mapped onto 0-100.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GitProgress {
+    pub phase: &'static str,
+    pub current: u64,
+    pub total: u64,
+    pub percent: u32,
+    /// Bytes received so far, only reported while receiving objects.
+    pub received_bytes: Option<u64>,
+}
+
+// Receiving objects dominates a clone, so it gets the bulk of the overall bar.
+const RECEIVING_WEIGHT: u32 = 85;
+
+impl GitProgress {
+    /// Progress of `phase` ("receiving", "resolving" or "checkout") at `current` of
+    /// `total` items, mapped onto the overall bar.
+    pub fn new(phase: &'static str, current: u64, total: u64, received_bytes: Option<u64>) -> Self {
+        let phase_percent = (current.min(total) * 100).checked_div(total).unwrap_or(0) as u32;
+        let percent = match phase {
+            "receiving" => phase_percent * RECEIVING_WEIGHT / 100,
+            "resolving" => RECEIVING_WEIGHT + phase_percent * (100 - RECEIVING_WEIGHT) / 100,
+            _ => 100,
+        };
+        Self {
+            phase,
+            current,
+            total,
+            percent,
+            received_bytes,
+        }
+    }
+}
+
+/// Parses lines such as `Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s`
+/// or `Resolving deltas: 30% (30/100)`. Other git chatter returns `None`.
+pub(crate) fn parse_git_progress(line: &str) -> Option<GitProgress> {
+    let line = line.trim().trim_start_matches("remote:").trim();
+    let (phase, rest) = if let Some(rest) = line.strip_prefix("Receiving objects:") {
+        ("receiving", rest)
+    } else if let Some(rest) = line.strip_prefix("Resolving deltas:") {
+        ("resolving", rest)
+    } else {
+        return None;
+    };
+
+    let open = rest.find('(')?;
+    let close = rest[open..].find(')')? + open;
+    let (current, total) = rest[open + 1..close].split_once('/')?;
+    let current: u64 = current.trim().parse().ok()?;
+    let total: u64 = total.trim().parse().ok()?;
+
+    let received_bytes = rest[close + 1..]
+        .trim_start_matches(',')
+        .split('|')
+        .next()
+        .and_then(parse_size);
+
+    Some(GitProgress::new(phase, current, total, received_bytes))
+}
+
+/// Parses git's human readable sizes (`512 bytes`, `1.20 MiB`, ...) into bytes.
+fn parse_size(text: &str) -> Option<u64> {
+    let mut parts = text.split_whitespace();
+    let value: f64 = parts.next()?.parse().ok()?;
+    let multiplier = match parts.next()? {
+        "bytes" | "byte" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((value * multiplier) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receiving_lines_carry_counts_and_bytes() {
+        let progress =
+            parse_git_progress("Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s")
+                .unwrap();
+        assert_eq!(progress.phase, "receiving");
+        assert_eq!((progress.current, progress.total), (450, 1000));
+        assert_eq!(progress.percent, 45 * RECEIVING_WEIGHT / 100);
+        assert_eq!(
+            progress.received_bytes,
+            Some((1.2 * 1024.0 * 1024.0) as u64)
+        );
+
+        let done = parse_git_progress(
+            "Receiving objects: 100% (1000/1000), 12.34 MiB | 5.67 MiB/s, done.",
+        )
+        .unwrap();
+        assert_eq!(done.percent, RECEIVING_WEIGHT);
+    }
+
+    #[test]
+    fn resolving_lines_fill_the_rest_of_the_bar() {
+        let progress = parse_git_progress("Resolving deltas:  30% (30/100)").unwrap();
+        assert_eq!(progress.phase, "resolving");
+        assert_eq!(progress.received_bytes, None);
+        assert_eq!(
+            progress.percent,
+            RECEIVING_WEIGHT + 30 * (100 - RECEIVING_WEIGHT) / 100
+        );
+
+        let done = parse_git_progress("Resolving deltas: 100% (100/100), done.").unwrap();
+        assert_eq!(done.percent, 100);
+    }
+
+    #[test]
+    fn remote_prefixes_and_carriage_returns_are_ignored() {
+        let progress = parse_git_progress("\rremote: Receiving objects:  10% (1/10)\r").unwrap();
+        assert_eq!((progress.current, progress.total), (1, 10));
+    }
+
+    #[test]
+    fn other_git_output_is_not_progress() {
+        for line in [
+            "Cloning into 'repo'...",
+            "remote: Enumerating objects: 1000, done.",
+            "remote: Counting objects: 100% (1000/1000), done.",
+            "remote: Compressing objects:  50% (20/40)",
+            "Receiving objects:  45%",
+            "Resolving deltas:  30% (x/100)",
+            "",
+        ] {
+            assert_eq!(parse_git_progress(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn empty_totals_do_not_divide_by_zero() {
+        let progress = parse_git_progress("Receiving objects: 100% (0/0)").unwrap();
+        assert_eq!(progress.percent, 0);
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(parse_size("512 bytes"), Some(512));
+        assert_eq!(parse_size(" 1 byte "), Some(1));
+        assert_eq!(parse_size("1.50 KiB"), Some(1536));
+        assert_eq!(parse_size("2.00 MiB"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1.00 GiB"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("3 MB"), None);
+        assert_eq!(parse_size("MiB"), None);
+        assert_eq!(parse_size("12"), None);
+    }
+}
@@ -0,0 +1,182 @@
+// Retry policy for transient HTTP failures of release-asset downloads: exponential
+// backoff, overridden by `Retry-After` or GitHub's rate-limit reset when present.
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    /// Retries after the first failed attempt; 0 disables retrying.
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (starting at 1): the base delay doubled for
+    /// every previous attempt, capped at `max_delay_ms`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+        let delay = self.base_delay_ms.saturating_mul(factor).min(self.max_delay_ms);
+        Duration::from_millis(delay)
+    }
+
+    /// Delay before retry number `attempt`: what the server asked for, or the backoff
+    /// when it did not say. `None` when the server asks for more than `max_delay_ms`,
+    /// which would hold the download slot for an unbounded time.
+    pub fn delay(&self, attempt: u32, server_delay: Option<Duration>) -> Option<Duration> {
+        match server_delay {
+            Some(delay) if delay > Duration::from_millis(self.max_delay_ms) => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+}
+
+/// Managed state holding the policy used by new retry decisions.
+pub(crate) struct RetrySettings(pub Mutex<RetryPolicy>);
+
+/// Whether GitHub reports the rate limit as exhausted (`x-ratelimit-remaining: 0`).
+fn rate_limited(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-ratelimit-remaining")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "0")
+}
+
+/// Whether a failed response is worth retrying: timeouts, throttling, server errors
+/// and GitHub's rate-limit 403s.
+pub(crate) fn is_transient(status: StatusCode, headers: &HeaderMap) -> bool {
+    match status {
+        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => rate_limited(headers),
+        status => status.is_server_error(),
+    }
+}
+
+/// How long the server asked us to wait, from `Retry-After` (seconds or HTTP date)
+/// or, when the rate limit is exhausted, from `x-ratelimit-reset` (Unix seconds).
+pub(crate) fn server_delay(headers: &HeaderMap) -> Option<Duration> {
+    server_delay_at(headers, SystemTime::now())
+}
+
+fn server_delay_at(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if let Some(value) = headers.get(RETRY_AFTER).and_then(|v| v.to_str().ok()) {
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = httpdate::parse_http_date(value) {
+            return Some(date.duration_since(now).unwrap_or(Duration::ZERO));
+        }
+    }
+
+    if rate_limited(headers) {
+        let reset: u64 = headers
+            .get("x-ratelimit-reset")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())?;
+        let reset = SystemTime::UNIX_EPOCH + Duration::from_secs(reset);
+        return Some(reset.duration_since(now).unwrap_or(Duration::ZERO));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay_ms: 500,
+            max_delay_ms: 5_000,
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempt| policy.backoff(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [500, 1_000, 2_000, 4_000, 5_000, 5_000]);
+        assert_eq!(policy.backoff(200), Duration::from_millis(5_000));
+    }
+
+    #[test]
+    fn retry_after_in_seconds_or_as_a_date() {
+        let now = at(1_700_000_000);
+        assert_eq!(
+            server_delay_at(&headers(&[("retry-after", " 30 ")]), now),
+            Some(Duration::from_secs(30))
+        );
+        let date = httpdate::fmt_http_date(now + Duration::from_secs(90));
+        assert_eq!(
+            server_delay_at(&headers(&[("retry-after", &date)]), now),
+            Some(Duration::from_secs(90))
+        );
+        let past = httpdate::fmt_http_date(now - Duration::from_secs(90));
+        assert_eq!(
+            server_delay_at(&headers(&[("retry-after", &past)]), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            server_delay_at(&headers(&[("retry-after", "soon")]), now),
+            None
+        );
+    }
+
+    #[test]
+    fn rate_limit_reset_applies_only_when_exhausted() {
+        let now = at(1_700_000_000);
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1700000045"),
+        ]);
+        assert_eq!(
+            server_delay_at(&exhausted, now),
+            Some(Duration::from_secs(45))
+        );
+        assert!(is_transient(StatusCode::FORBIDDEN, &exhausted));
+
+        let remaining = headers(&[
+            ("x-ratelimit-remaining", "12"),
+            ("x-ratelimit-reset", "1700000045"),
+        ]);
+        assert_eq!(server_delay_at(&remaining, now), None);
+        assert!(!is_transient(StatusCode::FORBIDDEN, &remaining));
+    }
+
+    #[test]
+    fn server_delays_beyond_the_cap_are_refused() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(3, None), Some(policy.backoff(3)));
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(60))),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(61))), None);
+    }
+}
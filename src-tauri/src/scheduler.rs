@@ -0,0 +1,322 @@
+// Background update checks. Every `interval_minutes` the scheduler looks at the
+// completed installs recorded in the install store and at the releases that downloaded
+// assets came from, then announces anything new with a native notification and an
+// `updates-available` event. Checks run one at a time and a round stops at the first
+// rate-limit error, leaving the rest for the next round.
+use crate::credentials::CredentialStore;
+use crate::forge::{self, ForgeRegistry};
+use crate::history::{DownloadHistory, HistoryEntry};
+use crate::installs::InstallStore;
+use crate::updates::CommitSummary;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::sync::Notify;
+
+/// Six hours between rounds until the frontend applies the user's setting.
+pub(crate) const DEFAULT_INTERVAL_MINUTES: u64 = 360;
+
+/// Longest interval accepted, 30 days; longer settings are capped to it.
+pub(crate) const MAX_INTERVAL_MINUTES: u64 = 30 * 24 * 60;
+
+/// Pause between two checks of one round, to stay well below API rate limits.
+const CHECK_SPACING: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UpdateKind {
+    /// New commits for an installed repository.
+    Repository,
+    /// A newer release than the one a downloaded asset belongs to.
+    Release,
+}
+
+/// One available update, as listed in the `updates-available` event.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct UpdateNotice {
+    pub kind: UpdateKind,
+    /// Repository URL.
+    pub url: String,
+    /// Install folder, for repositories.
+    pub path: Option<String>,
+    /// Installed commit or downloaded release tag, when known.
+    pub current: Option<String>,
+    /// Latest commit or release tag.
+    pub latest: String,
+    /// New commits, newest first; empty for releases.
+    pub commits: Vec<CommitSummary>,
+}
+
+/// Managed state: how often to check, and what was already announced.
+pub(crate) struct UpdateScheduler {
+    interval_minutes: Mutex<u64>,
+    /// Latest version announced per kind and URL, so each update is notified once.
+    announced: Mutex<HashMap<(UpdateKind, String), String>>,
+    wake: Notify,
+    /// Serialises rounds started by the timer and by `check_for_updates_now`.
+    round: tokio::sync::Mutex<()>,
+}
+
+impl UpdateScheduler {
+    pub fn new(interval_minutes: u64) -> Self {
+        Self {
+            interval_minutes: Mutex::new(interval_minutes.min(MAX_INTERVAL_MINUTES)),
+            announced: Mutex::new(HashMap::new()),
+            wake: Notify::new(),
+            round: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        *self.interval_minutes.lock().unwrap()
+    }
+
+    /// Sets the time between rounds, at most `MAX_INTERVAL_MINUTES`; 0 turns scheduled
+    /// checks off. The current wait is restarted with the new interval.
+    pub fn set_interval(&self, minutes: u64) {
+        *self.interval_minutes.lock().unwrap() = minutes.min(MAX_INTERVAL_MINUTES);
+        self.wake.notify_one();
+    }
+
+    /// Keeps only notices whose latest version was not announced before.
+    fn unannounced(&self, notices: &[UpdateNotice]) -> Vec<UpdateNotice> {
+        let mut announced = self.announced.lock().unwrap();
+        let mut fresh = Vec::new();
+        for notice in notices {
+            let key = (notice.kind, notice.url.clone());
+            if announced.get(&key) != Some(&notice.latest) {
+                announced.insert(key, notice.latest.clone());
+                fresh.push(notice.clone());
+            }
+        }
+        fresh
+    }
+}
+
+/// Repository URL and release tag of a release asset download URL such as
+/// `https://github.com/owner/repo/releases/download/v1.2/app.zip`.
+fn release_of_asset(url: &str) -> Option<(String, String)> {
+    let (repo_url, rest) = url.split_once("/releases/download/")?;
+    let tag = rest.split('/').next().filter(|tag| !tag.is_empty())?;
+    Some((repo_url.to_string(), tag.to_string()))
+}
+
+/// The release tag each repository's assets were last downloaded from, given the
+/// download history newest first.
+fn downloaded_releases(entries: &[HistoryEntry]) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|entry| entry.status == "completed")
+        .filter_map(|entry| release_of_asset(&entry.url))
+        // The history is newest first, so the first tag per repository is the current one
+        .filter(|(repo_url, _)| seen.insert(repo_url.clone()))
+        .collect()
+}
+
+/// Checks everything watched once and returns all updates found.
+pub(crate) async fn run_round(app: &tauri::AppHandle) -> Vec<UpdateNotice> {
+    let scheduler = app.state::<UpdateScheduler>();
+    let _round = scheduler.round.lock().await;
+    let repos = app.state::<InstallStore>().completed().unwrap_or_default();
+    let mut notices = Vec::new();
+
+    for repo in repos {
+        match crate::check_repository_updates(app.clone(), repo.path.clone(), repo.url.clone())
+            .await
+        {
+            Ok(status) if status.has_updates => notices.push(UpdateNotice {
+                kind: UpdateKind::Repository,
+                url: repo.url,
+                path: Some(repo.path),
+                current: status.current,
+                latest: status.latest.unwrap_or_default(),
+                commits: status.commits,
+            }),
+            Ok(_) => {}
+            Err(e) if e.starts_with(forge::RATE_LIMITED) => return notices,
+            Err(_) => {}
+        }
+        tokio::time::sleep(CHECK_SPACING).await;
+    }
+
+    let client = match crate::http_client() {
+        Ok(client) => client,
+        Err(_) => return notices,
+    };
+    let history = app.state::<DownloadHistory>().load().unwrap_or_default();
+    for (repo_url, tag) in downloaded_releases(&history) {
+        let Some((forge, repo)) = app.state::<ForgeRegistry>().resolve(&repo_url) else {
+            continue;
+        };
+        let token = app.state::<CredentialStore>().token_for_url(&repo_url);
+        let newer =
+            forge::newer_release_tag(&client, forge.as_ref(), &repo, &tag, token.as_deref()).await;
+        match newer {
+            Ok(Some(latest)) => notices.push(UpdateNotice {
+                kind: UpdateKind::Release,
+                url: repo_url,
+                path: None,
+                current: Some(tag),
+                latest,
+                commits: Vec::new(),
+            }),
+            Ok(_) => {}
+            Err(e) if e.starts_with(forge::RATE_LIMITED) => return notices,
+            Err(_) => {}
+        }
+        tokio::time::sleep(CHECK_SPACING).await;
+    }
+
+    notices
+}
+
+/// Emits `updates-available` and shows a notification for updates not announced yet.
+pub(crate) fn announce(app: &tauri::AppHandle, notices: &[UpdateNotice]) {
+    use tauri_plugin_notification::NotificationExt;
+
+    let fresh = app.state::<UpdateScheduler>().unannounced(notices);
+    if fresh.is_empty() {
+        return;
+    }
+    let _ = app.emit("updates-available", &fresh);
+
+    let names: Vec<&str> = fresh
+        .iter()
+        .map(|notice| notice.url.trim_end_matches('/').rsplit('/').next().unwrap_or(&notice.url))
+        .take(3)
+        .collect();
+    let more = if fresh.len() > names.len() { " 等" } else { "" };
+    let _ = app
+        .notification()
+        .builder()
+        .title("发现更新")
+        .body(format!("{} 个项目有新版本：{}{}", fresh.len(), names.join("、"), more))
+        .show();
+}
+
+/// Starts the scheduler loop. It waits for the interval (or until a setting changes),
+/// runs a round and announces what it found.
+pub(crate) fn spawn(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let scheduler = app.state::<UpdateScheduler>();
+            let minutes = scheduler.interval();
+            if minutes == 0 {
+                scheduler.wake.notified().await;
+                continue;
+            }
+            // A wake-up only restarts the wait with the new interval
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(minutes.saturating_mul(60))) => {}
+                _ = scheduler.wake.notified() => continue,
+            }
+            let notices = run_round(&app).await;
+            announce(&app, &notices);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, status: &str) -> HistoryEntry {
+        HistoryEntry {
+            url: url.to_string(),
+            filename: String::new(),
+            path: String::new(),
+            size: 0,
+            hash: None,
+            started_at: 0,
+            finished_at: 0,
+            status: status.to_string(),
+            error: None,
+        }
+    }
+
+    fn notice(kind: UpdateKind, url: &str, latest: &str) -> UpdateNotice {
+        UpdateNotice {
+            kind,
+            url: url.to_string(),
+            path: None,
+            current: None,
+            latest: latest.to_string(),
+            commits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn asset_urls_name_their_release() {
+        assert_eq!(
+            release_of_asset("https://github.com/o/r/releases/download/v1.2/app.zip"),
+            Some(("https://github.com/o/r".to_string(), "v1.2".to_string()))
+        );
+        assert_eq!(
+            release_of_asset("https://github.com/o/r/releases/download//app.zip"),
+            None
+        );
+        assert_eq!(release_of_asset("https://example.com/files/app.zip"), None);
+    }
+
+    #[test]
+    fn the_newest_completed_download_sets_the_release() {
+        let history = [
+            entry(
+                "https://github.com/o/r/releases/download/v3/app.zip",
+                "failed",
+            ),
+            entry(
+                "https://github.com/o/r/releases/download/v2/app.zip",
+                "completed",
+            ),
+            entry(
+                "https://github.com/o/other/releases/download/1.0/tool.tar.gz",
+                "completed",
+            ),
+            entry(
+                "https://github.com/o/r/releases/download/v1/app.zip",
+                "completed",
+            ),
+            entry("https://example.com/unrelated.zip", "completed"),
+        ];
+        assert_eq!(
+            downloaded_releases(&history),
+            [
+                ("https://github.com/o/r".to_string(), "v2".to_string()),
+                ("https://github.com/o/other".to_string(), "1.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn each_version_is_announced_once() {
+        let scheduler = UpdateScheduler::new(DEFAULT_INTERVAL_MINUTES);
+        let first = [
+            notice(UpdateKind::Repository, "https://github.com/o/r", "abc"),
+            notice(UpdateKind::Release, "https://github.com/o/r", "v2"),
+        ];
+        assert_eq!(scheduler.unannounced(&first).len(), 2);
+        assert!(scheduler.unannounced(&first).is_empty());
+
+        let second = [
+            notice(UpdateKind::Repository, "https://github.com/o/r", "def"),
+            notice(UpdateKind::Release, "https://github.com/o/r", "v2"),
+        ];
+        let fresh = scheduler.unannounced(&second);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(
+            (fresh[0].kind, fresh[0].latest.as_str()),
+            (UpdateKind::Repository, "def")
+        );
+    }
+
+    #[test]
+    fn intervals_are_capped() {
+        let scheduler = UpdateScheduler::new(u64::MAX);
+        assert_eq!(scheduler.interval(), MAX_INTERVAL_MINUTES);
+        scheduler.set_interval(0);
+        assert_eq!(scheduler.interval(), 0);
+    }
+}
@@ -0,0 +1,253 @@
+// Bandwidth throttling for release-asset and zipball downloads: one token bucket
+// shared by every transfer plus optional per-task buckets, adjustable at runtime.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Token bucket refilled at `rate` bytes per second; a rate of 0 means unlimited.
+struct Bucket {
+    rate: u64,
+    balance: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            balance: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Takes `bytes` out of the bucket at `now` and returns how long the caller has to
+    /// wait until the balance has been paid back.
+    fn take(&mut self, bytes: u64, now: Instant) -> Duration {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        if self.rate == 0 {
+            return Duration::ZERO;
+        }
+
+        // At most one second worth of unused bandwidth is saved up as burst
+        let rate = self.rate as f64;
+        self.balance = (self.balance + elapsed * rate).min(rate) - bytes as f64;
+        if self.balance >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.balance / rate)
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+pub(crate) struct BandwidthLimits {
+    /// Bytes per second shared by all downloads, 0 when unlimited.
+    pub global: u64,
+    /// Per-task limits in bytes per second, keyed by download URL.
+    pub tasks: HashMap<String, u64>,
+}
+
+struct LimiterState {
+    global: Bucket,
+    tasks: HashMap<String, Bucket>,
+}
+
+/// Managed state shared by all downloads.
+pub(crate) struct BandwidthLimiter {
+    state: Mutex<LimiterState>,
+    notify: Notify,
+}
+
+impl BandwidthLimiter {
+    pub fn new(global_limit: u64) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                global: Bucket::new(global_limit),
+                tasks: HashMap::new(),
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Sets the limit shared by all downloads; 0 removes it.
+    pub fn set_global_limit(&self, bytes_per_sec: u64) {
+        self.state.lock().unwrap().global = Bucket::new(bytes_per_sec);
+        self.notify.notify_waiters();
+    }
+
+    /// Sets the limit of a single download; 0 removes it.
+    pub fn set_task_limit(&self, key: &str, bytes_per_sec: u64) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if bytes_per_sec == 0 {
+                state.tasks.remove(key);
+            } else {
+                state.tasks.insert(key.to_string(), Bucket::new(bytes_per_sec));
+            }
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// Removes the limit of a single download if it is still `bytes_per_sec`, leaving
+    /// one changed in the meantime in place.
+    pub fn clear_task_limit(&self, key: &str, bytes_per_sec: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.tasks.get(key).is_some_and(|bucket| bucket.rate == bytes_per_sec) {
+            state.tasks.remove(key);
+        }
+    }
+
+    pub fn limits(&self) -> BandwidthLimits {
+        let state = self.state.lock().unwrap();
+        BandwidthLimits {
+            global: state.global.rate,
+            tasks: state
+                .tasks
+                .iter()
+                .map(|(key, bucket)| (key.clone(), bucket.rate))
+                .collect(),
+        }
+    }
+
+    /// Handle used by a single transfer to throttle itself under `key`.
+    pub fn task<'a>(&'a self, key: &'a str) -> TaskThrottle<'a> {
+        TaskThrottle { limiter: self, key }
+    }
+
+    /// Waits until `bytes` more may be transferred for `key`. A limit change cuts the
+    /// wait short so the new rate applies straight away.
+    async fn throttle(&self, key: &str, bytes: usize) {
+        let delay = self.delay(key, bytes as u64, Instant::now());
+        if delay.is_zero() {
+            return;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = self.notify.notified() => {}
+        }
+    }
+
+    /// Charges `bytes` for `key` against the global and per-task buckets and returns
+    /// the longer of the two waits.
+    fn delay(&self, key: &str, bytes: u64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let global = state.global.take(bytes, now);
+        let task = state
+            .tasks
+            .get_mut(key)
+            .map_or(Duration::ZERO, |bucket| bucket.take(bytes, now));
+        global.max(task)
+    }
+}
+
+pub(crate) struct TaskThrottle<'a> {
+    limiter: &'a BandwidthLimiter,
+    key: &'a str,
+}
+
+impl TaskThrottle<'_> {
+    pub async fn wait(&self, bytes: usize) {
+        self.limiter.throttle(self.key, bytes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Puts every bucket on a common clock so the tests never depend on real time.
+    fn start(limiter: &BandwidthLimiter) -> Instant {
+        let now = Instant::now();
+        let mut state = limiter.state.lock().unwrap();
+        state.global.last = now;
+        for bucket in state.tasks.values_mut() {
+            bucket.last = now;
+        }
+        now
+    }
+
+    #[test]
+    fn buckets_refill_at_their_rate() {
+        let mut bucket = Bucket::new(1000);
+        let t0 = bucket.last;
+        assert_eq!(bucket.take(500, t0), ms(500));
+        assert_eq!(bucket.take(500, t0 + ms(1000)), Duration::ZERO);
+        assert_eq!(bucket.take(250, t0 + ms(1000)), ms(250));
+    }
+
+    #[test]
+    fn bursts_are_capped_at_one_second() {
+        let mut bucket = Bucket::new(1000);
+        let t0 = bucket.last;
+        assert_eq!(bucket.take(0, t0 + ms(10_000)), Duration::ZERO);
+        assert_eq!(bucket.take(1000, t0 + ms(10_000)), Duration::ZERO);
+        assert_eq!(bucket.take(500, t0 + ms(10_000)), ms(500));
+    }
+
+    #[test]
+    fn unlimited_buckets_never_wait() {
+        let mut bucket = Bucket::new(0);
+        let t0 = bucket.last;
+        assert_eq!(bucket.take(u32::MAX as u64, t0), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_slower_of_global_and_task_limits_wins() {
+        let limiter = BandwidthLimiter::new(1000);
+        limiter.set_task_limit("slow", 100);
+        let t0 = start(&limiter);
+
+        assert_eq!(limiter.delay("slow", 100, t0), ms(1000));
+        // The global bucket is shared, so other downloads pay for the bytes above
+        assert_eq!(limiter.delay("other", 100, t0), ms(200));
+        assert_eq!(limiter.delay("other", 800, t0 + ms(1000)), Duration::ZERO);
+
+        limiter.set_global_limit(0);
+        let t0 = start(&limiter);
+        assert_eq!(limiter.delay("other", 1_000_000, t0), Duration::ZERO);
+        assert_eq!(limiter.delay("slow", 50, t0 + ms(1000)), ms(500));
+    }
+
+    #[test]
+    fn limits_report_and_remove_task_limits() {
+        let limiter = BandwidthLimiter::new(0);
+        limiter.set_task_limit("a", 100);
+        limiter.set_task_limit("b", 200);
+        limiter.set_task_limit("b", 0);
+        let limits = limiter.limits();
+        assert_eq!(limits.global, 0);
+        assert_eq!(limits.tasks, HashMap::from([("a".to_string(), 100)]));
+
+        // A limit changed since the download started is left in place
+        limiter.clear_task_limit("a", 300);
+        assert_eq!(limiter.limits().tasks.get("a"), Some(&100));
+        limiter.clear_task_limit("a", 100);
+        assert!(limiter.limits().tasks.is_empty());
+
+        let t0 = start(&limiter);
+        assert_eq!(limiter.delay("a", 1_000_000, t0), Duration::ZERO);
+    }
+
+    #[test]
+    fn limit_changes_cut_waits_short() {
+        let limiter = BandwidthLimiter::new(1);
+        let throttle = limiter.task("a");
+        let waits = async {
+            tokio::join!(throttle.wait(3600), async {
+                tokio::task::yield_now().await;
+                limiter.set_global_limit(0);
+            })
+        };
+        let waited = tauri::async_runtime::block_on(async {
+            tokio::time::timeout(Duration::from_secs(60), waits).await
+        });
+        assert!(waited.is_ok());
+    }
+}
@@ -0,0 +1,592 @@
+// Update detection for installed repositories. Git installs are compared against their
+// upstream after a `git fetch`; ZIP installs against the commit recorded in a marker
+// file at install time, through the forge API. Neither touches the working tree.
+use crate::installs::InstalledRepo;
+use crate::process::{self, Program};
+use std::path::Path;
+use std::process::Command;
+
+/// Written into the root of every ZIP install so later checks know what it contains.
+pub(crate) const MARKER_FILE: &str = ".openstore-install.json";
+
+/// Most commit summaries returned by one check.
+pub(crate) const MAX_COMMITS: usize = 50;
+
+/// What a ZIP install was made from.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct InstallMarker {
+    pub url: String,
+    /// Branch or tag the install follows; `None` for the default branch.
+    #[serde(default)]
+    pub reference: Option<String>,
+    pub commit: String,
+    /// Installed from a fixed commit or a tag, which updates leave where it is.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl InstallMarker {
+    pub fn read(repo_path: &Path) -> Option<Self> {
+        let data = std::fs::read(repo_path.join(MARKER_FILE)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn write(&self, repo_path: &Path) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(repo_path.join(MARKER_FILE), data).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct CommitSummary {
+    pub sha: String,
+    /// First line of the commit message.
+    pub summary: String,
+    pub author: String,
+    /// ISO 8601 commit date, empty when the source does not report one.
+    pub date: String,
+}
+
+/// Result of `check_repository_updates`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct UpdateStatus {
+    /// `"git"` or `"zip"`.
+    pub method: &'static str,
+    /// Installed commit; `None` for ZIP installs made before markers were written.
+    pub current: Option<String>,
+    pub latest: Option<String>,
+    /// Local commits the remote does not have.
+    pub ahead: u32,
+    /// Remote commits not installed yet.
+    pub behind: u32,
+    /// Also true when the installed version is unknown and may be outdated. Always
+    /// false for pinned installs.
+    pub has_updates: bool,
+    /// `HEAD` is detached or the ZIP install was made from a fixed commit; such
+    /// installs are not updated, whatever their upstream does.
+    pub pinned: bool,
+    /// New commits, newest first, at most 50.
+    pub commits: Vec<CommitSummary>,
+}
+
+/// Runs a git `command` and returns its trimmed stdout, or its stderr on failure.
+fn git_output(command: &mut Command) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("无法运行 git 进程: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git(repo_path: &Path, args: &[&str]) -> Result<String, String> {
+    git_output(process::command(Program::Git, args).current_dir(repo_path))
+}
+
+/// Ref the checkout should be compared with: the tracked branch, or `origin/HEAD` for
+/// detached checkouts such as commit-pinned installs.
+fn upstream_ref(repo_path: &Path) -> Option<String> {
+    ["@{upstream}", "refs/remotes/origin/HEAD"]
+        .iter()
+        .find(|candidate| git(repo_path, &["rev-parse", "--verify", "--quiet", candidate]).is_ok())
+        .map(|candidate| candidate.to_string())
+}
+
+/// Fetches `origin` and compares `HEAD` with its upstream. Checkouts without any
+/// remote-tracking ref to compare with fall back to `git ls-remote origin HEAD`.
+pub(crate) fn check_git(
+    repo_path: &Path,
+    remote_url: &str,
+    token: Option<&str>,
+) -> Result<UpdateStatus, String> {
+    let authenticated = |args: &[&str]| {
+        let mut command = process::command(Program::Git, args);
+        command.current_dir(repo_path);
+        crate::git::authenticate(&mut command, remote_url, token);
+        git_output(&mut command)
+    };
+
+    authenticated(&["fetch", "--quiet", "origin"])?;
+    let current = git(repo_path, &["rev-parse", "HEAD"])?;
+    let pinned = git(repo_path, &["symbolic-ref", "--quiet", "HEAD"]).is_err();
+
+    let upstream = match upstream_ref(repo_path) {
+        Some(upstream) => upstream,
+        None => {
+            let remote = authenticated(&["ls-remote", "origin", "HEAD"])?;
+            let latest = remote.split_whitespace().next().map(str::to_string);
+            return Ok(UpdateStatus {
+                method: "git",
+                has_updates: !pinned && latest.as_ref().is_some_and(|latest| *latest != current),
+                pinned,
+                current: Some(current),
+                latest,
+                ahead: 0,
+                behind: 0,
+                commits: Vec::new(),
+            });
+        }
+    };
+
+    let latest = git(repo_path, &["rev-parse", &upstream])?;
+    let counts = git(
+        repo_path,
+        &["rev-list", "--left-right", "--count", &format!("HEAD...{}", upstream)],
+    )?;
+    let mut counts = counts.split_whitespace().map(|n| n.parse::<u32>().unwrap_or(0));
+    let (ahead, behind) = (counts.next().unwrap_or(0), counts.next().unwrap_or(0));
+
+    let log = git(
+        repo_path,
+        &[
+            "log",
+            "--format=%H%x1f%s%x1f%an%x1f%cI",
+            &format!("--max-count={}", MAX_COMMITS),
+            &format!("HEAD..{}", upstream),
+        ],
+    )?;
+    let commits = log
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\x1f');
+            Some(CommitSummary {
+                sha: fields.next()?.to_string(),
+                summary: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+            })
+        })
+        .collect();
+
+    Ok(UpdateStatus {
+        method: "git",
+        has_updates: !pinned && behind > 0,
+        pinned,
+        current: Some(current),
+        latest: Some(latest),
+        ahead,
+        behind,
+        commits,
+    })
+}
+
+/// Compares a ZIP install's marker with the latest commit of the ref it follows.
+pub(crate) async fn check_zip(
+    client: &reqwest::Client,
+    forge: &dyn crate::forge::Forge,
+    repo: &crate::forge::RepoRef,
+    marker: Option<InstallMarker>,
+    token: Option<&str>,
+) -> Result<UpdateStatus, String> {
+    let reference = marker.as_ref().and_then(|marker| marker.reference.as_deref());
+    let latest = crate::forge::latest_commit(client, forge, repo, reference, token).await?;
+    let pinned = marker.as_ref().is_some_and(|marker| marker.pinned);
+    let current = marker.map(|marker| marker.commit);
+
+    let mut commits = match &current {
+        Some(current) if *current != latest.sha => {
+            crate::forge::compare_commits(client, forge, repo, current, &latest.sha, token).await?
+        }
+        _ => Vec::new(),
+    };
+    let behind = commits.len() as u32;
+    commits.truncate(MAX_COMMITS);
+
+    Ok(UpdateStatus {
+        method: "zip",
+        has_updates: !pinned && current.as_ref() != Some(&latest.sha),
+        pinned,
+        current,
+        latest: Some(latest.sha),
+        ahead: 0,
+        behind,
+        commits,
+    })
+}
+
+/// How `pull_repository` treats local changes in a git install.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UpdateStrategy {
+    /// Fast-forward only; refuse to touch a worktree with local modifications.
+    #[default]
+    FfOnly,
+    /// Stash local changes (including untracked files), fast-forward, then re-apply them.
+    Stash,
+    /// Discard local changes and commits and move to the upstream revision.
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UpdateErrorKind {
+    /// Tracked files have uncommitted changes; retry with `stash` or `reset`.
+    DirtyWorktree,
+    /// Local commits prevent a fast-forward; retry with `reset`.
+    Diverged,
+    /// There is no pre-update snapshot to roll back to.
+    NoSnapshot,
+}
+
+/// An update refused to protect local state, serialised as `{ kind, files, message }`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct UpdateError {
+    pub kind: UpdateErrorKind,
+    /// Modified files for `dirty_worktree`, empty otherwise.
+    pub files: Vec<String>,
+    pub message: String,
+}
+
+impl UpdateError {
+    pub fn new(kind: UpdateErrorKind, files: Vec<String>) -> Self {
+        let message = match kind {
+            UpdateErrorKind::DirtyWorktree => {
+                format!("工作区有 {} 个文件存在本地修改，请选择暂存或重置后再更新", files.len())
+            }
+            UpdateErrorKind::Diverged => "本地提交与远程分支已分叉，无法快进更新".to_string(),
+            UpdateErrorKind::NoSnapshot => "没有可回滚的更新记录".to_string(),
+        };
+        Self {
+            kind,
+            files,
+            message,
+        }
+    }
+}
+
+/// Refs holding the pre-update revision and stashed local changes of a git install,
+/// so they survive `git gc` until the next update replaces them.
+pub(crate) const ROLLBACK_HEAD: &str = "refs/openstore/rollback/head";
+pub(crate) const ROLLBACK_CHANGES: &str = "refs/openstore/rollback/changes";
+
+pub(crate) fn short(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
+
+/// Updates a git install to its upstream with `strategy`, first recording the current
+/// revision and any local changes for `rollback_git`.
+pub(crate) fn pull_git(
+    repo_path: &Path,
+    remote_url: &str,
+    token: Option<&str>,
+    strategy: UpdateStrategy,
+) -> Result<String, crate::error::CommandError> {
+    let mut fetch = process::command(Program::Git, ["fetch", "--quiet", "origin"]);
+    fetch.current_dir(repo_path);
+    crate::git::authenticate(&mut fetch, remote_url, token);
+    git_output(&mut fetch)?;
+
+    if git(repo_path, &["rev-parse", "--verify", "--quiet", "@{upstream}"]).is_err() {
+        return Err("当前检出未跟踪远程分支（可能固定在某个标签或提交），无法更新".to_string().into());
+    }
+    let head = git(repo_path, &["rev-parse", "HEAD"])?;
+    let upstream = git(repo_path, &["rev-parse", "@{upstream}"])?;
+    if git(repo_path, &["merge-base", "--is-ancestor", "@{upstream}", "HEAD"]).is_ok() {
+        return Ok("已是最新版本".to_string());
+    }
+
+    let status = git(repo_path, &["status", "--porcelain", "--untracked-files=no"])?;
+    let files: Vec<String> = status
+        .lines()
+        .filter_map(|line| line.get(2..))
+        .map(|file| file.trim_start().to_string())
+        .collect();
+    let dirty = !files.is_empty();
+    let fast_forward = git(repo_path, &["merge-base", "--is-ancestor", "HEAD", "@{upstream}"]).is_ok();
+
+    if strategy == UpdateStrategy::FfOnly && dirty {
+        return Err(UpdateError::new(UpdateErrorKind::DirtyWorktree, files).into());
+    }
+    if strategy != UpdateStrategy::Reset && !fast_forward {
+        return Err(UpdateError::new(UpdateErrorKind::Diverged, Vec::new()).into());
+    }
+
+    // Snapshot before anything changes
+    let changes = match (strategy, dirty) {
+        (UpdateStrategy::Stash, true) => {
+            git(
+                repo_path,
+                &["stash", "push", "--include-untracked", "--message", "openstore: before update"],
+            )?;
+            Some(git(repo_path, &["rev-parse", "stash@{0}"])?)
+        }
+        // `stash create` records the changes without touching the worktree
+        (UpdateStrategy::Reset, true) => Some(git(repo_path, &["stash", "create"])?),
+        _ => None,
+    };
+    git(repo_path, &["update-ref", ROLLBACK_HEAD, &head])?;
+    match &changes {
+        Some(changes) => git(repo_path, &["update-ref", ROLLBACK_CHANGES, changes])?,
+        None => git(repo_path, &["update-ref", "-d", ROLLBACK_CHANGES])?,
+    };
+
+    let updated = match strategy {
+        UpdateStrategy::Reset => git(repo_path, &["reset", "--hard", "--quiet", "@{upstream}"]),
+        _ => git(repo_path, &["merge", "--ff-only", "--quiet", "@{upstream}"]),
+    };
+    let mut message = format!("已从 {} 更新到 {}", short(&head), short(&upstream));
+    if strategy == UpdateStrategy::Stash && changes.is_some() {
+        if git(repo_path, &["stash", "pop", "--quiet"]).is_err() {
+            message.push_str("，但恢复本地修改时发生冲突，修改仍保存在 git stash 中");
+        } else {
+            message.push_str("，本地修改已恢复");
+        }
+    }
+    updated?;
+    Ok(message)
+}
+
+/// Returns a git install to the revision and local changes recorded by the last
+/// `pull_git`.
+pub(crate) fn rollback_git(repo_path: &Path) -> Result<String, crate::error::CommandError> {
+    let head = git(repo_path, &["rev-parse", "--verify", "--quiet", ROLLBACK_HEAD])
+        .map_err(|_| UpdateError::new(UpdateErrorKind::NoSnapshot, Vec::new()))?;
+    let changes = git(repo_path, &["rev-parse", "--verify", "--quiet", ROLLBACK_CHANGES]).ok();
+
+    git(repo_path, &["reset", "--hard", "--quiet", &head])?;
+    if let Some(changes) = &changes {
+        // Untracked files restored by the stash may still be in the worktree; remove them
+        // so `stash apply` can put back the saved versions
+        let untracked = format!("{}^3", changes);
+        if let Ok(files) = git(repo_path, &["ls-tree", "-r", "--name-only", &untracked]) {
+            for file in files.lines() {
+                let _ = std::fs::remove_file(repo_path.join(file));
+            }
+        }
+        git(repo_path, &["stash", "apply", "--quiet", changes])?;
+    }
+    git(repo_path, &["update-ref", "-d", ROLLBACK_HEAD])?;
+    let _ = git(repo_path, &["update-ref", "-d", ROLLBACK_CHANGES]);
+
+    Ok(format!("已回滚到 {}", short(&head)))
+}
+
+/// Where the previous tree of a ZIP install is kept while it is updated: a hidden
+/// sibling folder, so restoring it is a rename on the same filesystem.
+pub(crate) fn zip_snapshot_path(repo_path: &Path) -> Option<std::path::PathBuf> {
+    let name = repo_path.file_name()?.to_string_lossy();
+    Some(repo_path.with_file_name(format!(".{}.rollback", name)))
+}
+
+/// Puts the tree saved by the last ZIP update back in place of the current one.
+pub(crate) fn rollback_zip(repo_path: &Path) -> Result<String, crate::error::CommandError> {
+    let snapshot = zip_snapshot_path(repo_path)
+        .filter(|snapshot| snapshot.is_dir())
+        .ok_or_else(|| UpdateError::new(UpdateErrorKind::NoSnapshot, Vec::new()))?;
+    crate::zipball::replace_dir(&snapshot, repo_path, None)?;
+    Ok("已回滚到更新前的版本".to_string())
+}
+
+/// Updates run at once by `update_all_repositories` unless the caller says otherwise.
+pub(crate) const DEFAULT_UPDATE_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BatchStatus {
+    Updated,
+    UpToDate,
+    Failed,
+}
+
+/// Outcome for one repository, also emitted as an `update-all-progress` event with the
+/// number of repositories finished so far.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct BatchOutcome {
+    pub path: String,
+    pub url: String,
+    pub status: BatchStatus,
+    /// Update message, or the reason of a failure.
+    pub message: String,
+    /// New commits that were installed, when known.
+    pub commits: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct BatchProgress<'a> {
+    #[serde(flatten)]
+    pub outcome: &'a BatchOutcome,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Result of `update_all_repositories`, grouped by outcome.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub(crate) struct UpdateSummary {
+    pub updated: Vec<BatchOutcome>,
+    pub up_to_date: Vec<BatchOutcome>,
+    pub failed: Vec<BatchOutcome>,
+}
+
+impl UpdateSummary {
+    pub fn push(&mut self, outcome: BatchOutcome) {
+        match outcome.status {
+            BatchStatus::Updated => self.updated.push(outcome),
+            BatchStatus::UpToDate => self.up_to_date.push(outcome),
+            BatchStatus::Failed => self.failed.push(outcome),
+        }
+    }
+}
+
+/// Runs `update` for every repository in `repos`, at most `max_concurrent` (by default
+/// `DEFAULT_UPDATE_CONCURRENCY`) at a time, and reports each finished repository to
+/// `on_progress`. `update` returns the status, message and number of new commits.
+pub(crate) async fn run_batch<F, Fut>(
+    repos: Vec<InstalledRepo>,
+    max_concurrent: Option<usize>,
+    update: F,
+    mut on_progress: impl FnMut(BatchProgress),
+) -> UpdateSummary
+where
+    F: Fn(InstalledRepo) -> Fut,
+    Fut: std::future::Future<Output = (BatchStatus, String, u32)>,
+{
+    use futures_util::StreamExt;
+
+    let total = repos.len();
+    let limit = max_concurrent.unwrap_or(DEFAULT_UPDATE_CONCURRENCY).max(1);
+    let mut outcomes = futures_util::stream::iter(repos)
+        .map(|repo| {
+            let (path, url) = (repo.path.clone(), repo.url.clone());
+            let update = update(repo);
+            async move {
+                let (status, message, commits) = update.await;
+                BatchOutcome {
+                    path,
+                    url,
+                    status,
+                    message,
+                    commits,
+                }
+            }
+        })
+        .buffer_unordered(limit);
+
+    let mut summary = UpdateSummary::default();
+    let mut done = 0;
+    while let Some(outcome) = outcomes.next().await {
+        done += 1;
+        on_progress(BatchProgress {
+            outcome: &outcome,
+            done,
+            total,
+        });
+        summary.push(outcome);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn repo(name: &str) -> InstalledRepo {
+        InstalledRepo {
+            url: format!("https://github.com/o/{}", name),
+            path: format!("/downloads/GitAppStore/{}", name),
+            status: "completed".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn summary_groups_outcomes_by_status() {
+        let mut summary = UpdateSummary::default();
+        for (name, status) in [
+            ("a", BatchStatus::Updated),
+            ("b", BatchStatus::Failed),
+            ("c", BatchStatus::UpToDate),
+            ("d", BatchStatus::Updated),
+        ] {
+            summary.push(BatchOutcome {
+                path: String::new(),
+                url: name.to_string(),
+                status,
+                message: String::new(),
+                commits: 0,
+            });
+        }
+        let urls = |outcomes: &[BatchOutcome]| -> Vec<String> {
+            outcomes.iter().map(|outcome| outcome.url.clone()).collect()
+        };
+        assert_eq!(urls(&summary.updated), ["a", "d"]);
+        assert_eq!(urls(&summary.up_to_date), ["c"]);
+        assert_eq!(urls(&summary.failed), ["b"]);
+    }
+
+    #[test]
+    fn batches_report_every_repository_within_the_limit() {
+        let repos: Vec<InstalledRepo> = (0..10).map(|n| repo(&n.to_string())).collect();
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let update = |repo: InstalledRepo| {
+            let (running, most) = (&running, &most);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                let n: u32 = repo.url.rsplit('/').next().unwrap().parse().unwrap();
+                match n % 3 {
+                    0 => (BatchStatus::Updated, format!("updated {}", n), n),
+                    1 => (BatchStatus::UpToDate, "已是最新版本".to_string(), 0),
+                    _ => (BatchStatus::Failed, "工作区有本地修改".to_string(), 0),
+                }
+            }
+        };
+        let mut progress = Vec::new();
+        let summary = tauri::async_runtime::block_on(run_batch(repos, None, update, |p| {
+            progress.push((p.outcome.url.clone(), p.done, p.total));
+        }));
+
+        assert_eq!(most.load(Ordering::SeqCst), DEFAULT_UPDATE_CONCURRENCY);
+        assert_eq!(summary.updated.len(), 4);
+        assert_eq!(summary.up_to_date.len(), 3);
+        assert_eq!(summary.failed.len(), 3);
+        let six = summary
+            .updated
+            .iter()
+            .find(|outcome| outcome.url.ends_with("/6"))
+            .unwrap();
+        assert_eq!(six.path, "/downloads/GitAppStore/6");
+        assert_eq!((six.message.as_str(), six.commits), ("updated 6", 6));
+        assert_eq!(
+            progress
+                .iter()
+                .map(|(_, done, _)| *done)
+                .collect::<Vec<_>>(),
+            (1..=10).collect::<Vec<_>>()
+        );
+        assert!(progress.iter().all(|(_, _, total)| *total == 10));
+    }
+
+    #[test]
+    fn batches_run_at_least_one_at_a_time() {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let update = |_| {
+            let (running, most) = (&running, &most);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                (BatchStatus::UpToDate, String::new(), 0)
+            }
+        };
+        let repos = vec![repo("a"), repo("b"), repo("c")];
+        let summary = tauri::async_runtime::block_on(run_batch(repos, Some(0), update, |_| {}));
+        assert_eq!(most.load(Ordering::SeqCst), 1);
+        assert_eq!(summary.up_to_date.len(), 3);
+    }
+
+    #[test]
+    fn markers_without_a_pin_follow_their_reference() {
+        let marker: InstallMarker =
+            serde_json::from_str(r#"{ "url": "https://github.com/o/r", "commit": "abc" }"#)
+                .unwrap();
+        assert!(!marker.pinned);
+        assert_eq!(marker.reference, None);
+    }
+}
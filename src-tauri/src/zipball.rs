@@ -0,0 +1,391 @@
+// Native zipball download & extraction, used by `clone_repository` when git is
+// unavailable or the user picked the ZIP method. Works the same on every OS.
+use crate::throttle::TaskThrottle;
+use futures_util::StreamExt;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Sends `request` (already authorised for its forge) and saves the archive to `zip_path`.
+/// `on_progress` receives `(downloaded, total)` bytes; `total` is 0 when the server
+/// streams the archive without a Content-Length, as GitHub usually does.
+pub(crate) async fn download_zipball(
+    request: reqwest::RequestBuilder,
+    zip_path: &Path,
+    throttle: &TaskThrottle<'_>,
+    on_progress: &mut (impl FnMut(u64, u64) + Send),
+) -> Result<(), String> {
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP 错误，状态码: {}", response.status()));
+    }
+
+    let total_size = response.content_length().unwrap_or(0);
+    let mut file = std::fs::File::create(zip_path).map_err(|e| e.to_string())?;
+    let mut downloaded: u64 = 0;
+    let mut stream = response.bytes_stream();
+    on_progress(0, total_size);
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = chunk_result.map_err(|e| e.to_string())?;
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        downloaded += chunk.len() as u64;
+        on_progress(downloaded, total_size);
+        throttle.wait(chunk.len()).await;
+    }
+    file.flush().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Returns the single top-level folder shared by every entry (forges wrap
+/// archives in a folder such as `owner-repo-sha/`), or `None` if the archive has no common root.
+fn common_root<R: std::io::Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Option<PathBuf> {
+    let mut root: Option<PathBuf> = None;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).ok()?;
+        // Entries escaping the archive are skipped on extraction, so they do not count
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let mut components = name.components();
+        let first = match components.next() {
+            Some(Component::Normal(first)) => PathBuf::from(first),
+            _ => return None,
+        };
+        // A file sitting directly at the archive root means there is nothing to strip
+        if components.next().is_none() && !entry.is_dir() {
+            return None;
+        }
+        match &root {
+            Some(existing) if *existing != first => return None,
+            Some(_) => {}
+            None => root = Some(first),
+        }
+    }
+    root
+}
+
+/// Whether `relative` survives a sparse checkout of `sparse_paths`: files at the root
+/// and anything inside one of the listed directories, like git's cone mode.
+fn in_sparse_set(relative: &Path, is_dir: bool, sparse_paths: &[String]) -> bool {
+    if sparse_paths.is_empty() || (!is_dir && relative.components().count() == 1) {
+        return true;
+    }
+    sparse_paths.iter().any(|sparse| {
+        let sparse = Path::new(sparse.trim_matches('/'));
+        relative.starts_with(sparse) || (is_dir && sparse.starts_with(relative))
+    })
+}
+
+/// Extracts `zip_path` into `extract_dir`, stripping the archive's top-level folder.
+/// With `sparse_paths`, only the entries a sparse checkout of them would contain.
+pub(crate) fn extract_zipball(
+    zip_path: &Path,
+    extract_dir: &Path,
+    sparse_paths: &[String],
+) -> Result<(), String> {
+    let file = std::fs::File::open(zip_path).map_err(|e| e.to_string())?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("无法读取 ZIP 文件: {}", e))?;
+    let root = common_root(&mut archive);
+
+    std::fs::create_dir_all(extract_dir).map_err(|e| e.to_string())?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("无法读取 ZIP 条目: {}", e))?;
+
+        // Skip entries whose names would escape the extraction directory
+        let name = match entry.enclosed_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let relative = match &root {
+            Some(root) => match name.strip_prefix(root) {
+                Ok(rest) => rest.to_path_buf(),
+                Err(_) => continue,
+            },
+            None => name,
+        };
+        if relative.as_os_str().is_empty()
+            || !in_sparse_set(&relative, entry.is_dir(), sparse_paths)
+        {
+            continue;
+        }
+
+        let out_path = extract_dir.join(&relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path).map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = std::fs::File::create(&out_path).map_err(|e| e.to_string())?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode));
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `staging` to `dest`, keeping the previous `dest` aside until the rename succeeds.
+/// With `keep`, the previous `dest` is left at that path afterwards instead of deleted.
+pub(crate) fn replace_dir(staging: &Path, dest: &Path, keep: Option<&Path>) -> Result<(), String> {
+    if !dest.exists() {
+        return std::fs::rename(staging, dest).map_err(|e| e.to_string());
+    }
+
+    let backup = match keep {
+        Some(keep) => keep.to_path_buf(),
+        None => {
+            let mut backup = dest.as_os_str().to_owned();
+            backup.push(".old");
+            PathBuf::from(backup)
+        }
+    };
+    if backup.exists() {
+        std::fs::remove_dir_all(&backup).map_err(|e| e.to_string())?;
+    }
+
+    std::fs::rename(dest, &backup).map_err(|e| e.to_string())?;
+    if let Err(e) = std::fs::rename(staging, dest) {
+        let _ = std::fs::rename(&backup, dest);
+        return Err(e.to_string());
+    }
+    if keep.is_none() {
+        let _ = std::fs::remove_dir_all(&backup);
+    }
+
+    Ok(())
+}
+
+/// Hidden name for the temporary files of one install, unique even when the same
+/// repository is installed twice at once. Being hidden keeps them out of integrity scans.
+fn temp_stem(stem: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        ".{}-{}-{}",
+        stem,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Downloads the zipball `request` points at and extracts it into `dest`. Temporary files are named after
+/// `stem` next to `dest` and are removed whether or not the install succeeds. A previous
+/// tree at `dest` is moved to `keep` when given, for rolling the update back.
+pub(crate) async fn install_zipball(
+    request: reqwest::RequestBuilder,
+    stem: &str,
+    dest: &Path,
+    sparse_paths: &[String],
+    keep: Option<&Path>,
+    throttle: &TaskThrottle<'_>,
+    on_progress: &mut (impl FnMut(u64, u64) + Send),
+) -> Result<(), String> {
+    let work_dir = dest.parent().ok_or_else(|| "无效的目标目录".to_string())?;
+    std::fs::create_dir_all(work_dir).map_err(|e| e.to_string())?;
+
+    let stem = temp_stem(stem);
+    let zip_path = work_dir.join(format!("{}.zip", stem));
+    let staging = work_dir.join(format!("{}_temp_extract", stem));
+
+    let result = async {
+        download_zipball(request, &zip_path, throttle, on_progress).await?;
+
+        let (zip_src, extract_dir) = (zip_path.clone(), staging.clone());
+        let sparse_paths = sparse_paths.to_vec();
+        tauri::async_runtime::spawn_blocking(move || {
+            if extract_dir.exists() {
+                std::fs::remove_dir_all(&extract_dir).map_err(|e| e.to_string())?;
+            }
+            extract_zipball(&zip_src, &extract_dir, &sparse_paths)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        replace_dir(&staging, dest, keep)
+    }
+    .await;
+
+    let _ = std::fs::remove_file(&zip_path);
+    let _ = std::fs::remove_dir_all(&staging);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throttle::BandwidthLimiter;
+    use std::io::{Read, Write};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openstore-zipball-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A zip archive holding `entries`, names ending in `/` as directories.
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in entries {
+            if name.ends_with('/') {
+                writer.add_directory(*name, options).unwrap();
+            } else {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut found = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            for entry in std::fs::read_dir(next).unwrap().flatten() {
+                if entry.file_type().unwrap().is_dir() {
+                    pending.push(entry.path());
+                } else {
+                    let relative = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+                    found.push(relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn extract_strips_the_top_level_folder() {
+        let dir = scratch_dir("strip");
+        let zip_path = dir.join("repo.zip");
+        std::fs::write(
+            &zip_path,
+            archive(&[
+                ("owner-repo-1a2b3c/", ""),
+                ("owner-repo-1a2b3c/README.md", "readme"),
+                ("owner-repo-1a2b3c/src/", ""),
+                ("owner-repo-1a2b3c/src/main.rs", "fn main() {}"),
+                ("owner-repo-1a2b3c/docs/guide.md", "guide"),
+            ]),
+        )
+        .unwrap();
+
+        extract_zipball(&zip_path, &dir.join("all"), &[]).unwrap();
+        assert_eq!(
+            files(&dir.join("all")),
+            ["README.md", "docs/guide.md", "src/main.rs"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("all/src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+
+        extract_zipball(&zip_path, &dir.join("sparse"), &["src".to_string()]).unwrap();
+        assert_eq!(files(&dir.join("sparse")), ["README.md", "src/main.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn extract_skips_entries_escaping_the_target() {
+        let dir = scratch_dir("slip");
+        let zip_path = dir.join("evil.zip");
+        std::fs::write(
+            &zip_path,
+            archive(&[
+                ("repo-main/ok.txt", "ok"),
+                ("repo-main/../../escaped.txt", "evil"),
+                ("../escaped.txt", "evil"),
+                ("/tmp/openstore-zipball-absolute.txt", "evil"),
+            ]),
+        )
+        .unwrap();
+
+        let target = dir.join("nested/out");
+        extract_zipball(&zip_path, &target, &[]).unwrap();
+        assert_eq!(files(&target), ["ok.txt"]);
+        assert_eq!(files(&dir), ["evil.zip", "nested/out/ok.txt"]);
+        assert!(!dir.parent().unwrap().join("escaped.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Serves `body` once over HTTP on a local port and returns its URL.
+    fn serve_once(body: Vec<u8>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/archive.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn install_downloads_extracts_and_replaces() {
+        let dir = scratch_dir("install");
+        let dest = dir.join("owner_repo");
+        let keep = dir.join(".owner_repo.previous");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(dest.join("old.txt"), "old").unwrap();
+
+        let body = archive(&[
+            ("owner-repo-abc/README.md", "new"),
+            ("owner-repo-abc/src/lib.rs", ""),
+        ]);
+        let size = body.len() as u64;
+        let url = serve_once(body);
+        let limiter = BandwidthLimiter::new(0);
+        let mut progress = Vec::new();
+        tauri::async_runtime::block_on(install_zipball(
+            reqwest::Client::new().get(url),
+            "owner_repo",
+            &dest,
+            &[],
+            Some(&keep),
+            &limiter.task("test"),
+            &mut |downloaded, total| progress.push((downloaded, total)),
+        ))
+        .unwrap();
+
+        assert_eq!(files(&dest), ["README.md", "src/lib.rs"]);
+        assert_eq!(files(&keep), ["old.txt"]);
+        assert_eq!(progress.last(), Some(&(size, size)));
+        // The archive and staging folder are gone
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(left, [".owner_repo.previous", "owner_repo"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn temporary_names_are_unique() {
+        assert_ne!(temp_stem("owner_repo"), temp_stem("owner_repo"));
+        assert!(temp_stem("owner_repo").starts_with(".owner_repo-"));
+    }
+}
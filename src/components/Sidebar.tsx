@@ -8,6 +8,7 @@ export default function Sidebar() {
     activeTab,
     setActiveTab,
     gitInstalled,
+    gitCliInstalled,
     checkGit,
     mobileSidebarOpen,
     setMobileSidebarOpen
@@ -162,11 +163,11 @@ export default function Sidebar() {
               <span
                 className={cn(
                   "w-1.5 h-1.5 rounded-full",
-                  gitInstalled ? "bg-green-500 shadow shadow-green-500/30" : "bg-yellow-500 shadow shadow-yellow-500/30"
+                  gitCliInstalled ? "bg-green-500 shadow shadow-green-500/30" : "bg-yellow-500 shadow shadow-yellow-500/30"
                 )}
               />
               <span className="text-[10px] font-bold">
-                {gitInstalled ? "Git CLI 正常工作" : "Git 未就绪 (ZIP 兼容)"}
+                {gitCliInstalled ? "Git 正常工作" : gitInstalled ? "未安装 Git (使用内置 Git)" : "Git 未就绪 (ZIP 兼容)"}
               </span>
             </div>
           </div>
@@ -3,6 +3,7 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import Database from "@tauri-apps/plugin-sql";
 import { UrlSourceConfig, UrlSourceTestResult, testUrlSource as testUrlSourceAdapter, clearUrlSourceCache } from "@/lib/urlSourcesAdapter";
+import { sourceCredentialHost } from "@/lib/forgeApi";
 
 export interface DataSource {
   id: string;
@@ -11,7 +12,8 @@ export interface DataSource {
   apiEndpointMode: "public" | "enterprise";
   customEndpoint: string;
   apiVersion: string;
-  token: string;
+  /** 令牌保存在后端凭据库中(主机见 sourceCredentialHost),这里只标记是否已配置 */
+  hasToken?: boolean;
   addedAt: string;
   enabled?: boolean;
   /** openstore_api 类型:首页/无 query 时按这个列表逐个请求并合并 */
@@ -26,19 +28,139 @@ export interface InstalledRepo {
   path: string;
   status: "completed" | "failed" | "cloning" | "downloading_zip" | "pulling";
   message: string;
+  /** 克隆 / ZIP 下载进度（仅在任务进行中有意义） */
+  downloaded?: number;
+  total?: number;
+  percent?: number;
   dateInstalled: string;
   description: string;
   primaryLanguage: string;
   stars: number;
 }
 
+/** 克隆参数：浅克隆深度、分支/标签、固定提交与稀疏检出目录（ZIP 方式同样适用） */
+export interface CloneOptions {
+  depth?: number;
+  reference?: string;
+  commit?: string;
+  sparse_paths?: string[];
+  /** 递归初始化子模块 */
+  submodules?: boolean;
+  /** 拉取 Git LFS 文件（需要安装 git-lfs） */
+  lfs?: boolean;
+}
+
+/** pull_repository 处理本地修改的方式：仅快进 / 暂存后更新 / 重置到远程 */
+export type UpdateStrategy = "ff_only" | "stash" | "reset";
+
+export interface CommitSummary {
+  sha: string;
+  summary: string;
+  author: string;
+  date: string;
+}
+
+/** check_repository_updates 的结果：ahead/behind 为本地领先/落后的提交数 */
+export interface RepoUpdateStatus {
+  method: "git" | "zip";
+  current: string | null;
+  latest: string | null;
+  ahead: number;
+  behind: number;
+  has_updates: boolean;
+  /** 固定在某个提交或标签（游离 HEAD 或固定提交的 ZIP 安装），不会被更新 */
+  pinned: boolean;
+  commits: CommitSummary[];
+}
+
+export interface BatchOutcome {
+  path: string;
+  url: string;
+  status: "updated" | "up_to_date" | "failed";
+  message: string;
+  commits: number;
+}
+
+/** update_all_repositories 的汇总结果 */
+export interface UpdateSummary {
+  updated: BatchOutcome[];
+  up_to_date: BatchOutcome[];
+  failed: BatchOutcome[];
+}
+
+/** 后台定时检查发现的更新（updates-available 事件） */
+export interface UpdateNotice {
+  kind: "repository" | "release";
+  url: string;
+  path: string | null;
+  current: string | null;
+  latest: string;
+  commits: CommitSummary[];
+}
+
+/** uninstall_repository 的结果；dry_run 时仅列出将被删除的内容 */
+export interface UninstallReport {
+  mode: "delete" | "trash";
+  dry_run: boolean;
+  targets: string[];
+  files: number;
+  bytes: number;
+}
+
+export type RepairAction = "adopt" | "reclone" | "forget";
+
+/** scan_installations 发现的问题：目录丢失、.git 损坏、安装不完整、HEAD 游离或下载目录中的未知文件夹 */
+export interface InstallIssue {
+  kind:
+    | "missing_folder"
+    | "broken_git"
+    | "incomplete"
+    | "detached_head"
+    | "unknown_folder";
+  path: string;
+  url: string | null;
+  detail: string;
+  actions: RepairAction[];
+}
+
+/** 从本地导入的 Git 仓库 */
+export interface LocalRepo {
+  path: string;
+  url: string;
+  host: string;
+  owner: string;
+  repo: string;
+  commit: string;
+  branch: string | null;
+}
+
+export interface ImportSummary {
+  imported: LocalRepo[];
+  skipped: { path: string; reason: string }[];
+}
+
 export interface AssetDownload {
   url: string;
   filename: string;
   downloaded: number;
   total: number;
   percent: number;
-  status: "downloading" | "completed" | "failed" | "cancelled";
+  status: "queued" | "downloading" | "paused" | "retrying" | "verifying" | "completed" | "failed" | "cancelled";
+  attempt?: number;
+}
+
+/** get_download_history 的一条记录（最新的在前） */
+export interface DownloadHistoryEntry {
+  url: string;
+  filename: string;
+  path: string;
+  size: number;
+  /** 下载完成时计算的摘要，格式为 `algorithm:hex` */
+  hash: string | null;
+  started_at: number;
+  finished_at: number;
+  status: "completed" | "failed" | "cancelled";
+  error: string | null;
 }
 
 interface AppContextType {
@@ -48,16 +170,27 @@ interface AppContextType {
   setSelectedRepo: (repo: any | null) => void;
   searchQuery: string;
   setSearchQuery: (query: string) => void;
-  githubToken: string;
+  hasGithubToken: boolean;
   setGithubToken: (token: string) => void;
   downloadDir: string;
   setDownloadDir: (dir: string) => void;
   gitInstalled: boolean;
+  /** A git executable is installed (SSH remotes, sparse checkouts) */
+  gitCliInstalled: boolean;
+  /** The git-lfs extension is installed */
+  gitLfsInstalled: boolean;
   checkGit: () => Promise<boolean>;
   installedRepos: InstalledRepo[];
-  installRepository: (owner: string, repo: string, stars: number, description: string, language: string, customUrl?: string) => Promise<void>;
-  updateRepository: (repo: InstalledRepo) => Promise<void>;
+  installRepository: (owner: string, repo: string, stars: number, description: string, language: string, customUrl?: string, options?: CloneOptions) => Promise<void>;
+  updateRepository: (repo: InstalledRepo, strategy?: UpdateStrategy) => Promise<void>;
+  rollbackRepository: (repo: InstalledRepo) => Promise<void>;
+  updateAllRepositories: () => Promise<UpdateSummary>;
+  checkRepositoryUpdates: (repo: InstalledRepo) => Promise<RepoUpdateStatus>;
   uninstallRepository: (repo: InstalledRepo) => Promise<void>;
+  previewUninstall: (repo: InstalledRepo) => Promise<UninstallReport | null>;
+  scanInstallations: () => Promise<InstallIssue[]>;
+  repairInstallation: (issue: InstallIssue, action: RepairAction) => Promise<string>;
+  importLocalRepositories: (dir: string) => Promise<ImportSummary>;
   openFolder: (path: string) => Promise<void>;
   openVSCode: (path: string) => Promise<void>;
   searchHistory: string[];
@@ -73,6 +206,9 @@ interface AppContextType {
   // Binary asset downloads
   assetDownloads: Record<string, AssetDownload>;
 
+  // Updates found by the background scheduler
+  availableUpdates: UpdateNotice[];
+
   // Personalization settings
   theme: "auto" | "light" | "dark";
   setTheme: (theme: "auto" | "light" | "dark") => void;
@@ -94,8 +230,10 @@ interface AppContextType {
   setMobileSidebarOpen: (open: boolean) => void;
 
   // Gitee token settings
-  giteeToken: string;
+  hasGiteeToken: boolean;
   setGiteeToken: (token: string) => void;
+  /** 保存数据源的令牌（空字符串表示删除） */
+  setSourceToken: (source: DataSource, token: string) => Promise<void>;
 
   // Network sensing states
   preferredPlatform: "github" | "gitee";
@@ -126,20 +264,58 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
   const dbRef = useRef<Database | null>(null);
   
   // Settings State
-  const [githubToken, setGithubTokenState] = useState<string>(
-    localStorage.getItem("git_store_token") || ""
-  );
-  const [giteeToken, setGiteeTokenState] = useState<string>(
-    localStorage.getItem("git_store_gitee_token") || ""
-  );
+  // Tokens stay in the backend credential store; the UI only knows which hosts have one
+  const [tokenHosts, setTokenHosts] = useState<Record<string, boolean>>({});
+  const hasGithubToken = !!tokenHosts["github.com"];
+  const hasGiteeToken = !!tokenHosts["gitee.com"];
   const [downloadDir, setDownloadDirState] = useState<string>(
     localStorage.getItem("git_store_download_dir") || ""
   );
   const [gitInstalled, setGitInstalled] = useState<boolean>(false);
+  const [gitCliInstalled, setGitCliInstalled] = useState<boolean>(false);
+  const [gitLfsInstalled, setGitLfsInstalled] = useState<boolean>(false);
+
+  // Git operations and forge API requests read tokens from the backend credential
+  // store, keyed by host; an empty token removes the stored one
+  const storeToken = async (host: string, token: string) => {
+    const command = token.trim() ? "set_credential" : "delete_credential";
+    await invoke(command, { host, token: token.trim() });
+    setTokenHosts((prev) => ({ ...prev, [host]: token.trim() !== "" }));
+  };
+
+  const refreshTokenHost = (host: string) => {
+    invoke<boolean>("has_credential", { host })
+      .then((has) => setTokenHosts((prev) => ({ ...prev, [host]: has })))
+      .catch((err) => console.error(`Failed to look up credential for ${host}:`, err));
+  };
 
   const setGiteeToken = (token: string) => {
-    setGiteeTokenState(token);
-    localStorage.setItem("git_store_gitee_token", token);
+    storeToken("gitee.com", token).catch((err) =>
+      console.error("Failed to store credential for gitee.com:", err)
+    );
+  };
+
+  const setSourceToken = async (source: DataSource, token: string) => {
+    const host = sourceCredentialHost(source);
+    if (host) await storeToken(host, token);
+  };
+
+  // Earlier versions saved each source's token with it; move such tokens into the
+  // credential store and drop them from the source
+  const withoutTokens = async (list: (DataSource & { token?: string })[]) => {
+    const stripped: DataSource[] = [];
+    for (const { token, ...source } of list) {
+      const host = sourceCredentialHost(source);
+      if (token && token.trim() && host) {
+        try {
+          await storeToken(host, token);
+        } catch (err) {
+          console.error(`Failed to store credential for ${host}:`, err);
+        }
+      }
+      stripped.push(source);
+    }
+    return stripped;
   };
 
 
@@ -242,6 +418,63 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
     localStorage.setItem("git_store_url_sources", JSON.stringify(urlSources));
   }, [urlSources]);
 
+  // Move tokens earlier versions kept in localStorage into the backend credential store
+  // (once), then look up which hosts have one
+  useEffect(() => {
+    const tokens: [string, string][] = [
+      ["github.com", "git_store_token"],
+      ["gitee.com", "git_store_gitee_token"],
+    ];
+    tokens.forEach(async ([host, key]) => {
+      try {
+        const legacy = localStorage.getItem(key);
+        if (legacy !== null) {
+          if (legacy.trim()) {
+            await invoke("set_credential", { host, token: legacy.trim() });
+          }
+          localStorage.removeItem(key);
+        }
+      } catch (err) {
+        console.error(`Failed to migrate credential for ${host}:`, err);
+      }
+      refreshTokenHost(host);
+    });
+  }, []);
+
+  // Self-hosted sources are registered as forges (for ZIP installs and API requests);
+  // their tokens are stored under the host of their API endpoint
+  useEffect(() => {
+    customDataSources.forEach((source) => {
+      const host = sourceCredentialHost(source);
+      if (host) refreshTokenHost(host);
+      if (source.apiEndpointMode !== "enterprise" || !source.customEndpoint) return;
+      try {
+        const endpoint = new URL(source.customEndpoint);
+        if (source.platform === "github" || source.platform === "gitee") {
+          invoke("add_forge", {
+            forge: { kind: source.platform, base_url: endpoint.origin, api_base: source.customEndpoint },
+          }).catch((err) => console.error(`Failed to register forge ${endpoint.origin}:`, err));
+        }
+      } catch (e) {}
+    });
+  }, [customDataSources]);
+
+  // Re-apply the saved global bandwidth limit (KB/s) to the backend on startup
+  useEffect(() => {
+    const kbps = Number(localStorage.getItem("git_store_rate_limit_kbps") || 0);
+    if (kbps > 0) {
+      invoke("set_download_rate_limit", { bytesPerSecond: kbps * 1024 }).catch(() => {});
+    }
+  }, []);
+
+  // Re-apply the saved background update check interval (minutes, 0 = off) on startup
+  useEffect(() => {
+    const saved = localStorage.getItem("git_store_update_interval_minutes");
+    if (saved !== null) {
+      invoke("set_update_check_interval", { minutes: Math.max(0, Number(saved) || 0) }).catch(() => {});
+    }
+  }, []);
+
   /* ---------------- URL data sources helpers ---------------- */
   const addUrlSource = (source: UrlSourceConfig) => {
     setUrlSources((prev) => {
@@ -285,17 +518,21 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
       apiEndpointMode: "public",
       customEndpoint: "https://api.github.com",
       apiVersion: "2026-03-10",
-      token: githubToken,
       addedAt: "系统内置",
     };
 
-    return [builtinGithub, ...customDataSources].map(s => ({
-      ...s,
-      enabled: !disabledSourceIds.includes(s.id)
-    }));
-  }, [customDataSources, githubToken, disabledSourceIds]);
+    return [builtinGithub, ...customDataSources].map(s => {
+      const host = sourceCredentialHost(s);
+      return {
+        ...s,
+        hasToken: !!host && !!tokenHosts[host],
+        enabled: !disabledSourceIds.includes(s.id)
+      };
+    });
+  }, [customDataSources, tokenHosts, disabledSourceIds]);
 
   const [assetDownloads, setAssetDownloads] = useState<Record<string, AssetDownload>>({});
+  const [availableUpdates, setAvailableUpdates] = useState<UpdateNotice[]>([]);
   
   // Search History (retains in localStorage for quick access)
   const [searchHistory, setSearchHistory] = useState<string[]>(() => {
@@ -391,35 +628,45 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
   }, [dataSources]);
 
   const setGithubToken = (token: string) => {
-    setGithubTokenState(token);
-    localStorage.setItem("git_store_token", token);
+    storeToken("github.com", token).catch((err) =>
+      console.error("Failed to store credential for github.com:", err)
+    );
+  };
+
+  // Installs go into (and uninstall only deletes from) the GitAppStore folder of the
+  // download directory; the backend resolves and creates it
+  const registerDownloadDir = (dir: string) => {
+    if (!dir) return;
+    invoke<string>("register_download_dir", { dir })
+      .then((installDir) => {
+        if (installDir !== dir) {
+          setDownloadDirState(installDir);
+          localStorage.setItem("git_store_download_dir", installDir);
+        }
+      })
+      .catch((err) => console.error("Failed to register download directory:", err));
   };
 
   const setDownloadDir = (dir: string) => {
     setDownloadDirState(dir);
     localStorage.setItem("git_store_download_dir", dir);
+    registerDownloadDir(dir);
   };
 
-  // SQLite database synchronization helpers
+  // Installed repositories live in the backend's store; these keep it in step with the UI
   const saveRepoToDb = async (repo: InstalledRepo) => {
-    if (!dbRef.current) return;
     try {
-      await dbRef.current.execute(
-        `INSERT OR REPLACE INTO installed_repos (url, owner, repo, name, path, status, message, dateInstalled, description, primaryLanguage, stars) 
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)`,
-        [repo.url, repo.owner, repo.repo, repo.name, repo.path, repo.status, repo.message, repo.dateInstalled, repo.description, repo.primaryLanguage, repo.stars]
-      );
+      await invoke("record_install", { repo });
     } catch (err) {
-      console.error("Failed to save repository record to SQLite database:", err);
+      console.error("Failed to save repository record:", err);
     }
   };
 
   const deleteRepoFromDb = async (url: string) => {
-    if (!dbRef.current) return;
     try {
-      await dbRef.current.execute("DELETE FROM installed_repos WHERE url = $1", [url]);
+      await invoke("forget_install", { url });
     } catch (err) {
-      console.error("Failed to delete repository record from SQLite database:", err);
+      console.error("Failed to delete repository record:", err);
     }
   };
 
@@ -430,46 +677,11 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
         const db = await Database.load("sqlite:openstore.db");
         dbRef.current = db;
 
-        // 1. Create table installed_repos
-        await db.execute(`
-          CREATE TABLE IF NOT EXISTS installed_repos (
-            url TEXT PRIMARY KEY,
-            owner TEXT,
-            repo TEXT,
-            name TEXT,
-            path TEXT,
-            status TEXT,
-            message TEXT,
-            dateInstalled TEXT,
-            description TEXT,
-            primaryLanguage TEXT,
-            stars INTEGER
-          )
-        `);
-
-        // 2. Create table data_sources
-        await db.execute(`
-          CREATE TABLE IF NOT EXISTS data_sources (
-            id TEXT PRIMARY KEY,
-            name TEXT,
-            apiEndpointMode TEXT,
-            customEndpoint TEXT,
-            apiVersion TEXT,
-            token TEXT,
-            addedAt TEXT,
-            platform TEXT
-          )
-        `);
-
-        // Migration: add platform column if not exists
-        try {
-          await db.execute("ALTER TABLE data_sources ADD COLUMN platform TEXT");
-        } catch (e) {
-          // column already exists, safe to ignore
-        }
+        // The backend creates and migrates the schema before the window opens
+        registerDownloadDir(localStorage.getItem("git_store_download_dir") || "");
 
-        // 3. Load installed repos
-        const repos = await db.select<InstalledRepo[]>("SELECT * FROM installed_repos");
+        // 1. Load installed repos
+        const repos = await invoke<InstalledRepo[]>("list_installed");
         if (repos.length > 0) {
           setInstalledRepos(repos);
         } else {
@@ -478,32 +690,29 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
           if (saved) {
             const list = JSON.parse(saved) as InstalledRepo[];
             for (const repo of list) {
-              await db.execute(
-                `INSERT OR REPLACE INTO installed_repos (url, owner, repo, name, path, status, message, dateInstalled, description, primaryLanguage, stars) 
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)`,
-                [repo.url, repo.owner, repo.repo, repo.name, repo.path, repo.status, repo.message, repo.dateInstalled, repo.description, repo.primaryLanguage, repo.stars]
-              );
+              await saveRepoToDb(repo);
             }
             setInstalledRepos(list);
           }
         }
 
-        // 4. Load data sources
-        let sources = await db.select<DataSource[]>("SELECT * FROM data_sources");
-        if (sources.length === 0) {
+        // 2. Load data sources (the backend has already moved their tokens out)
+        let sources = await withoutTokens(await db.select<DataSource[]>("SELECT * FROM data_sources"));
+        const savedSources = localStorage.getItem("git_store_data_sources");
+        const savedList = savedSources ? await withoutTokens(JSON.parse(savedSources)) : [];
+        if (savedSources) {
+          localStorage.setItem("git_store_data_sources", JSON.stringify(savedList));
+        }
+        if (sources.length === 0 && savedList.length > 0) {
           // LocalStorage fallback migration
-          const savedSources = localStorage.getItem("git_store_data_sources");
-          if (savedSources) {
-            const list = JSON.parse(savedSources) as DataSource[];
-            for (const ds of list) {
-              await db.execute(
-                `INSERT OR REPLACE INTO data_sources (id, name, apiEndpointMode, customEndpoint, apiVersion, token, addedAt, platform) 
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)`,
-                [ds.id, ds.name, ds.apiEndpointMode, ds.customEndpoint, ds.apiVersion, ds.token, ds.addedAt, ds.platform || "github"]
-              );
-            }
-            sources = list;
+          for (const ds of savedList) {
+            await db.execute(
+              `INSERT OR REPLACE INTO data_sources (id, name, apiEndpointMode, customEndpoint, apiVersion, token, addedAt, platform) 
+               VALUES ($1, $2, $3, $4, $5, '', $6, $7)`,
+              [ds.id, ds.name, ds.apiEndpointMode, ds.customEndpoint, ds.apiVersion, ds.addedAt, ds.platform || "github"]
+            );
           }
+          sources = savedList;
         }
 
         // Filter out any built-in sources from the database records
@@ -515,7 +724,8 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
         try {
           const savedSources = localStorage.getItem("git_store_data_sources");
           if (savedSources) {
-            const list = JSON.parse(savedSources) as DataSource[];
+            const list = await withoutTokens(JSON.parse(savedSources));
+            localStorage.setItem("git_store_data_sources", JSON.stringify(list));
             setCustomDataSources(list.filter(s => s.id !== "builtin_github" && s.id !== "builtin_gitee"));
           }
           const savedRepos = localStorage.getItem("git_store_installed_repos");
@@ -538,18 +748,29 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
 
     // Listen for progress events from Rust
     const unlistenPromise = listen("download-progress", (event: any) => {
-      const payload = event.payload as { repo_url: string; status: string; message: string };
-      console.log("Download progress:", payload);
-      
+      const payload = event.payload as {
+        repo_url: string;
+        status: string;
+        message: string;
+        downloaded: number;
+        total: number;
+        percent: number;
+      };
+
       setInstalledRepos((prev) =>
         prev.map((item) => {
           if (item.url === payload.repo_url) {
+            const statusChanged = item.status !== payload.status;
             const updated = {
               ...item,
               status: payload.status as any,
               message: payload.message,
+              downloaded: payload.downloaded,
+              total: payload.total,
+              percent: payload.percent,
             };
-            saveRepoToDb(updated); // Sync status change to SQL
+            // Progress ticks arrive often; only persist actual status changes
+            if (statusChanged) saveRepoToDb(updated);
             return updated;
           }
           return item;
@@ -559,34 +780,49 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
 
     // Listen for binary asset progress events
     const unlistenAssetPromise = listen("asset-download-progress", (event: any) => {
-      const payload = event.payload as { url: string; downloaded: number; total: number; percent: number; status: string };
+      const payload = event.payload as { url: string; filename: string; downloaded: number; total: number; percent: number; status: string; attempt: number };
       setAssetDownloads((prev) => ({
         ...prev,
         [payload.url]: {
           url: payload.url,
-          filename: payload.url.substring(payload.url.lastIndexOf('/') + 1) || "install_package",
+          filename: payload.filename || payload.url.substring(payload.url.lastIndexOf('/') + 1) || "install_package",
           downloaded: payload.downloaded,
           total: payload.total,
           percent: payload.percent,
           status: payload.status as any,
+          attempt: payload.attempt,
         }
       }));
     });
 
+    // Newer notices replace older ones for the same repository and kind
+    const unlistenUpdatesPromise = listen("updates-available", (event: any) => {
+      const notices = event.payload as UpdateNotice[];
+      setAvailableUpdates((prev) => [
+        ...notices,
+        ...prev.filter((p) => !notices.some((n) => n.kind === p.kind && n.url === p.url)),
+      ]);
+    });
+
     return () => {
       unlistenPromise.then((unlisten) => unlisten());
       unlistenAssetPromise.then((unlisten) => unlisten());
+      unlistenUpdatesPromise.then((unlisten) => unlisten());
     };
   }, []);
 
   const checkGit = async (): Promise<boolean> => {
     try {
-      const installed = await invoke<boolean>("check_git_installed");
-      setGitInstalled(installed);
-      return installed;
+      const support = await invoke<{ library: boolean; cli: boolean; lfs: boolean }>("check_git_support");
+      setGitInstalled(support.library);
+      setGitCliInstalled(support.cli);
+      setGitLfsInstalled(support.lfs);
+      return support.library;
     } catch (e) {
       console.error("Failed to check Git availability:", e);
       setGitInstalled(false);
+      setGitCliInstalled(false);
+      setGitLfsInstalled(false);
       return false;
     }
   };
@@ -608,7 +844,8 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
     stars: number,
     description: string,
     language: string,
-    customUrl?: string
+    customUrl?: string,
+    options?: CloneOptions
   ) => {
     const repoUrl = customUrl || `https://github.com/${owner}/${repo}`;
 
@@ -632,28 +869,6 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
       return [newRepo, ...prev];
     });
 
-    // Find matching source for authentication
-    const matchedSource = dataSources.find((s) => {
-      if (s.apiEndpointMode === "public") {
-        if (s.platform === "github" && repoUrl.includes("github.com/")) return true;
-        if (s.platform === "gitee" && repoUrl.includes("gitee.com/")) return true;
-      } else if (s.apiEndpointMode === "enterprise" && s.customEndpoint) {
-        try {
-          const urlObj = new URL(s.customEndpoint);
-          if (repoUrl.includes(urlObj.hostname)) return true;
-        } catch (e) {}
-      }
-      return false;
-    });
-
-    const isMatchedGitee = matchedSource ? matchedSource.platform === "gitee" : repoUrl.includes("gitee.com");
-    const tokenToUse = matchedSource 
-      ? matchedSource.token 
-      : (isMatchedGitee ? giteeToken : githubToken);
-
-    const activeGithubToken = !isMatchedGitee ? (tokenToUse || null) : (githubToken || null);
-    const activeGiteeToken = isMatchedGitee ? (tokenToUse || null) : (giteeToken || null);
-
     try {
       const folderName = `${owner}_${repo}`.replace(/[^a-zA-Z0-9_.-]/g, "_");
       
@@ -663,9 +878,8 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
           repoUrl,
           targetDir: downloadDir,
           folderName,
-          githubToken: activeGithubToken,
-          giteeToken: activeGiteeToken,
           useZip: !gitInstalled,
+          options,
         });
       } catch (firstErr) {
         console.warn("First clone attempt failed, trying fallback url...", firstErr);
@@ -694,9 +908,9 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
             repoUrl: backupUrl,
             targetDir: downloadDir,
             folderName,
-            githubToken: activeGithubToken,
-            giteeToken: activeGiteeToken,
             useZip: !gitInstalled,
+            options,
+            recordAs: repoUrl,
           });
         } else {
           throw firstErr;
@@ -726,7 +940,7 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
             const updated = {
               ...item,
               status: "failed" as const,
-              message: typeof err === "string" ? err : JSON.stringify(err),
+              message: typeof err === "string" ? err : err.message || JSON.stringify(err),
             };
             saveRepoToDb(updated); // Sync failed status to SQL
             return updated;
@@ -737,7 +951,7 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
     }
   };
 
-  const updateRepository = async (repoItem: InstalledRepo) => {
+  const updateRepository = async (repoItem: InstalledRepo, strategy?: UpdateStrategy) => {
     setInstalledRepos((prev) =>
       prev.map((item) => {
         if (item.url === repoItem.url) {
@@ -754,11 +968,9 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
     );
 
     try {
-      await invoke<string>("pull_repository", {
+      const message = await invoke<string>("pull_repository", {
         repoPath: repoItem.path,
-        repoUrl: repoItem.url,
-        githubToken: githubToken || null,
-        giteeToken: giteeToken || null,
+        strategy,
       });
 
       setInstalledRepos((prev) =>
@@ -767,7 +979,7 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
             const updated = {
               ...item,
               status: "completed" as const,
-              message: "更新已完成",
+              message: message || "更新已完成",
             };
             saveRepoToDb(updated); // Sync updated completed status to SQL
             return updated;
@@ -775,15 +987,29 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
           return item;
         })
       );
+      setAvailableUpdates((prev) =>
+        prev.filter((u) => !(u.kind === "repository" && u.url === repoItem.url))
+      );
     } catch (err: any) {
       console.error("Update failed:", err);
+      // Local changes block the update: let the user pick how to deal with them
+      if (err?.kind === "dirty_worktree") {
+        const files = (err.files as string[]).slice(0, 10).join("\n");
+        if (confirm(`${err.message}\n\n${files}\n\n确定：暂存本地修改后更新，完成后自动恢复\n取消：保留现状，不更新`)) {
+          return updateRepository(repoItem, "stash");
+        }
+      } else if (err?.kind === "diverged") {
+        if (confirm(`${err.message}\n\n确定：重置到远程版本（本地提交和修改可通过“回滚”恢复）\n取消：保留现状，不更新`)) {
+          return updateRepository(repoItem, "reset");
+        }
+      }
       setInstalledRepos((prev) =>
         prev.map((item) => {
           if (item.url === repoItem.url) {
             const updated = {
               ...item,
               status: "failed" as const,
-              message: typeof err === "string" ? err : JSON.stringify(err),
+              message: typeof err === "string" ? err : err?.message || JSON.stringify(err),
             };
             saveRepoToDb(updated); // Sync updated failed status to SQL
             return updated;
@@ -794,17 +1020,102 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
     }
   };
 
+  // Returns an installed repo to its state before the last update
+  const rollbackRepository = async (repoItem: InstalledRepo) => {
+    try {
+      const message = await invoke<string>("rollback_repository", { repoPath: repoItem.path });
+      setInstalledRepos((prev) =>
+        prev.map((item) => {
+          if (item.url === repoItem.url) {
+            const updated = { ...item, status: "completed" as const, message };
+            saveRepoToDb(updated);
+            return updated;
+          }
+          return item;
+        })
+      );
+    } catch (err: any) {
+      alert("回滚失败: " + (err?.message || err));
+    }
+  };
+
+  // Checks and updates every completed install the backend has recorded; per-repo
+  // status arrives via download-progress
+  const updateAllRepositories = () => invoke<UpdateSummary>("update_all_repositories");
+
+  // Compares an installed repo with its remote without touching the working tree
+  const checkRepositoryUpdates = (repoItem: InstalledRepo) =>
+    invoke<RepoUpdateStatus>("check_repository_updates", {
+      repoPath: repoItem.path,
+      repoUrl: repoItem.url,
+    });
+
+  const scanInstallations = () => invoke<InstallIssue[]>("scan_installations");
+
+  // Repairs change the backend's records, so the Library is reloaded from them afterwards
+  const repairInstallation = async (issue: InstallIssue, action: RepairAction) => {
+    const reload = async () => setInstalledRepos(await invoke<InstalledRepo[]>("list_installed"));
+    if (action === "reclone") {
+      // Show the row as cloning right away; progress events update it from here
+      await reload();
+      setInstalledRepos((prev) =>
+        prev.map((item) =>
+          item.url === issue.url ? { ...item, status: "cloning" as const, message: "正在重新克隆..." } : item
+        )
+      );
+    }
+    try {
+      return await invoke<string>("repair_installation", { path: issue.path, action });
+    } finally {
+      await reload().catch((err) => console.error("Failed to reload installed repositories:", err));
+    }
+  };
+
+  // Registers existing checkouts in (or below) `dir` as installed, leaving them in place
+  const importLocalRepositories = async (dir: string) => {
+    const summary = await invoke<ImportSummary>("import_local_repositories", { dir });
+    if (summary.imported.length > 0) {
+      setInstalledRepos(await invoke<InstalledRepo[]>("list_installed"));
+    }
+    return summary;
+  };
+
+  // Lists what uninstalling would remove, without touching anything
+  const previewUninstall = async (repoItem: InstalledRepo) => {
+    if (!repoItem.path) return null;
+    try {
+      return await invoke<UninstallReport>("uninstall_repository", {
+        repoPath: repoItem.path,
+        dryRun: true,
+      });
+    } catch (e) {
+      console.error("Failed to preview uninstall:", e);
+      return null;
+    }
+  };
+
   const uninstallRepository = async (repoItem: InstalledRepo) => {
+    const removeFromLibrary = () => {
+      setInstalledRepos((prev) => prev.filter((item) => item.url !== repoItem.url));
+      deleteRepoFromDb(repoItem.url); // Remove from SQL
+    };
     try {
       if (repoItem.path) {
-        await invoke("uninstall_repository", { repoPath: repoItem.path });
+        // Cancelled clones are always deleted; finished installs follow the setting
+        const toTrash =
+          repoItem.status === "completed" && localStorage.getItem("git_store_uninstall_to_trash") === "true";
+        await invoke<UninstallReport>("uninstall_repository", {
+          repoPath: repoItem.path,
+          mode: toTrash ? "trash" : "delete",
+        });
       }
-      
-      setInstalledRepos((prev) => prev.filter((item) => item.url !== repoItem.url));
-      deleteRepoFromDb(repoItem.url); // Remove from SQL
+      removeFromLibrary();
     } catch (e) {
       console.error("Failed to uninstall:", e);
-      alert("卸载失败: " + e);
+      // The backend refuses folders it did not install; the entry can still be dropped
+      if (confirm(`卸载失败: ${e}\n\n是否仅从应用库中移除该记录（不删除任何文件）？`)) {
+        removeFromLibrary();
+      }
     }
   };
 
@@ -841,6 +1152,9 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
   };
 
   const addDataSource = async (source: DataSource) => {
+    // Whether it has a token is looked up from the credential store, not saved
+    const { hasToken: _hasToken, enabled: _enabled, ...saved } = source;
+    source = saved;
     setCustomDataSources((prev) => {
       const filtered = prev.filter((s) => s.id !== source.id);
       const updated = [...filtered, source];
@@ -853,8 +1167,8 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
       try {
         await dbRef.current.execute(
           `INSERT OR REPLACE INTO data_sources (id, name, apiEndpointMode, customEndpoint, apiVersion, token, addedAt, platform) 
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)`,
-          [source.id, source.name, source.apiEndpointMode, source.customEndpoint, source.apiVersion, source.token, source.addedAt, source.platform || "github"]
+           VALUES ($1, $2, $3, $4, $5, '', $6, $7)`,
+          [source.id, source.name, source.apiEndpointMode, source.customEndpoint, source.apiVersion, source.addedAt, source.platform || "github"]
         );
       } catch (err) {
         console.error("Failed to add data source to SQLite database:", err);
@@ -888,16 +1202,25 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
         setSelectedRepo,
         searchQuery,
         setSearchQuery,
-        githubToken,
+        hasGithubToken,
         setGithubToken,
         downloadDir,
         setDownloadDir,
         gitInstalled,
+        gitCliInstalled,
+        gitLfsInstalled,
         checkGit,
         installedRepos,
         installRepository,
         updateRepository,
+        rollbackRepository,
+        updateAllRepositories,
+        checkRepositoryUpdates,
         uninstallRepository,
+        previewUninstall,
+        scanInstallations,
+        repairInstallation,
+        importLocalRepositories,
         openFolder,
         openVSCode,
         searchHistory,
@@ -908,6 +1231,7 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
         deleteDataSource,
         toggleDataSourceEnabled,
         assetDownloads,
+        availableUpdates,
         theme,
         setTheme,
         bgType,
@@ -924,8 +1248,9 @@ export function AppProvider({ children }: { children: React.ReactNode }) {
         setBgBlur,
         mobileSidebarOpen,
         setMobileSidebarOpen,
-        giteeToken,
+        hasGiteeToken,
         setGiteeToken,
+        setSourceToken,
         preferredPlatform,
         githubLatency,
         giteeLatency,
@@ -0,0 +1,62 @@
+/**
+ * 代码托管平台 API 请求
+ *
+ * 所有需要鉴权的 GitHub / Gitee(含自建实例)API 请求都经后端 `forge_api_request` 发出:
+ * 后端按主机从凭据库中取出令牌并附加,令牌不会进入 WebView。返回值模仿 fetch 的
+ * Response,便于替换原有的 fetch 调用。
+ */
+import { invoke } from "@tauri-apps/api/core";
+import type { DataSource } from "@/context/AppContext";
+
+interface ForgeApiResponse {
+  status: number;
+  /** 响应头,名称均为小写 */
+  headers: Record<string, string>;
+  body: string;
+}
+
+export interface ForgeFetchOptions {
+  headers?: Record<string, string>;
+  /** URL 不属于任何已注册平台时,按哪种平台的方式附加令牌 */
+  kind?: "github" | "gitee";
+  /** 仅用于测试用户刚输入、尚未保存的令牌 */
+  token?: string;
+}
+
+export interface ForgeFetchResponse {
+  ok: boolean;
+  status: number;
+  headers: { get: (name: string) => string | null };
+  json: () => Promise<any>;
+  text: () => Promise<string>;
+}
+
+export async function forgeFetch(url: string, options: ForgeFetchOptions = {}): Promise<ForgeFetchResponse> {
+  const res = await invoke<ForgeApiResponse>("forge_api_request", {
+    url,
+    headers: options.headers ?? {},
+    kind: options.kind ?? null,
+    token: options.token?.trim() || null,
+  });
+  return {
+    ok: res.status >= 200 && res.status < 300,
+    status: res.status,
+    headers: { get: (name) => res.headers[name.toLowerCase()] ?? null },
+    json: async () => JSON.parse(res.body),
+    text: async () => res.body,
+  };
+}
+
+/** 数据源令牌在后端凭据库中对应的主机:公网 API 为 github.com / gitee.com,自建实例为其 API 地址的主机 */
+export function sourceCredentialHost(
+  source: Pick<DataSource, "platform" | "apiEndpointMode" | "customEndpoint">
+): string | null {
+  if (source.apiEndpointMode !== "enterprise") {
+    return source.platform === "gitee" ? "gitee.com" : "github.com";
+  }
+  try {
+    return new URL(source.customEndpoint.trim()).host.toLowerCase();
+  } catch {
+    return null;
+  }
+}
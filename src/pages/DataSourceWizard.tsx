@@ -2,6 +2,7 @@ import { useState, useEffect } from "react";
 import { useApp, DataSource } from "@/context/AppContext";
 import { CheckCircle2, Terminal, AlertTriangle, Key, Globe, Cpu, Plus, Trash, ArrowLeft, Edit, Package, Archive, GitFork } from "lucide-react";
 import { cn } from "@/lib/utils";
+import { forgeFetch, sourceCredentialHost } from "@/lib/forgeApi";
 import { open } from "@tauri-apps/plugin-dialog";
 
 // Custom SVG GitHub Icon
@@ -41,7 +42,7 @@ function GiteeIcon(props: React.SVGProps<SVGSVGElement>) {
 
 
 export default function DataSourceWizard() {
-  const { githubToken, setGithubToken, giteeToken, setGiteeToken, dataSources, addDataSource, deleteDataSource, toggleDataSourceEnabled } = useApp();
+  const { setSourceToken, dataSources, addDataSource, deleteDataSource, toggleDataSourceEnabled } = useApp();
 
   // Navigation state: 'list' | 'add' | 'edit'
   const [view, setView] = useState<"list" | "add" | "edit">("list");
@@ -93,10 +94,7 @@ export default function DataSourceWizard() {
   const [listTestingId, setListTestingId] = useState<string | null>(null);
   const [listTestResults, setListTestResults] = useState<Record<string, "success" | "error" | "idle">>({});
 
-  // Sync token if context updates
-  useEffect(() => {
-    setTokenInput(githubToken);
-  }, [githubToken]);
+  const editingSource = editingSourceId ? dataSources.find((s) => s.id === editingSourceId) : undefined;
 
   // If public source is already added, auto-select enterprise mode
   useEffect(() => {
@@ -138,7 +136,8 @@ export default function DataSourceWizard() {
     setApiEndpointMode(source.apiEndpointMode);
     setCustomEndpoint(source.customEndpoint);
     setApiVersion(source.apiVersion);
-    setTokenInput(source.token);
+    // Saved tokens never leave the backend; an empty input keeps the stored one
+    setTokenInput("");
 
     if (source.platform === "zip") {
       setZipPath(source.customEndpoint);
@@ -176,11 +175,15 @@ export default function DataSourceWizard() {
         apiEndpointMode,
         customEndpoint: resolvedEndpoint,
         apiVersion: selectedType === "gitee" ? "v5" : apiVersion,
-        token: tokenInput.trim(),
         addedAt: editingSourceAddedAt || new Date().toLocaleDateString()
       };
 
       addDataSource(updatedSource);
+      if (tokenInput.trim()) {
+        setSourceToken(updatedSource, tokenInput).catch((err) =>
+          console.error("Failed to store data source token:", err)
+        );
+      }
       setView("list");
       setEditingSourceId(null);
     }
@@ -204,21 +207,16 @@ export default function DataSourceWizard() {
           ? zipPath.trim()
           : (selectedType === "git_link" ? gitUrl.trim() : customEndpoint.trim()),
         apiVersion: selectedType === "gitee" ? "v5" : apiVersion,
-        token: tokenInput.trim(),
         addedAt: new Date().toLocaleDateString()
       };
 
       addDataSource(newSource);
 
-      // Set as active Token in global context
+      // The token goes to the backend credential store under the source's host
       if (tokenInput.trim()) {
-        if (selectedType === "gitee") {
-          setGiteeToken(tokenInput.trim());
-          localStorage.setItem("git_store_gitee_token", tokenInput.trim());
-        } else {
-          setGithubToken(tokenInput.trim());
-          localStorage.setItem("git_store_token", tokenInput.trim());
-        }
+        setSourceToken(newSource, tokenInput).catch((err) =>
+          console.error("Failed to store data source token:", err)
+        );
       }
 
       setStep(3);
@@ -322,7 +320,8 @@ export default function DataSourceWizard() {
       ? (apiEndpointMode === "public" ? "https://gitee.com/api/v5" : customEndpoint.trim())
       : (apiEndpointMode === "public" ? "https://api.github.com" : customEndpoint.trim());
 
-    const hasToken = tokenInput.trim() !== "";
+    // While editing, an empty input means the source's stored token is used
+    const hasToken = tokenInput.trim() !== "" || (view === "edit" && !!editingSource?.hasToken);
     const requestUrl = hasToken
       ? `${baseEndpoint}/user`
       : (isGitee ? "https://gitee.com/api/v5/repos/chunge16/git-app-store/subscribers" : "https://api.github.com");
@@ -335,14 +334,11 @@ export default function DataSourceWizard() {
         headers["X-GitHub-Api-Version"] = apiVersion;
       }
 
-      let finalRequestUrl = requestUrl;
-      if (isGitee && hasToken) {
-        finalRequestUrl += `${requestUrl.includes('?') ? '&' : '?'}access_token=${encodeURIComponent(tokenInput.trim())}`;
-      } else if (hasToken) {
-        headers.Authorization = `Bearer ${tokenInput.trim()}`;
-      }
-
-      const res = await fetch(finalRequestUrl, { headers });
+      const res = await forgeFetch(requestUrl, {
+        headers,
+        kind: isGitee ? "gitee" : "github",
+        token: tokenInput,
+      });
 
       if (!isGitee) {
         const limitHeader = res.headers.get("x-ratelimit-limit");
@@ -423,7 +419,7 @@ export default function DataSourceWizard() {
       ? (source.apiEndpointMode === "public" ? "https://gitee.com/api/v5" : source.customEndpoint.trim())
       : (source.apiEndpointMode === "public" ? "https://api.github.com" : source.customEndpoint.trim());
 
-    const hasToken = source.token && source.token.trim() !== "";
+    const hasToken = !!source.hasToken;
     const requestUrl = hasToken
       ? `${baseEndpoint}/user`
       : (isGitee ? "https://gitee.com/api/v5/repos/chunge16/git-app-store/subscribers" : "https://api.github.com");
@@ -436,14 +432,8 @@ export default function DataSourceWizard() {
         headers["X-GitHub-Api-Version"] = source.apiVersion;
       }
 
-      let finalRequestUrl = requestUrl;
-      if (isGitee && hasToken) {
-        finalRequestUrl += `${requestUrl.includes('?') ? '&' : '?'}access_token=${encodeURIComponent(source.token.trim())}`;
-      } else if (hasToken) {
-        headers.Authorization = `Bearer ${source.token.trim()}`;
-      }
-
-      const res = await fetch(finalRequestUrl, { headers });
+      // The backend attaches the stored token for the source's host
+      const res = await forgeFetch(requestUrl, { headers, kind: isGitee ? "gitee" : "github" });
       const isReachable = res.ok || res.status === 401 || res.status === 403;
       if (isReachable) {
         setListTestResults((prev) => ({ ...prev, [source.id]: "success" }));
@@ -470,15 +460,16 @@ export default function DataSourceWizard() {
       const deleted = dataSources.find((s) => s.id === id);
       deleteDataSource(id);
 
-      if (deleted) {
-        if (deleted.platform === "gitee" && deleted.token === giteeToken) {
-          setGiteeToken("");
-          localStorage.removeItem("git_store_gitee_token");
+      // Tokens are stored per host, so only drop one no remaining source uses
+      const host = deleted?.hasToken ? sourceCredentialHost(deleted) : null;
+      if (deleted && host && !dataSources.some((s) => s.id !== id && sourceCredentialHost(s) === host)) {
+        setSourceToken(deleted, "").catch((err) =>
+          console.error("Failed to remove data source token:", err)
+        );
+        if (deleted.platform === "gitee") {
           localStorage.removeItem("git_store_gitee_api_mode");
           localStorage.removeItem("git_store_gitee_custom_endpoint");
-        } else if (deleted.platform !== "gitee" && deleted.token === githubToken) {
-          setGithubToken("");
-          localStorage.removeItem("git_store_token");
+        } else {
           localStorage.removeItem("git_store_api_mode");
           localStorage.removeItem("git_store_custom_endpoint");
           localStorage.removeItem("git_store_api_version");
@@ -496,6 +487,7 @@ export default function DataSourceWizard() {
   const handleFinish = () => {
     setView("list");
     setStep(1);
+    setTokenInput("");
     setSelectedType(null);
     setTestResult("idle");
     setGithubUser(null);
@@ -602,8 +594,8 @@ export default function DataSourceWizard() {
                       {source.platform !== "zip" && source.platform !== "git_link" && (
                         <p className="text-[10px] text-[var(--fluent-secondary)] mt-0.5">
                           密钥: <span className="font-mono text-zinc-500">
-                            {source.token
-                              ? `${source.platform === "gitee" ? "access" : "ghp"}_••••••••${source.token.slice(-4)}`
+                            {source.hasToken
+                              ? "已配置令牌 (保存在系统凭据库)"
                               : "匿名访问 (无令牌 - 存在频次限制)"}
                           </span>
                         </p>
@@ -1366,6 +1358,7 @@ export default function DataSourceWizard() {
   // Rendering view: Edit flow
   const renderEditView = () => {
     const isGitee = selectedType === "gitee";
+    const tokenPlaceholder = editingSource?.hasToken ? "已配置令牌，留空则保持不变" : null;
     return (
       <div className="flex-1 flex flex-col">
         {/* Header Navigation */}
@@ -1489,7 +1482,7 @@ export default function DataSourceWizard() {
                   type="password"
                   value={tokenInput}
                   onChange={(e) => setTokenInput(e.target.value)}
-                  placeholder="私有仓库的个人访问令牌 (PAT / Access Token)"
+                  placeholder={tokenPlaceholder ?? "私有仓库的个人访问令牌 (PAT / Access Token)"}
                   className="w-full text-xs font-mono bg-[var(--fluent-card)] border border-[var(--fluent-border)] rounded-lg px-3 py-2 text-white focus:outline-none focus:border-[var(--fluent-accent)]"
                 />
               </div>
@@ -1593,7 +1586,7 @@ export default function DataSourceWizard() {
                     type="password"
                     value={tokenInput}
                     onChange={(e) => setTokenInput(e.target.value)}
-                    placeholder={isGitee ? "请输入 Gitee Access Token..." : "请输入 GitHub Personal Access Token (classic 或 fine-grained)..."}
+                    placeholder={tokenPlaceholder ?? (isGitee ? "请输入 Gitee Access Token..." : "请输入 GitHub Personal Access Token (classic 或 fine-grained)...")}
                     className="w-full text-xs font-mono bg-[var(--fluent-card)] border border-[var(--fluent-border)] rounded-lg pl-9 pr-3 py-2 text-white focus:outline-none focus:border-[var(--fluent-accent)]"
                   />
                   <Key className="w-4 h-4 text-zinc-500 absolute left-3 top-2.5" />
@@ -1,7 +1,8 @@
 import { useState, useEffect } from "react";
 import { useApp } from "@/context/AppContext";
-import { Star, ChevronLeft, Calendar, FileText, Code2, Cpu, Terminal, FolderOpen, XCircle, RefreshCw, Trash2, ArrowUpRight, AlertTriangle, Package } from "lucide-react";
+import { Star, ChevronLeft, Calendar, FileText, Code2, Cpu, Terminal, FolderOpen, XCircle, RefreshCw, Undo2, Trash2, ArrowUpRight, AlertTriangle, Package } from "lucide-react";
 import { cn } from "@/lib/utils";
+import { forgeFetch } from "@/lib/forgeApi";
 import AppIcon from "@/components/AppIcon";
 import ReactMarkdown from "react-markdown";
 import remarkGfm from "remark-gfm";
@@ -15,12 +16,12 @@ export default function Detail() {
     installRepository,
     installedRepos,
     updateRepository,
+    rollbackRepository,
     uninstallRepository,
     openFolder,
     openVSCode,
-    githubToken,
-    giteeToken,
     gitInstalled,
+    gitLfsInstalled,
     assetDownloads,
     dataSources
   } = useApp();
@@ -53,7 +54,9 @@ export default function Detail() {
       const { invoke } = await import("@tauri-apps/api/core");
       const path = await invoke<string>("download_release_asset", {
         url: matchedAsset.browser_download_url,
-        filename: matchedAsset.name
+        filename: matchedAsset.name,
+        // GitHub reports release digests as "sha256:<hex>"; gateway sources use a bare sha256
+        expectedHash: matchedAsset.digest || matchedAsset.sha256 || null,
       });
       setDownloadedAssetPath(path);
 
@@ -83,6 +86,33 @@ export default function Detail() {
        repoInfo.platform === "openstore_api" || 
        (!repoInfo.url || (!repoInfo.url.includes("github.com") && !repoInfo.url.includes("gitee.com"))))
     : false;
+  // 子模块 / LFS 检测：Git 克隆时由用户勾选启用（默认关闭），ZIP 模式下提示源码可能不完整
+  const [repoFeatures, setRepoFeatures] = useState<{ submodules: boolean; lfs: boolean } | null>(null);
+  const [withSubmodules, setWithSubmodules] = useState(false);
+  const [withLfs, setWithLfs] = useState(false);
+
+  useEffect(() => {
+    setRepoFeatures(null);
+    setWithSubmodules(false);
+    setWithLfs(false);
+    if (!repoInfo?.url || isClosedSource) return;
+    let cancelled = false;
+    import("@tauri-apps/api/core")
+      .then(({ invoke }) => invoke<{ submodules: boolean; lfs: boolean }>("inspect_repository", { repoUrl: repoInfo.url }))
+      .then((features) => {
+        if (!cancelled) setRepoFeatures(features);
+      })
+      .catch((err) => console.warn("Failed to inspect repository:", err));
+    return () => {
+      cancelled = true;
+    };
+  }, [repoInfo?.url]);
+
+  const lfs = withLfs && gitLfsInstalled;
+  const cloneOptions =
+    withSubmodules || lfs ? { submodules: withSubmodules, lfs } : undefined;
+  const zipIncomplete = !gitInstalled && !!repoFeatures && (repoFeatures.submodules || repoFeatures.lfs);
+
   const repoSources = repoInfo?.sources || [];
   const [activeSourceId, setActiveSourceId] = useState<string | null>(null);
 
@@ -156,11 +186,7 @@ export default function Detail() {
           : matchedSource.customEndpoint.trim())
       : (isMatchedGitee ? "https://gitee.com/api/v5" : "https://api.github.com");
 
-    const token = matchedSource
-      ? (matchedSource.token || "")
-      : (isMatchedGitee 
-          ? (localStorage.getItem("git_store_gitee_token") || giteeToken || "")
-          : (localStorage.getItem("git_store_token") || githubToken || ""));
+    const kind = isMatchedGitee ? "gitee" : "github";
 
     const headers: Record<string, string> = {
       Accept: isMatchedGitee ? "application/json" : "application/vnd.github.v3+json",
@@ -169,18 +195,11 @@ export default function Detail() {
       const version = matchedSource?.apiVersion || "2026-03-10";
       headers["X-GitHub-Api-Version"] = version;
     }
-    if (token.trim() && !isMatchedGitee) {
-      headers.Authorization = `Bearer ${token.trim()}`;
-    }
 
     try {
       // 1. Fetch README
-      let readmeApi = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}/readme`;
-      if (isMatchedGitee && token.trim()) {
-        readmeApi += `?access_token=${encodeURIComponent(token.trim())}`;
-      }
-
-      const readmeRes = await fetch(readmeApi, { headers });
+      const readmeApi = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}/readme`;
+      const readmeRes = await forgeFetch(readmeApi, { headers, kind });
       if (readmeRes.ok) {
         const data = await readmeRes.json();
         // Decode base64 safely supporting utf-8 characters
@@ -197,12 +216,8 @@ export default function Detail() {
       }
 
       // 2. Fetch Detailed Repo Info
-      let detailsApi = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}`;
-      if (isMatchedGitee && token.trim()) {
-        detailsApi += `?access_token=${encodeURIComponent(token.trim())}`;
-      }
-
-      const detailsRes = await fetch(detailsApi, { headers });
+      const detailsApi = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}`;
+      const detailsRes = await forgeFetch(detailsApi, { headers, kind });
       if (detailsRes.ok) {
         const data = await detailsRes.json();
         setExtraInfo(data);
@@ -213,11 +228,8 @@ export default function Detail() {
         let releaseData = null;
         if (isMatchedGitee) {
           // Gitee releases API endpoint list, pick first one as latest
-          let releaseUrl = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}/releases?per_page=1`;
-          if (token.trim()) {
-            releaseUrl += `&access_token=${encodeURIComponent(token.trim())}`;
-          }
-          const releaseRes = await fetch(releaseUrl, { headers });
+          const releaseUrl = `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}/releases?per_page=1`;
+          const releaseRes = await forgeFetch(releaseUrl, { headers, kind });
           if (releaseRes.ok) {
             const list = await releaseRes.json();
             if (Array.isArray(list) && list.length > 0) {
@@ -226,9 +238,9 @@ export default function Detail() {
           }
         } else {
           // GitHub latest release endpoint
-          const releaseRes = await fetch(
+          const releaseRes = await forgeFetch(
             `${apiBase}/repos/${repoInfo.owner}/${repoInfo.repo}/releases/latest`,
-            { headers }
+            { headers, kind }
           );
           if (releaseRes.ok) {
             releaseData = await releaseRes.json();
@@ -360,7 +372,7 @@ export default function Detail() {
     const lang = (repoInfo.language || "").toLowerCase();
     const reqs = [
       { name: "操作系统", value: "Windows 10/11 x64, macOS 10.15+, or Linux" },
-      { name: "Git CLI 状态", value: gitInstalled ? "已安装就绪" : "未就绪 ( ZIP 模式克隆 )" },
+      { name: "Git 状态", value: gitInstalled ? "已安装就绪" : "未就绪 ( ZIP 模式克隆 )" },
     ];
 
     if (lang === "rust") {
@@ -541,6 +553,46 @@ export default function Detail() {
               )
             )}
             {!isClosedSource && !installedItem ? (
+              <>
+              {zipIncomplete && (
+                <span className="text-[10px] text-yellow-500 font-bold bg-yellow-500/10 border border-yellow-500/20 px-3 py-1.5 rounded-lg flex items-center gap-1">
+                  <AlertTriangle className="w-3.5 h-3.5" />
+                  <span>
+                    该仓库使用了{[repoFeatures?.submodules && "子模块", repoFeatures?.lfs && "Git LFS"].filter(Boolean).join("和")}，
+                    未安装 Git 时 ZIP 方式下载的源码将不完整
+                  </span>
+                </span>
+              )}
+              {gitInstalled && (repoFeatures?.submodules || repoFeatures?.lfs) && (
+                <div className="flex items-center gap-3 text-[10px] text-[var(--fluent-secondary)] font-bold">
+                  {repoFeatures.submodules && (
+                    <label className="flex items-center gap-1 cursor-pointer select-none">
+                      <input
+                        type="checkbox"
+                        checked={withSubmodules}
+                        onChange={(e) => setWithSubmodules(e.target.checked)}
+                        className="accent-[var(--fluent-accent)]"
+                      />
+                      <span>同时拉取子模块</span>
+                    </label>
+                  )}
+                  {repoFeatures.lfs && (
+                    <label
+                      className={cn("flex items-center gap-1 select-none", gitLfsInstalled ? "cursor-pointer" : "opacity-50 cursor-not-allowed")}
+                      title={gitLfsInstalled ? "使用 git-lfs 拉取 LFS 文件" : "未检测到 git-lfs，无法拉取 LFS 文件"}
+                    >
+                      <input
+                        type="checkbox"
+                        checked={lfs}
+                        disabled={!gitLfsInstalled}
+                        onChange={(e) => setWithLfs(e.target.checked)}
+                        className="accent-[var(--fluent-accent)]"
+                      />
+                      <span>同时拉取 Git LFS 文件</span>
+                    </label>
+                  )}
+                </div>
+              )}
               <button
                 onClick={() => {
                   installRepository(
@@ -549,7 +601,8 @@ export default function Detail() {
                     repoInfo.stars,
                     repoInfo.description,
                     repoInfo.language,
-                    repoInfo.url
+                    repoInfo.url,
+                    cloneOptions
                   );
                 }}
                 className={cn(
@@ -560,6 +613,7 @@ export default function Detail() {
                 <Package className="w-3.5 h-3.5" />
                 <span>下载项目源码</span>
               </button>
+              </>
             ) : installedItem ? (
               <div className="flex flex-col sm:flex-row items-stretch sm:items-center gap-3">
                 {/* Downloading diagnostics */}
@@ -599,7 +653,8 @@ export default function Detail() {
                           repoInfo.stars,
                           repoInfo.description,
                           repoInfo.language,
-                          repoInfo.url
+                          repoInfo.url,
+                          cloneOptions
                         )
                       }
                       className="bg-zinc-800 hover:bg-zinc-700 text-white text-xs font-bold px-4 py-2 rounded-lg border border-[var(--fluent-border)] active:scale-95"
@@ -639,7 +694,22 @@ export default function Detail() {
                       </button>
                       <button
                         onClick={() => {
-                          if (confirm(`确定要彻底卸载并删除 ${repoInfo.title} 仓库吗？\n此操作将清空本地目录！`)) {
+                          if (confirm(`确定要将 ${repoInfo.title} 回滚到上次更新前的版本吗？`)) {
+                            rollbackRepository(installedItem);
+                          }
+                        }}
+                        className="p-2 hover:bg-[rgba(128,128,128,0.15)] rounded-lg text-amber-400 transition"
+                        title="回滚到更新前版本"
+                      >
+                        <Undo2 className="w-4 h-4" />
+                      </button>
+                      <button
+                        onClick={() => {
+                          const msg =
+                            localStorage.getItem("git_store_uninstall_to_trash") === "true"
+                              ? `确定要卸载 ${repoInfo.title} 仓库吗？\n本地目录将被移至回收站。`
+                              : `确定要彻底卸载并删除 ${repoInfo.title} 仓库吗？\n此操作将清空本地目录！`;
+                          if (confirm(msg)) {
                             uninstallRepository(installedItem);
                           }
                         }}
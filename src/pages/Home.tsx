@@ -1,6 +1,7 @@
 import { useState, useEffect } from "react";
 import { useApp } from "@/context/AppContext";
 import { fetchAndAdaptUrlSource } from "@/lib/urlSourcesAdapter";
+import { forgeFetch } from "@/lib/forgeApi";
 import { loadAppsFromZipSourceWithMeta } from "@/lib/zipSourceLoader";
 import AppIcon from "@/components/AppIcon";
 import { BorderBeam } from "@/components/magicui/border-beam";
@@ -27,7 +28,7 @@ interface GitHubAppItem {
 }
 
 export default function Home() {
-  const { setSelectedRepo, setActiveTab, installRepository, installedRepos, activeTab, githubToken, giteeToken, preferredPlatform, dataSources, urlSources } = useApp();
+  const { setSelectedRepo, setActiveTab, installRepository, installedRepos, activeTab, hasGithubToken, hasGiteeToken, preferredPlatform, dataSources, urlSources } = useApp();
   
   const [apps, setApps] = useState<GitHubAppItem[]>([]);
   const [loading, setLoading] = useState(true);
@@ -87,7 +88,6 @@ export default function Home() {
           }
         }
 
-        const token = source.token || "";
         const mode = source.apiEndpointMode;
         
         let base = "";
@@ -158,22 +158,17 @@ export default function Home() {
           headers["Accept"] = "application/json";
           const query = "tauri";
           fetchUrl = `${base}/search/repositories?q=${encodeURIComponent(query)}&order=desc&per_page=50`;
-          if (token.trim()) {
-            fetchUrl += `&access_token=${encodeURIComponent(token.trim())}`;
-          }
         } else {
           base = mode === "public" ? "https://api.github.com" : source.customEndpoint.trim() || "https://api.github.com";
           headers["Accept"] = "application/vnd.github.v3+json";
           headers["X-GitHub-Api-Version"] = source.apiVersion || "2026-03-10";
-          if (token.trim()) {
-            headers["Authorization"] = `Bearer ${token.trim()}`;
-          }
           const query = "stars:>1000 tauri OR electron OR rust-gui OR desktop-app";
           fetchUrl = `${base}/search/repositories?q=${encodeURIComponent(query)}&sort=stars&order=desc&per_page=50`;
         }
 
         try {
-          const res = await fetch(fetchUrl, { headers });
+          // The backend adds the source's stored token
+          const res = await forgeFetch(fetchUrl, { headers, kind: isGitee ? "gitee" : "github" });
           if (!res.ok) {
             console.warn(`Failed to fetch from data source ${source.name}: ${res.status}`);
             return [];
@@ -289,7 +284,7 @@ export default function Home() {
 
   useEffect(() => {
     fetchStoreData();
-  }, [githubToken, giteeToken, preferredPlatform, dataSources, urlSources]);
+  }, [hasGithubToken, hasGiteeToken, preferredPlatform, dataSources, urlSources]);
 
   // Derived sections
   const getCarouselItems = () => {
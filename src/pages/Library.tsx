@@ -1,5 +1,6 @@
 import { useState, MouseEvent } from "react";
-import { useApp, InstalledRepo } from "@/context/AppContext";
+import { open } from "@tauri-apps/plugin-dialog";
+import { useApp, InstalledRepo, InstallIssue, RepairAction, DownloadHistoryEntry } from "@/context/AppContext";
 import {
   FolderOpen,
   Code2,
@@ -13,29 +14,60 @@ import {
   ArrowDownToLine,
   ChevronRight,
   RotateCcw,
+  Undo2,
   HardDrive,
   GitBranch,
   Clock,
   CheckCircle2,
   Play,
+  Pause,
+  ShieldCheck,
+  Wrench,
+  FolderInput,
 } from "lucide-react";
 import AppIcon from "@/components/AppIcon";
 
 type LibraryView = "library" | "downloads";
 
+const ISSUE_LABELS: Record<InstallIssue["kind"], string> = {
+  missing_folder: "目录丢失",
+  broken_git: "仓库损坏",
+  incomplete: "安装不完整",
+  detached_head: "HEAD 游离",
+  unknown_folder: "未记录",
+};
+
+const ACTION_LABELS: Record<RepairAction, string> = {
+  adopt: "保留",
+  reclone: "重新克隆",
+  forget: "移除记录",
+};
+
 export default function Library() {
   const {
     installedRepos,
     openFolder,
     openVSCode,
     updateRepository,
+    rollbackRepository,
+    updateAllRepositories,
     uninstallRepository,
+    previewUninstall,
+    scanInstallations,
+    repairInstallation,
+    importLocalRepositories,
     setActiveTab,
     setSelectedRepo,
     assetDownloads,
+    availableUpdates,
   } = useApp();
 
   const [view, setView] = useState<LibraryView>("library");
+  const [updatingAll, setUpdatingAll] = useState(false);
+  const [issues, setIssues] = useState<InstallIssue[] | null>(null);
+  const [scanning, setScanning] = useState(false);
+  const [repairing, setRepairing] = useState<string | null>(null);
+  const [importing, setImporting] = useState(false);
 
   // ── Data partitions ─────────────────────────────────────
   // Active in-progress tasks (downloads / clones / pulls)
@@ -47,7 +79,12 @@ export default function Library() {
   );
 
   const downloadingAssets = Object.values(assetDownloads).filter(
-    (a) => a.status === "downloading"
+    (a) =>
+      a.status === "queued" ||
+      a.status === "downloading" ||
+      a.status === "paused" ||
+      a.status === "retrying" ||
+      a.status === "verifying"
   );
 
   const totalActiveDownloads = downloadingRepos.length + downloadingAssets.length;
@@ -69,6 +106,19 @@ export default function Library() {
   const needsAttentionRepos = failedRepos;
 
   // ── Helpers ─────────────────────────────────────────────
+  // The backend refuses to run an installer that no longer matches the digest recorded
+  // when it was downloaded
+  const launchInstaller = async (filename: string) => {
+    const { invoke } = await import("@tauri-apps/api/core");
+    try {
+      const history = await invoke<DownloadHistoryEntry[]>("get_download_history");
+      const entry = history.find((e) => e.status === "completed" && e.filename === filename && e.hash);
+      await invoke("run_installer", { filename, expectedHash: entry?.hash ?? null });
+    } catch (err: any) {
+      alert(`无法启动安装包：${typeof err === "string" ? err : err.message}`);
+    }
+  };
+
   const formatSize = (bytes: number) => {
     if (!bytes || bytes === 0) return "0 B";
     const k = 1024;
@@ -82,15 +132,92 @@ export default function Library() {
     await updateRepository(repo);
   };
 
+  const handleUpdateAll = async () => {
+    setUpdatingAll(true);
+    try {
+      const summary = await updateAllRepositories();
+      const lines = [
+        `已更新: ${summary.updated.length} 个`,
+        `已是最新: ${summary.up_to_date.length} 个`,
+        `失败: ${summary.failed.length} 个`,
+        ...summary.failed.map((f) => `  · ${f.url.split("/").slice(-2).join("/")}: ${f.message}`),
+      ];
+      alert("批量更新完成\n\n" + lines.join("\n"));
+    } catch (err: any) {
+      alert("批量更新失败: " + (err?.message || err));
+    } finally {
+      setUpdatingAll(false);
+    }
+  };
+
+  const handleRollback = async (e: MouseEvent, repo: InstalledRepo) => {
+    e.stopPropagation();
+    if (confirm(`确定要将 ${repo.repo} 回滚到上次更新前的版本吗？`)) await rollbackRepository(repo);
+  };
+
   const handleUninstall = async (e: MouseEvent, repo: InstalledRepo) => {
     e.stopPropagation();
-    const msg =
-      repo.status === "completed"
-        ? `确定要彻底删除本地仓库 ${repo.repo} 吗？\n警告：此操作不可恢复，本地修改将全部丢失！`
-        : `确定要取消对 ${repo.repo} 仓库的克隆任务吗？`;
+    if (repo.status !== "completed") {
+      if (confirm(`确定要取消对 ${repo.repo} 仓库的克隆任务吗？`)) await uninstallRepository(repo);
+      return;
+    }
+    const toTrash = localStorage.getItem("git_store_uninstall_to_trash") === "true";
+    const report = await previewUninstall(repo);
+    const details = report
+      ? `\n\n将${toTrash ? "移至回收站" : "删除"}以下目录（${report.files} 个文件，${formatSize(report.bytes)}）：\n${report.targets.join("\n")}`
+      : "";
+    const msg = toTrash
+      ? `确定要卸载本地仓库 ${repo.repo} 吗？${details}`
+      : `确定要彻底删除本地仓库 ${repo.repo} 吗？\n警告：此操作不可恢复，本地修改将全部丢失！${details}`;
     if (confirm(msg)) await uninstallRepository(repo);
   };
 
+  const handleScan = async () => {
+    setScanning(true);
+    try {
+      setIssues(await scanInstallations());
+    } catch (err) {
+      alert("检查失败: " + err);
+    } finally {
+      setScanning(false);
+    }
+  };
+
+  const handleRepair = async (issue: InstallIssue, action: RepairAction) => {
+    if (action === "reclone" && !confirm(`将删除 ${issue.path} 中现有的内容并重新克隆，确定继续吗？`)) return;
+    setRepairing(issue.path);
+    try {
+      await repairInstallation(issue, action);
+      setIssues((prev) => prev?.filter((item) => item.path !== issue.path) ?? null);
+    } catch (err: any) {
+      alert("修复失败: " + (err?.message || err));
+    } finally {
+      setRepairing(null);
+    }
+  };
+
+  const handleImport = async () => {
+    const dir = await open({ directory: true, title: "选择本地仓库或包含多个仓库的文件夹" });
+    if (typeof dir !== "string") return;
+    setImporting(true);
+    try {
+      const summary = await importLocalRepositories(dir);
+      const skipped = summary.skipped.map((item) => `${item.path}: ${item.reason}`).join("\n");
+      if (summary.imported.length === 0 && summary.skipped.length === 0) {
+        alert("未在该文件夹中找到 Git 仓库。");
+      } else {
+        alert(
+          `已导入 ${summary.imported.length} 个仓库。` +
+            (skipped ? `\n\n跳过 ${summary.skipped.length} 个：\n${skipped}` : "")
+        );
+      }
+    } catch (err) {
+      alert("导入失败: " + err);
+    } finally {
+      setImporting(false);
+    }
+  };
+
   const handleOpenFolder = (e: MouseEvent, path: string) => {
     e.stopPropagation();
     openFolder(path);
@@ -175,6 +302,30 @@ export default function Library() {
             </div>
           )}
 
+          {view === "library" && (
+            <button
+              onClick={handleImport}
+              disabled={importing}
+              className="flex items-center gap-2 text-xs font-bold px-4 py-2 rounded-xl border transition cursor-pointer active:scale-95 shadow-sm bg-[var(--fluent-card)] text-[var(--fluent-text)] border-[var(--fluent-border)] hover:border-[var(--fluent-accent)]/40 disabled:opacity-50 disabled:cursor-not-allowed"
+              title="将已在本地克隆的 Git 仓库加入我的库，无需重新下载"
+            >
+              <FolderInput className="w-3.5 h-3.5" />
+              {importing ? "正在导入..." : "导入本地仓库"}
+            </button>
+          )}
+
+          {view === "library" && (
+            <button
+              onClick={handleScan}
+              disabled={scanning}
+              className="flex items-center gap-2 text-xs font-bold px-4 py-2 rounded-xl border transition cursor-pointer active:scale-95 shadow-sm bg-[var(--fluent-card)] text-[var(--fluent-text)] border-[var(--fluent-border)] hover:border-[var(--fluent-accent)]/40 disabled:opacity-50 disabled:cursor-not-allowed"
+              title="检查已安装项目的目录和 Git 仓库是否完好"
+            >
+              <ShieldCheck className={`w-3.5 h-3.5 ${scanning ? "animate-pulse" : ""}`} />
+              {scanning ? "正在检查..." : "检查完整性"}
+            </button>
+          )}
+
           {/* Download queue entry button */}
           <button
             onClick={() => setView(view === "library" ? "downloads" : "library")}
@@ -227,6 +378,68 @@ export default function Library() {
             </div>
           )}
 
+          {/* ── Integrity scan results (完整性检查) ─── */}
+          {issues !== null && (
+            <div className="text-left flex flex-col">
+              <SectionHeader
+                icon={<ShieldCheck className="w-4 h-4 text-amber-400" />}
+                title="完整性检查"
+                count={issues.length}
+                subtitle={issues.length > 0 ? "以下安装与磁盘上的内容不一致" : "未发现问题，所有安装均完好"}
+                countColor="text-amber-400"
+                action={
+                  <button
+                    onClick={() => setIssues(null)}
+                    className="text-[10px] font-bold text-[var(--fluent-secondary)] hover:text-[var(--fluent-text)] px-2 py-1 cursor-pointer transition"
+                  >
+                    关闭
+                  </button>
+                }
+              />
+              {issues.length > 0 && (
+                <div className="border border-amber-500/15 rounded-xl bg-amber-500/[0.03] overflow-hidden">
+                  <div className="divide-y divide-[var(--fluent-border)]">
+                    {issues.map((issue) => (
+                      <div key={issue.path} className="flex items-center gap-4 px-4 py-3.5">
+                        <Wrench className="w-4 h-4 text-amber-400 shrink-0" />
+                        <div className="min-w-0 flex-1">
+                          <div className="flex items-center gap-2">
+                            <h4 className="font-extrabold text-xs text-white truncate" title={issue.path}>
+                              {issue.path.split(/[\\/]/).pop()}
+                            </h4>
+                            <span className="text-[9px] bg-amber-500/10 border border-amber-500/20 text-amber-400 px-1.5 py-0.5 rounded font-black tracking-wide">
+                              {ISSUE_LABELS[issue.kind]}
+                            </span>
+                          </div>
+                          <p className="text-[9px] text-[var(--fluent-secondary)] truncate mt-0.5" title={issue.detail}>
+                            {issue.detail}
+                            {issue.url ? ` · ${issue.url}` : ""}
+                          </p>
+                        </div>
+                        <div className="flex items-center gap-1 shrink-0">
+                          {issue.actions.map((action) => (
+                            <button
+                              key={action}
+                              onClick={() => handleRepair(issue, action)}
+                              disabled={repairing !== null}
+                              className={`text-[10px] font-black px-3 py-1.5 rounded-lg border cursor-pointer transition disabled:opacity-50 disabled:cursor-not-allowed ${
+                                action === "forget"
+                                  ? "text-red-400 border-red-500/20 hover:bg-red-500/10"
+                                  : "bg-[var(--fluent-accent)]/10 hover:bg-[var(--fluent-accent)]/20 text-[var(--fluent-accent)] border-[var(--fluent-accent)]/20"
+                              }`}
+                            >
+                              {repairing === issue.path ? "处理中..." : ACTION_LABELS[action]}
+                            </button>
+                          ))}
+                        </div>
+                      </div>
+                    ))}
+                  </div>
+                </div>
+              )}
+            </div>
+          )}
+
           {/* ── Section 1: Installed Repos (已安装项目) ─── */}
           {completedRepos.length > 0 && (
             <div className="text-left flex flex-col">
@@ -235,6 +448,16 @@ export default function Library() {
                 title="已安装项目"
                 count={completedRepos.length}
                 subtitle="通过应用商店克隆或下载的 Git 源码仓库"
+                action={
+                  <button
+                    onClick={handleUpdateAll}
+                    disabled={updatingAll}
+                    className="flex items-center gap-1 text-[10px] font-black bg-[var(--fluent-accent)]/10 hover:bg-[var(--fluent-accent)]/20 text-[var(--fluent-accent)] border border-[var(--fluent-accent)]/20 px-3 py-1.5 rounded-lg cursor-pointer transition disabled:opacity-50 disabled:cursor-not-allowed"
+                  >
+                    <RotateCcw className={`w-3 h-3 ${updatingAll ? "animate-spin" : ""}`} />
+                    {updatingAll ? "正在更新..." : "全部更新"}
+                  </button>
+                }
               />
               <div className="border border-[var(--fluent-border)] rounded-xl bg-[var(--fluent-card)] overflow-hidden shadow-sm">
                 <div className="overflow-x-auto">
@@ -274,10 +497,17 @@ export default function Library() {
                             </div>
                           </td>
                           <td className="px-4 py-3">
-                            <span className="inline-flex items-center gap-1 px-2 py-0.5 rounded text-[9px] font-black bg-green-500/10 border border-green-500/20 text-green-400 uppercase tracking-wide">
-                              <CheckCircle2 className="w-2.5 h-2.5" />
-                              就绪
-                            </span>
+                            {availableUpdates.some((u) => u.kind === "repository" && u.url === repo.url) ? (
+                              <span className="inline-flex items-center gap-1 px-2 py-0.5 rounded text-[9px] font-black bg-amber-500/10 border border-amber-500/20 text-amber-400 uppercase tracking-wide">
+                                <RotateCcw className="w-2.5 h-2.5" />
+                                有更新
+                              </span>
+                            ) : (
+                              <span className="inline-flex items-center gap-1 px-2 py-0.5 rounded text-[9px] font-black bg-green-500/10 border border-green-500/20 text-green-400 uppercase tracking-wide">
+                                <CheckCircle2 className="w-2.5 h-2.5" />
+                                就绪
+                              </span>
+                            )}
                           </td>
                           <td className="px-4 py-3 font-mono text-[9px] text-zinc-400">
                             <span className="truncate max-w-[200px] block" title={repo.path}>
@@ -301,6 +531,9 @@ export default function Library() {
                               <ActionBtn title="拉取最新代码" onClick={(e) => handleUpdate(e, repo)} className="text-purple-400">
                                 <RotateCcw className="w-3.5 h-3.5" />
                               </ActionBtn>
+                              <ActionBtn title="回滚到更新前版本" onClick={(e) => handleRollback(e, repo)} className="text-amber-400">
+                                <Undo2 className="w-3.5 h-3.5" />
+                              </ActionBtn>
                               <ActionBtn title="卸载并删除" onClick={(e) => handleUninstall(e, repo)} className="text-red-500 hover:bg-red-500/10">
                                 <Trash2 className="w-3.5 h-3.5" />
                               </ActionBtn>
@@ -363,12 +596,7 @@ export default function Library() {
 
                       {/* Install button */}
                       <button
-                        onClick={async () => {
-                          const { invoke } = await import("@tauri-apps/api/core");
-                          invoke("run_installer", { filename: asset.filename }).catch((err: string) => {
-                            alert(`无法启动安装包：${err}`);
-                          });
-                        }}
+                        onClick={() => launchInstaller(asset.filename)}
                         className="flex items-center gap-1.5 text-[10px] font-black bg-[var(--fluent-accent)] hover:bg-[var(--fluent-accent-hover)] text-white px-3 py-1.5 rounded-lg cursor-pointer transition active:scale-95 shadow-sm shadow-blue-500/20"
                         title="启动安装"
                       >
@@ -520,10 +748,33 @@ export default function Library() {
                       <p className="text-[10px] text-[var(--fluent-secondary)] truncate mb-2">
                         {getStatusText(repo.status)}
                       </p>
-                      {/* Indeterminate progress bar */}
-                      <div className="w-full bg-zinc-800 rounded-full h-1 overflow-hidden relative">
-                        <div className="bg-[var(--fluent-accent)] h-full rounded-full w-1/3 absolute left-0 animate-progress-bar shadow shadow-blue-500/50" />
-                      </div>
+                      {repo.percent || repo.downloaded ? (
+                        <>
+                          <div className="flex justify-between text-[9px] text-[var(--fluent-secondary)] font-mono mb-1.5">
+                            <span>{repo.downloaded ? formatSize(repo.downloaded) : ""}</span>
+                            <span>
+                              {repo.percent ? `${repo.percent}%` : ""}
+                              {repo.total ? ` / ${formatSize(repo.total)}` : ""}
+                            </span>
+                          </div>
+                          {/* Determinate progress */}
+                          <div className="w-full bg-zinc-800 rounded-full h-1 overflow-hidden relative">
+                            {repo.percent ? (
+                              <div
+                                className="bg-[var(--fluent-accent)] h-full rounded-full transition-all duration-300 shadow shadow-blue-500/50"
+                                style={{ width: `${repo.percent}%` }}
+                              />
+                            ) : (
+                              <div className="bg-[var(--fluent-accent)] h-full rounded-full w-1/3 absolute left-0 animate-progress-bar shadow shadow-blue-500/50" />
+                            )}
+                          </div>
+                        </>
+                      ) : (
+                        /* Indeterminate progress bar */
+                        <div className="w-full bg-zinc-800 rounded-full h-1 overflow-hidden relative">
+                          <div className="bg-[var(--fluent-accent)] h-full rounded-full w-1/3 absolute left-0 animate-progress-bar shadow shadow-blue-500/50" />
+                        </div>
+                      )}
                     </div>
                     <button
                       onClick={(e) => handleUninstall(e, repo)}
@@ -562,9 +813,19 @@ export default function Library() {
                         <h4 className="font-extrabold text-xs text-white truncate" title={asset.filename}>
                           {asset.filename}
                         </h4>
-                        <span className="text-[9px] bg-green-500/10 border border-green-500/20 text-green-400 px-1.5 py-0.5 rounded font-black uppercase tracking-wide animate-pulse">
-                          下载中
-                        </span>
+                        {asset.status === "paused" || asset.status === "queued" || asset.status === "retrying" ? (
+                          <span className="text-[9px] bg-amber-500/10 border border-amber-500/20 text-amber-400 px-1.5 py-0.5 rounded font-black uppercase tracking-wide">
+                            {asset.status === "paused"
+                              ? "已暂停"
+                              : asset.status === "retrying"
+                                ? `重试中 (第 ${asset.attempt} 次)`
+                                : "排队中"}
+                          </span>
+                        ) : (
+                          <span className="text-[9px] bg-green-500/10 border border-green-500/20 text-green-400 px-1.5 py-0.5 rounded font-black uppercase tracking-wide animate-pulse">
+                            下载中
+                          </span>
+                        )}
                       </div>
                       <div className="flex justify-between text-[9px] text-[var(--fluent-secondary)] font-mono mb-1.5">
                         <span>{formatSize(asset.downloaded)}</span>
@@ -578,6 +839,17 @@ export default function Library() {
                         />
                       </div>
                     </div>
+                    <button
+                      onClick={async () => {
+                        const { invoke } = await import("@tauri-apps/api/core");
+                        const command = asset.status === "paused" ? "resume_download" : "pause_download";
+                        await invoke(command, { url: asset.url }).catch(() => {});
+                      }}
+                      className="p-2 border border-[var(--fluent-border)] bg-white/5 hover:bg-white/10 text-[var(--fluent-secondary)] rounded-lg cursor-pointer transition shrink-0 active:scale-95"
+                      title={asset.status === "paused" ? "继续下载" : "暂停下载"}
+                    >
+                      {asset.status === "paused" ? <Play className="w-4 h-4" /> : <Pause className="w-4 h-4" />}
+                    </button>
                     <button
                       onClick={async () => {
                         const { invoke } = await import("@tauri-apps/api/core");
@@ -607,12 +879,14 @@ function SectionHeader({
   count,
   subtitle,
   countColor = "text-[var(--fluent-accent)]",
+  action,
 }: {
   icon: React.ReactNode;
   title: string;
   count: number;
   subtitle: string;
   countColor?: string;
+  action?: React.ReactNode;
 }) {
   return (
     <div className="mb-3.5 flex items-start justify-between gap-4">
@@ -624,6 +898,7 @@ function SectionHeader({
         </h3>
         <p className="text-[10px] text-[var(--fluent-secondary)] mt-0.5 ml-6">{subtitle}</p>
       </div>
+      {action}
     </div>
   );
 }
@@ -3,6 +3,7 @@ import { useApp } from "@/context/AppContext";
 import { Search as SearchIcon, Star, AlertCircle, History, ArrowRight } from "lucide-react";
 import { cn } from "@/lib/utils";
 import { fetchAndAdaptUrlSource } from "@/lib/urlSourcesAdapter";
+import { forgeFetch } from "@/lib/forgeApi";
 import { loadAppsFromZipSource } from "@/lib/zipSourceLoader";
 import AppIcon from "@/components/AppIcon";
 
@@ -77,24 +78,19 @@ export default function Search() {
             ? (isGiteeSource ? "https://gitee.com/api/v5" : "https://api.github.com")
             : source.customEndpoint.trim();
 
-          const sourceToken = source.token || "";
-          
           const sourceHeaders: Record<string, string> = {
             Accept: isGiteeSource ? "application/json" : "application/vnd.github.v3+json",
           };
           if (!isGiteeSource) {
             sourceHeaders["X-GitHub-Api-Version"] = source.apiVersion || "2026-03-10";
           }
-          if (sourceToken.trim() && !isGiteeSource) {
-            sourceHeaders.Authorization = `Bearer ${sourceToken.trim()}`;
-          }
 
           try {
-            let url = `${base}/repos/${directRepoPath}`;
-            if (isGiteeSource && sourceToken.trim()) {
-              url += `?access_token=${encodeURIComponent(sourceToken.trim())}`;
-            }
-            const tempRes = await fetch(url, { headers: sourceHeaders });
+            // The backend adds the source's stored token
+            const tempRes = await forgeFetch(`${base}/repos/${directRepoPath}`, {
+              headers: sourceHeaders,
+              kind: isGiteeSource ? "gitee" : "github",
+            });
             if (tempRes.ok) {
               const data = await tempRes.json();
               return [{
@@ -199,25 +195,17 @@ export default function Search() {
             ? (isGiteeSource ? "https://gitee.com/api/v5" : "https://api.github.com")
             : source.customEndpoint.trim();
 
-          const sourceToken = source.token || "";
-          
           const sourceHeaders: Record<string, string> = {
             Accept: isGiteeSource ? "application/json" : "application/vnd.github.v3+json",
           };
           if (!isGiteeSource) {
             sourceHeaders["X-GitHub-Api-Version"] = source.apiVersion || "2026-03-10";
           }
-          if (sourceToken.trim() && !isGiteeSource) {
-            sourceHeaders.Authorization = `Bearer ${sourceToken.trim()}`;
-          }
 
           if (isGiteeSource) {
-            let url = `${base}/search/repositories?q=${encodeURIComponent(finalQuery)}&order=desc&per_page=30`;
-            if (sourceToken.trim()) {
-              url += `&access_token=${encodeURIComponent(sourceToken.trim())}`;
-            }
+            const url = `${base}/search/repositories?q=${encodeURIComponent(finalQuery)}&order=desc&per_page=30`;
             searchPromises.push(
-              fetch(url, { headers: sourceHeaders })
+              forgeFetch(url, { headers: sourceHeaders, kind: "gitee" })
                 .then(async r => {
                   if (!r.ok) return [];
                   const data = await r.json();
@@ -277,7 +265,10 @@ export default function Search() {
             }
           } else {
             searchPromises.push(
-              fetch(`${base}/search/repositories?q=${encodeURIComponent(finalQuery)}`, { headers: sourceHeaders })
+              forgeFetch(`${base}/search/repositories?q=${encodeURIComponent(finalQuery)}`, {
+                headers: sourceHeaders,
+                kind: "github",
+              })
                 .then(async r => {
                   if (!r.ok) return [];
                   const data = await r.json();
@@ -1,6 +1,7 @@
 import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { useApp } from "@/context/AppContext";
-import { Globe, Wifi, User, Shield, Bell, ChevronRight, Folder, Key, Terminal, Info, CheckCircle2, Trash } from "lucide-react";
+import { Globe, Wifi, User, Shield, Bell, ChevronRight, Folder, Key, Terminal, Info, CheckCircle2, Trash, Gauge, RefreshCw } from "lucide-react";
 import { cn } from "@/lib/utils";
 
 // Custom small Windows-style toggle switch matching the mockup
@@ -36,9 +37,11 @@ export default function Settings() {
   const {
     downloadDir,
     setDownloadDir,
-    githubToken,
+    hasGithubToken,
     setGithubToken,
     gitInstalled,
+    gitCliInstalled,
+    gitLfsInstalled,
     checkGit,
     clearHistory,
     theme,
@@ -55,14 +58,15 @@ export default function Settings() {
     setBgOpacity,
     bgBlur,
     setBgBlur,
-    giteeToken,
+    hasGiteeToken,
     setGiteeToken
   } = useApp();
 
   // Inputs state
   const [dirInput, setDirInput] = useState(downloadDir);
-  const [tokenInput, setTokenInput] = useState(githubToken);
-  const [giteeTokenInput, setGiteeTokenInput] = useState(giteeToken);
+  // Saved tokens stay in the backend credential store; the inputs only take new ones
+  const [tokenInput, setTokenInput] = useState("");
+  const [giteeTokenInput, setGiteeTokenInput] = useState("");
   
   // Custom mock configuration states from layout screenshot, loaded from localStorage
   const [language, setLanguage] = useState(
@@ -78,6 +82,16 @@ export default function Settings() {
     localStorage.getItem("git_store_update_notif") === "true" ? true : false
   );
 
+  const [uninstallToTrash, setUninstallToTrash] = useState(
+    localStorage.getItem("git_store_uninstall_to_trash") === "true"
+  );
+
+  const [rateLimitInput, setRateLimitInput] = useState(
+    localStorage.getItem("git_store_rate_limit_kbps") || "0"
+  );
+  const [updateIntervalInput, setUpdateIntervalInput] = useState(
+    localStorage.getItem("git_store_update_interval_minutes") || "360"
+  );
   const [saveSuccess, setSaveSuccess] = useState(false);
   const [rechecking, setRechecking] = useState(false);
 
@@ -89,14 +103,34 @@ export default function Settings() {
   const handleSave = (e: React.FormEvent) => {
     e.preventDefault();
     setDownloadDir(dirInput.trim());
-    setGithubToken(tokenInput.trim());
-    setGiteeToken(giteeTokenInput.trim());
+    // An empty input keeps the saved token; use the clear buttons to remove one
+    if (tokenInput.trim()) {
+      setGithubToken(tokenInput.trim());
+      setTokenInput("");
+    }
+    if (giteeTokenInput.trim()) {
+      setGiteeToken(giteeTokenInput.trim());
+      setGiteeTokenInput("");
+    }
     
     // Save other config states to localStorage
     localStorage.setItem("git_store_language", language);
     localStorage.setItem("git_store_wifi_only", String(wifiOnly));
     localStorage.setItem("git_store_push_enabled", String(pushEnabled));
     localStorage.setItem("git_store_update_notif", String(updateNotif));
+    localStorage.setItem("git_store_uninstall_to_trash", String(uninstallToTrash));
+
+    // Global download bandwidth limit, entered in KB/s (0 = unlimited)
+    const kbps = Math.max(0, Math.floor(Number(rateLimitInput) || 0));
+    localStorage.setItem("git_store_rate_limit_kbps", String(kbps));
+    setRateLimitInput(String(kbps));
+    invoke("set_download_rate_limit", { bytesPerSecond: kbps * 1024 }).catch(() => {});
+
+    // Background update check interval in minutes (0 = off, at most 30 days)
+    const minutes = Math.min(43200, Math.max(0, Math.floor(Number(updateIntervalInput) || 0)));
+    localStorage.setItem("git_store_update_interval_minutes", String(minutes));
+    setUpdateIntervalInput(String(minutes));
+    invoke("set_update_check_interval", { minutes }).catch(() => {});
 
     setSaveSuccess(true);
     setTimeout(() => setSaveSuccess(false), 2000);
@@ -204,6 +238,18 @@ export default function Settings() {
                 <Toggle checked={wifiOnly} onChange={() => setWifiOnly(!wifiOnly)} />
               </div>
             </div>
+
+            {/* Uninstall into the system trash */}
+            <div className="flex items-center justify-between p-3 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl shadow-sm">
+              <div className="flex items-center gap-3">
+                <Trash className="w-4 h-4 text-[var(--fluent-secondary)]" />
+                <span className="text-xs font-semibold">卸载方式</span>
+              </div>
+              <div className="flex items-center gap-3">
+                <span className="text-[11px] text-[var(--fluent-secondary)] font-semibold">移至回收站而不是永久删除</span>
+                <Toggle checked={uninstallToTrash} onChange={() => setUninstallToTrash(!uninstallToTrash)} />
+              </div>
+            </div>
           </div>
         </div>
 
@@ -472,44 +518,102 @@ export default function Settings() {
               />
             </div>
 
-            {/* GitHub Token */}
+            {/* Download bandwidth limit */}
             <div className="p-4 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl space-y-2.5 shadow-sm">
               <div className="flex items-center gap-2.5">
-                <Key className="w-4 h-4 text-yellow-500" />
-                <span className="text-xs font-semibold">GitHub 个人访问令牌 (Token)</span>
+                <Gauge className="w-4 h-4 text-violet-400" />
+                <span className="text-xs font-semibold">下载限速 (KB/s，0 为不限速)</span>
               </div>
               <input
-                type="password"
-                value={tokenInput}
-                onChange={(e) => setTokenInput(e.target.value)}
-                placeholder="ghp_xxxxxxxxxxxxxxxxxxxxxx 或 github_pat_xxxx"
+                type="number"
+                min={0}
+                value={rateLimitInput}
+                onChange={(e) => setRateLimitInput(e.target.value)}
+                placeholder="0"
                 className="w-full bg-[rgba(128,128,128,0.05)] border border-[var(--fluent-border)] rounded-lg py-2 px-3 text-xs font-mono focus:outline-none focus:ring-1 focus:ring-[var(--fluent-accent)] focus:border-[var(--fluent-accent)] transition-all"
               />
             </div>
 
-            {/* Gitee Token */}
+            {/* Background update checks */}
             <div className="p-4 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl space-y-2.5 shadow-sm">
               <div className="flex items-center gap-2.5">
-                <Key className="w-4 h-4 text-red-500" />
-                <span className="text-xs font-semibold">Gitee (码云) 个人访问令牌 (Token)</span>
+                <RefreshCw className="w-4 h-4 text-emerald-400" />
+                <span className="text-xs font-semibold">自动检查更新间隔 (分钟，0 为关闭)</span>
               </div>
               <input
-                type="password"
-                value={giteeTokenInput}
-                onChange={(e) => setGiteeTokenInput(e.target.value)}
-                placeholder="请输入您的 Gitee 私人访问令牌"
+                type="number"
+                min={0}
+                value={updateIntervalInput}
+                onChange={(e) => setUpdateIntervalInput(e.target.value)}
+                placeholder="360"
                 className="w-full bg-[rgba(128,128,128,0.05)] border border-[var(--fluent-border)] rounded-lg py-2 px-3 text-xs font-mono focus:outline-none focus:ring-1 focus:ring-[var(--fluent-accent)] focus:border-[var(--fluent-accent)] transition-all"
               />
             </div>
 
+            {/* GitHub Token */}
+            <div className="p-4 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl space-y-2.5 shadow-sm">
+              <div className="flex items-center gap-2.5">
+                <Key className="w-4 h-4 text-yellow-500" />
+                <span className="text-xs font-semibold">GitHub 个人访问令牌 (Token)</span>
+              </div>
+              <div className="flex items-center gap-2">
+                <input
+                  type="password"
+                  value={tokenInput}
+                  onChange={(e) => setTokenInput(e.target.value)}
+                  placeholder={hasGithubToken ? "已配置令牌，留空则保持不变" : "ghp_xxxxxxxxxxxxxxxxxxxxxx 或 github_pat_xxxx"}
+                  className="w-full bg-[rgba(128,128,128,0.05)] border border-[var(--fluent-border)] rounded-lg py-2 px-3 text-xs font-mono focus:outline-none focus:ring-1 focus:ring-[var(--fluent-accent)] focus:border-[var(--fluent-accent)] transition-all"
+                />
+                {hasGithubToken && (
+                  <button
+                    type="button"
+                    onClick={() => setGithubToken("")}
+                    className="shrink-0 px-3 py-2 rounded-lg border border-[var(--fluent-border)] text-xs font-semibold hover:bg-[rgba(128,128,128,0.1)] transition-all"
+                  >
+                    清除
+                  </button>
+                )}
+              </div>
+            </div>
+
+            {/* Gitee Token */}
+            <div className="p-4 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl space-y-2.5 shadow-sm">
+              <div className="flex items-center gap-2.5">
+                <Key className="w-4 h-4 text-red-500" />
+                <span className="text-xs font-semibold">Gitee (码云) 个人访问令牌 (Token)</span>
+              </div>
+              <div className="flex items-center gap-2">
+                <input
+                  type="password"
+                  value={giteeTokenInput}
+                  onChange={(e) => setGiteeTokenInput(e.target.value)}
+                  placeholder={hasGiteeToken ? "已配置令牌，留空则保持不变" : "请输入您的 Gitee 私人访问令牌"}
+                  className="w-full bg-[rgba(128,128,128,0.05)] border border-[var(--fluent-border)] rounded-lg py-2 px-3 text-xs font-mono focus:outline-none focus:ring-1 focus:ring-[var(--fluent-accent)] focus:border-[var(--fluent-accent)] transition-all"
+                />
+                {hasGiteeToken && (
+                  <button
+                    type="button"
+                    onClick={() => setGiteeToken("")}
+                    className="shrink-0 px-3 py-2 rounded-lg border border-[var(--fluent-border)] text-xs font-semibold hover:bg-[rgba(128,128,128,0.1)] transition-all"
+                  >
+                    清除
+                  </button>
+                )}
+              </div>
+            </div>
+
             {/* Git verification check */}
             <div className="p-4 border border-[var(--fluent-border)] bg-[var(--fluent-card)] rounded-xl flex items-center justify-between gap-4 shadow-sm">
               <div className="flex items-center gap-3">
                 <Terminal className="w-4 h-4 text-emerald-400 shrink-0" />
                 <div className="text-left">
-                  <h4 className="text-xs font-bold">Git CLI 运行状态</h4>
+                  <h4 className="text-xs font-bold">Git 运行状态</h4>
                   <p className="text-[10px] text-[var(--fluent-secondary)] mt-0.5 leading-snug">
-                    {gitInstalled ? "系统检测到本地安装了 Git，支持完整的仓库拉取及自动更新操作。" : "系统未找到 Git，应用将自动降级为 ZIP 压缩包下载模式。"}
+                    {!gitInstalled
+                      ? "Git 支持不可用，应用将自动降级为 ZIP 压缩包下载模式。"
+                      : gitCliInstalled
+                        ? `应用内置 Git 支持，并检测到本地 Git${gitLfsInstalled ? " 与 Git LFS" : "（未安装 Git LFS，将跳过 LFS 文件）"}，可完整拉取仓库并自动更新。`
+                        : "应用内置 Git 支持，可完整拉取仓库并自动更新；未检测到本地 Git，稀疏检出、LFS 和 SSH 地址不可用。"}
                   </p>
                 </div>
               </div>